@@ -0,0 +1,83 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::NaiveDateTime;
+use crate::error::NwError;
+use crate::store;
+
+/// Audit log next to the portfolio file, one line per saved change.
+pub fn log_path() -> Result<PathBuf, NwError> {
+    Ok(store::portfolio_path()?.with_file_name("audit.log"))
+}
+
+/// One log line: local timestamp, a tab, and the change summary (e.g. "asset add vti").
+/// Newlines in the summary are flattened so every entry stays on one line.
+pub fn format_entry(timestamp: NaiveDateTime, summary: &str) -> String {
+    let summary = summary.replace(['\n', '\r'], " ");
+    format!("{}\t{}\n", timestamp.format("%Y-%m-%d %H:%M:%S"), summary)
+}
+
+/// Record a change that was just saved. Best effort: a log that can't be written only
+/// produces a warning, since the change itself already succeeded.
+pub fn append(summary: &str) {
+    let entry = format_entry(chrono::Local::now().naive_local(), summary);
+    if let Err(e) = write_entry(&entry) {
+        eprintln!("warning: could not write the audit log: {e}");
+    }
+}
+
+fn write_entry(entry: &str) -> Result<(), NwError> {
+    let path = log_path()?;
+    let write_error = |source| NwError::WriteFile { path: path.display().to_string(), source };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(write_error)?;
+    file.write_all(entry.as_bytes()).map_err(write_error)
+}
+
+/// The last `count` lines of the audit log; empty if nothing has been logged yet.
+pub fn tail(count: usize) -> Result<Vec<String>, NwError> {
+    let path = log_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| NwError::ReadFile {
+        path: path.display().to_string(),
+        source: e,
+    })?;
+    Ok(last_lines(&contents, count))
+}
+
+fn last_lines(contents: &str, count: usize) -> Vec<String> {
+    let lines: Vec<&str> = contents.lines().collect();
+    lines[lines.len().saturating_sub(count)..].iter().map(|l| l.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 2, 28).unwrap().and_hms_opt(h, m, s).unwrap()
+    }
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(
+            format_entry(at(9, 5, 3), "snapshot edit 2025-02-28"),
+            "2025-02-28 09:05:03\tsnapshot edit 2025-02-28\n"
+        );
+        assert_eq!(format_entry(at(23, 0, 0), "a\nb"), "2025-02-28 23:00:00\ta b\n");
+    }
+
+    #[test]
+    fn test_last_lines() {
+        let log = "one\ntwo\nthree\n";
+        assert_eq!(last_lines(log, 2), vec!["two", "three"]);
+        assert_eq!(last_lines(log, 10).len(), 3);
+        assert!(last_lines("", 5).is_empty());
+    }
+}
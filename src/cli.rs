@@ -1,12 +1,48 @@
+use std::str::FromStr;
 use clap::{Args, Parser, Subcommand};
+use crate::error::NwError;
+use crate::model::OutputFormat;
+
+/// Environment variable that acts like `--read-only` when set to 1, true, or yes.
+pub const READ_ONLY_ENV: &str = "NW_READONLY";
 
 #[derive(Parser)]
 #[command(name = "nw", about = "Net worth tracker CLI")]
 pub struct Cli {
+    /// Output format: text or json (json reports errors as JSON on stderr)
+    #[arg(long, default_value = "text", global = true)]
+    pub format: String,
+    /// Disable colored output (also honors NO_COLOR)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Refuse any command that would modify the portfolio (also honors NW_READONLY=1)
+    #[arg(long, global = true)]
+    pub read_only: bool,
+    /// Read typed values and rates with a comma as the decimal mark ("1.234,56")
+    #[arg(long, global = true)]
+    pub decimal_comma: bool,
     #[command(subcommand)]
     pub command: Command,
 }
 
+impl Cli {
+    /// The `--format` output format, given before or after the subcommand.
+    pub fn output_format(&self) -> Result<OutputFormat, NwError> {
+        OutputFormat::from_str(&self.format)
+    }
+
+    /// Fail with `ReadOnly` if read-only mode is on and the command would write.
+    /// `env_value` is the value of `NW_READONLY`, if set.
+    pub fn check_writable(&self, env_value: Option<&str>) -> Result<(), NwError> {
+        let env_on = env_value
+            .is_some_and(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"));
+        if (self.read_only || env_on) && self.command.is_mutating() {
+            return Err(NwError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Manage assets
@@ -17,6 +53,64 @@ pub enum Command {
     Show(ShowArgs),
     /// Show net worth history over a time range
     History(HistoryArgs),
+    /// Print the latest USD total, e.g. for a shell prompt
+    Total(TotalArgs),
+    /// Share of net worth held in each currency (FX exposure)
+    Exposure(ExposureArgs),
+    /// Money-weighted annual return (XIRR) from contributions and the latest total
+    Xirr(XirrArgs),
+    /// Export data for use in other tools
+    Export(ExportArgs),
+    /// Manage currencies across all assets and snapshots
+    Currency(CurrencyArgs),
+    /// Manage settings stored in the portfolio file
+    Config(ConfigArgs),
+    /// Clean up casing and whitespace in stored data (as add/edit would) and re-save
+    Normalize,
+    /// Validate the portfolio file quietly for CI: exit 4 and list problems if any
+    Check,
+    /// Encrypt the portfolio file with a passphrase (replaces portfolio.json)
+    #[cfg(feature = "encryption")]
+    Encrypt,
+    /// Print the JSON Schema of the portfolio file
+    #[cfg(feature = "schema")]
+    Schema,
+    /// Print the most recent entries of the audit log of changes
+    Log(LogArgs),
+    /// Print the location of the portfolio file
+    Path,
+    /// Print version, data location, and portfolio size (for bug reports)
+    Version,
+}
+
+impl Command {
+    /// Whether the command writes the portfolio file.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Command::Asset(a) => {
+                !matches!(a.subcommand, AssetSubcommand::List | AssetSubcommand::Show(_))
+            }
+            Command::Snapshot(a) => !matches!(
+                a.subcommand,
+                SnapshotSubcommand::List(_) | SnapshotSubcommand::Export(_)
+            ),
+            Command::Currency(_) | Command::Config(_) | Command::Normalize => true,
+            #[cfg(feature = "encryption")]
+            Command::Encrypt => true,
+            Command::Show(_)
+            | Command::Total(_)
+            | Command::History(_)
+            | Command::Exposure(_)
+            | Command::Xirr(_)
+            | Command::Export(_)
+            | Command::Check
+            | Command::Log(_)
+            | Command::Path
+            | Command::Version => false,
+            #[cfg(feature = "schema")]
+            Command::Schema => false,
+        }
+    }
 }
 
 #[derive(Args)]
@@ -35,18 +129,27 @@ pub enum AssetSubcommand {
     Remove(AssetRemoveArgs),
     /// List all assets
     List,
+    /// Show one asset in full, including its note
+    Show(AssetShowArgs),
 }
 
+/// Fields left out are asked for interactively.
 #[derive(Args)]
 pub struct AssetAddArgs {
     #[arg(long)]
-    pub id: String,
+    pub id: Option<String>,
+    #[arg(long)]
+    pub name: Option<String>,
     #[arg(long)]
-    pub name: String,
+    pub category: Option<String>,
     #[arg(long)]
-    pub category: String,
+    pub currency: Option<String>,
+    /// Free-form description of the asset
     #[arg(long)]
-    pub currency: String,
+    pub note: Option<String>,
+    /// Purchase cost in USD, for unrealized gain in `show`
+    #[arg(long)]
+    pub cost_basis: Option<String>,
 }
 
 #[derive(Args)]
@@ -59,12 +162,40 @@ pub struct AssetEditArgs {
     pub category: Option<String>,
     #[arg(long)]
     pub currency: Option<String>,
+    /// Free-form description of the asset (`none` removes it)
+    #[arg(long)]
+    pub note: Option<String>,
+    /// Purchase cost in USD, for unrealized gain in `show` (`none` removes it)
+    #[arg(long)]
+    pub cost_basis: Option<String>,
+    /// Lowest plausible native value; entering less asks first (`none` removes it)
+    #[arg(long)]
+    pub min_value: Option<String>,
+    /// Highest plausible native value; entering more asks first (`none` removes it)
+    #[arg(long)]
+    pub max_value: Option<String>,
+    /// Derive the value as a share of another asset instead of entering it, e.g.
+    /// house=40% (`none` removes it)
+    #[arg(long)]
+    pub percent_of: Option<String>,
+    /// Position within its category in `show`, lowest first (`none` removes it)
+    #[arg(long, allow_negative_numbers = true)]
+    pub order: Option<String>,
+}
+
+#[derive(Args)]
+pub struct AssetShowArgs {
+    #[arg(long)]
+    pub id: String,
 }
 
 #[derive(Args)]
 pub struct AssetRemoveArgs {
     #[arg(long)]
     pub id: String,
+    /// Skip the confirmation prompt
+    #[arg(long, short)]
+    pub yes: bool,
 }
 
 #[derive(Args)]
@@ -76,32 +207,466 @@ pub struct SnapshotArgs {
 #[derive(Subcommand)]
 pub enum SnapshotSubcommand {
     /// Add a new snapshot
-    Add(SnapshotDateArg),
+    Add(SnapshotAddArgs),
     /// Edit an existing snapshot
-    Edit(SnapshotDateArg),
+    Edit(SnapshotEntryArgs),
     /// List all snapshots
-    List,
+    List(SnapshotListArgs),
+    /// Create snapshots from a CSV of balances (date, asset ids, optional rate:XXX columns)
+    Import(SnapshotImportArgs),
+    /// Turn a draft snapshot into a regular one
+    Finalize(SnapshotFinalizeArgs),
+    /// Move a snapshot to another date
+    Rename(SnapshotRenameArgs),
+    /// Delete a snapshot
+    Remove(SnapshotRemoveArgs),
+    /// Protect a snapshot from edit, rename, and remove (they then need --force)
+    Lock(SnapshotLockArgs),
+    /// Allow a locked snapshot to be changed again
+    Unlock(SnapshotLockArgs),
+    /// Write one snapshot and the assets it references as JSON
+    Export(SnapshotExportArgs),
+    /// Add a snapshot written by `snapshot export`, creating missing assets
+    ImportOne(SnapshotImportOneArgs),
+}
+
+#[derive(Args)]
+pub struct SnapshotExportArgs {
+    #[arg(long)]
+    pub date: String,
+    /// Write to this file instead of stdout
+    #[arg(long)]
+    pub out: Option<std::path::PathBuf>,
+    /// Replace asset names and scale all values by a random factor before exporting
+    #[arg(long)]
+    pub anonymize: bool,
+}
+
+#[derive(Args)]
+pub struct SnapshotImportOneArgs {
+    /// JSON file from `snapshot export`
+    #[arg(long = "in")]
+    pub input: std::path::PathBuf,
 }
 
 #[derive(Args)]
-pub struct SnapshotDateArg {
+pub struct SnapshotRenameArgs {
+    /// Current date of the snapshot
+    #[arg(long)]
+    pub from: String,
+    /// New date for the snapshot
+    #[arg(long)]
+    pub to: String,
+    /// Rename even if the snapshot is locked
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct SnapshotRemoveArgs {
     #[arg(long)]
     pub date: String,
+    /// Skip the confirmation prompt
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Remove even if the snapshot is locked
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct SnapshotLockArgs {
+    #[arg(long)]
+    pub date: String,
+}
+
+#[derive(Args)]
+pub struct SnapshotFinalizeArgs {
+    #[arg(long)]
+    pub date: String,
+}
+
+#[derive(Args)]
+pub struct SnapshotImportArgs {
+    /// CSV file with a header row: date,<asset id>...,rate:<CURRENCY>...
+    #[arg(long = "in")]
+    pub input: std::path::PathBuf,
+    /// Dates run across the header row and each asset or rate:<CURRENCY> has its own row
+    #[arg(long)]
+    pub transposed: bool,
+}
+
+#[derive(Args)]
+pub struct SnapshotAddArgs {
+    #[arg(long)]
+    pub date: String,
+    /// Enter each asset's value as dated lots
+    #[arg(long)]
+    pub lots: bool,
+    /// Reject (instead of asking about) a date within the minimum snapshot interval
+    #[arg(long)]
+    pub strict: bool,
+    /// Save as a draft, left out of `show` and `history` until finalized
+    #[arg(long)]
+    pub draft: bool,
+    /// Event since the previous snapshot as KIND:AMOUNT in USD, e.g. deposit:5000 (repeatable)
+    #[arg(long = "event", allow_hyphen_values = true)]
+    pub events: Vec<String>,
+    /// Free-form remark about the snapshot
+    #[arg(long)]
+    pub note: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SnapshotEntryArgs {
+    #[arg(long)]
+    pub date: String,
+    /// Enter each asset's value as dated lots
+    #[arg(long)]
+    pub lots: bool,
+    /// Replace the snapshot's events with these KIND:AMOUNT events (repeatable)
+    #[arg(long = "event", allow_hyphen_values = true)]
+    pub events: Vec<String>,
+    /// Replace the snapshot's note (an empty note removes it)
+    #[arg(long)]
+    pub note: Option<String>,
+    /// Edit even if the snapshot is locked
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct SnapshotListArgs {
+    /// Sort order by date: asc or desc
+    #[arg(long, default_value = "asc")]
+    pub sort: String,
+    /// Show only the N most recent snapshots
+    #[arg(long)]
+    pub limit: Option<usize>,
 }
 
 #[derive(Args)]
 pub struct ShowArgs {
+    /// Snapshot by position from the end: -1 is the latest, -2 the one before, ...
+    #[arg(allow_negative_numbers = true, conflicts_with = "date")]
+    pub index: Option<i64>,
     /// Show snapshot for a specific date (default: latest)
     #[arg(long)]
     pub date: Option<String>,
     /// Filter display to one category
     #[arg(long)]
     pub category: Option<String>,
+    /// Hide holdings worth less than this many USD (still counted in totals)
+    #[arg(long)]
+    pub min_value: Option<f64>,
+    /// strftime pattern for displayed dates, e.g. "%b %d, %Y" (default: ISO)
+    #[arg(long)]
+    pub date_format: Option<String>,
+    /// Flag holdings above this share of the total, e.g. 25%
+    #[arg(long)]
+    pub highlight_above: Option<String>,
+    /// Also value a past snapshot at the latest snapshot's exchange rates
+    #[arg(long)]
+    pub at_latest_rates: bool,
+    /// Show holdings with a missing rate as excluded instead of failing
+    #[arg(long)]
+    pub lenient: bool,
+    /// Add a column with each holding's change since the snapshot on this date
+    #[arg(long)]
+    pub compare: Option<String>,
+    /// With --category, show each holding's share of that category's subtotal
+    #[arg(long, requires = "category")]
+    pub allocation_within: bool,
+    /// Group the allocation block by category (default) or currency
+    #[arg(long, conflicts_with = "allocation_within")]
+    pub allocation_by: Option<String>,
+    /// List assets absent from the snapshot as zero rows
+    #[arg(long)]
+    pub show_missing: bool,
+    /// List entries for deleted assets under UNKNOWN with their native amount. They have no
+    /// USD value, so the total, allocation and goals leave them out, as a footer notes
+    #[arg(long)]
+    pub show_unknown: bool,
+    /// Leave out the allocation block
+    #[arg(long)]
+    pub no_allocation: bool,
+    /// Print only the allocation block, without the category tables and totals
+    #[arg(long, conflicts_with = "no_allocation")]
+    pub allocation_only: bool,
+    /// Exit with status 3 instead of 0 when there are no snapshots
+    #[arg(long)]
+    pub fail_on_empty: bool,
+    /// Let draft snapshots be picked as the latest or by index
+    #[arg(long)]
+    pub include_drafts: bool,
+    /// Round displayed USD values and totals to the nearest N, e.g. 100 or 1000
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub round_to: Option<u64>,
+    /// Reload and redraw every SECONDS (default 5) until Ctrl-C; terminals only
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub watch: Option<u64>,
 }
 
 #[derive(Args)]
 pub struct HistoryArgs {
-    /// Time range: 1M, 6M, 1Y, 5Y, ALL
+    /// Time range: 1M, 6M, 1Y, 5Y, YTD, ALL
+    #[arg(long, required_unless_present = "since_last")]
+    pub range: Option<String>,
+    /// Only compare the two most recent snapshots, with a per-category breakdown
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "range", "category", "attribution", "annualized", "drawdown", "events",
+            "constant_latest_rates", "note_contains", "explain", "plot",
+        ]
+    )]
+    pub since_last: bool,
+    /// Only count assets in one category
+    #[arg(long)]
+    pub category: Option<String>,
+    /// Split each change into holdings-driven and exchange-rate-driven parts
+    #[arg(long, conflicts_with = "category")]
+    pub attribution: bool,
+    /// Add each period's change annualized over the days since the previous snapshot
     #[arg(long)]
+    pub annualized: bool,
+    /// Report the largest peak-to-trough decline in the range
+    #[arg(long)]
+    pub drawdown: bool,
+    /// Split each change into event-driven (deposits, withdrawals, ...) and organic parts
+    #[arg(long, conflicts_with = "category")]
+    pub events: bool,
+    /// Value every snapshot at the latest snapshot's exchange rates, hiding FX moves
+    #[arg(long, conflicts_with = "attribution")]
+    pub constant_latest_rates: bool,
+    /// strftime pattern for displayed dates, e.g. "%b %d, %Y" (default: ISO)
+    #[arg(long)]
+    pub date_format: Option<String>,
+    /// Exit with status 3 instead of 0 when no snapshots fall in the range
+    #[arg(long)]
+    pub fail_on_empty: bool,
+    /// Count draft snapshots too
+    #[arg(long)]
+    pub include_drafts: bool,
+    /// Only list snapshots whose note contains this text (case-insensitive); changes are
+    /// then measured between the listed snapshots
+    #[arg(long)]
+    pub note_contains: Option<String>,
+    /// Before the table, print the range cutoff and which snapshots were included or left out
+    #[arg(long)]
+    pub explain: bool,
+    /// Draw an ASCII line chart of the totals instead of the table
+    #[arg(long, conflicts_with_all = ["annualized", "attribution", "events"])]
+    pub plot: bool,
+}
+
+#[derive(Args)]
+pub struct TotalArgs {
+    /// Compact form with the change since the previous snapshot: "$128,430 ▲ +2.3%"
+    #[arg(long)]
+    pub oneline: bool,
+    /// Let a draft snapshot count as the latest
+    #[arg(long)]
+    pub include_drafts: bool,
+}
+
+#[derive(Args)]
+pub struct ExposureArgs {
+    /// Snapshot date (default: latest)
+    #[arg(long)]
+    pub date: Option<String>,
+    /// strftime pattern for displayed dates, e.g. "%b %d, %Y" (default: ISO)
+    #[arg(long)]
+    pub date_format: Option<String>,
+}
+
+#[derive(Args)]
+pub struct XirrArgs {
+    /// Dated contribution as YYYY-MM-DD=AMOUNT in USD (repeatable; negative for withdrawals)
+    #[arg(long = "contribution", required = true)]
+    pub contributions: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// What to export: allocation-csv, or html for a report of the latest snapshot
+    #[arg(long)]
+    pub kind: String,
+    /// Time range (of the history in an html report): 1M, 6M, 1Y, 5Y, YTD, ALL
+    #[arg(long, default_value = "ALL")]
     pub range: String,
+    /// Write to this file instead of stdout
+    #[arg(long)]
+    pub out: Option<std::path::PathBuf>,
+    /// Replace asset names and scale all values by a random factor before exporting
+    #[arg(long)]
+    pub anonymize: bool,
+}
+
+#[derive(Args)]
+pub struct LogArgs {
+    /// Number of entries to print
+    #[arg(long, default_value_t = 20)]
+    pub lines: usize,
+}
+
+#[derive(Args)]
+pub struct CurrencyArgs {
+    #[command(subcommand)]
+    pub subcommand: CurrencySubcommand,
+}
+
+#[derive(Subcommand)]
+pub enum CurrencySubcommand {
+    /// Rename a currency code on every asset and clean up snapshot rates
+    Rename(CurrencyRenameArgs),
+}
+
+#[derive(Args)]
+pub struct CurrencyRenameArgs {
+    #[arg(long)]
+    pub from: String,
+    #[arg(long)]
+    pub into: String,
+}
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub subcommand: ConfigSubcommand,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigSubcommand {
+    /// Set decimal places used for a currency's native values
+    Decimals(ConfigDecimalsArgs),
+    /// Only confirm removals that affect at least this many snapshots
+    ConfirmThreshold(ConfigConfirmThresholdArgs),
+    /// Color a category's header in `show`, e.g. crypto=magenta (color `none` clears it)
+    CategoryColor(ConfigCategoryColorArgs),
+    /// USD target for a category, e.g. crypto=20000 (amount `none` clears it)
+    CategoryGoal(ConfigCategoryGoalArgs),
+    /// Month the fiscal year starts in (used by the YTD range)
+    FiscalYearStart(ConfigFiscalYearStartArgs),
+    /// Snapshot `show` displays by default: latest or latest-complete
+    DefaultShow(ConfigDefaultShowArgs),
+    /// Ask before adding a snapshot this close to an existing one
+    MinSnapshotInterval(ConfigMinSnapshotIntervalArgs),
+    /// Which rate wins when a manual and a fetched rate disagree
+    RatePolicy(ConfigRatePolicyArgs),
+    /// Round stored exchange rates to a number of significant figures
+    RateSigFigs(ConfigRateSigFigsArgs),
+}
+
+#[derive(Args)]
+pub struct ConfigRateSigFigsArgs {
+    /// Significant figures to keep, 1-15 (omit to store rates as entered)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=15))]
+    pub figures: Option<u32>,
+}
+
+#[derive(Args)]
+pub struct ConfigRatePolicyArgs {
+    /// prefer-manual (default), prefer-api, or average
+    pub policy: String,
+}
+
+#[derive(Args)]
+pub struct ConfigMinSnapshotIntervalArgs {
+    /// Minimum days between snapshots (omit to allow any spacing)
+    #[arg(long)]
+    pub days: Option<u32>,
+}
+
+#[derive(Args)]
+pub struct ConfigDefaultShowArgs {
+    /// latest (default) or latest-complete (newest snapshot with every asset filled)
+    pub mode: String,
+}
+
+#[derive(Args)]
+pub struct ConfigFiscalYearStartArgs {
+    /// 1 = January (default) … 12 = December
+    #[arg(long)]
+    pub month: u32,
+}
+
+#[derive(Args)]
+pub struct ConfigCategoryColorArgs {
+    /// CATEGORY=COLOR, with red, green, yellow, blue, magenta, cyan, or none
+    pub assignment: String,
+}
+
+#[derive(Args)]
+pub struct ConfigCategoryGoalArgs {
+    /// CATEGORY=AMOUNT in USD, or CATEGORY=none
+    pub assignment: String,
+}
+
+#[derive(Args)]
+pub struct ConfigConfirmThresholdArgs {
+    /// Snapshot count at which removals start asking (omit to always ask)
+    #[arg(long)]
+    pub snapshots: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ConfigDecimalsArgs {
+    #[arg(long)]
+    pub currency: String,
+    #[arg(long)]
+    pub places: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("nw").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_read_only_rejects_mutating_commands() {
+        let cli = parse(&["--read-only", "asset", "remove", "--id", "vti"]);
+        assert!(matches!(cli.check_writable(None), Err(NwError::ReadOnly)));
+        let cli = parse(&["--read-only", "snapshot", "remove", "--date", "2025-01-01", "-y"]);
+        assert!(matches!(cli.check_writable(None), Err(NwError::ReadOnly)));
+
+        let cli = parse(&["snapshot", "add", "--date", "2025-01-01"]);
+        assert!(cli.check_writable(None).is_ok());
+        assert!(matches!(cli.check_writable(Some("1")), Err(NwError::ReadOnly)));
+        assert!(cli.check_writable(Some("0")).is_ok());
+    }
+
+    #[test]
+    fn test_format_after_subcommand() {
+        let cli = parse(&["show", "--format", "json"]);
+        assert_eq!(cli.output_format().unwrap(), OutputFormat::Json);
+        let cli = parse(&["--format", "json", "asset", "list"]);
+        assert_eq!(cli.output_format().unwrap(), OutputFormat::Json);
+        assert!(parse(&["show", "--format", "xml"]).output_format().is_err());
+
+        let cli = parse(&["--format", "json", "export", "--kind", "html"]);
+        assert_eq!(cli.output_format().unwrap(), OutputFormat::Json);
+        assert!(matches!(cli.command, Command::Export(a) if a.kind == "html"));
+    }
+
+    #[test]
+    fn test_read_only_allows_reads() {
+        for args in [
+            &["show", "--read-only"][..],
+            &["--read-only", "history", "--range", "ALL"],
+            &["--read-only", "asset", "list"],
+            &["--read-only", "snapshot", "list"],
+        ] {
+            assert!(parse(args).check_writable(Some("true")).is_ok(), "{args:?}");
+        }
+    }
 }
@@ -0,0 +1,46 @@
+use chrono::NaiveDate;
+use crate::error::NwError;
+
+/// Environment variable that pins "today" (YYYY-MM-DD) for tests and replays.
+pub const TODAY_ENV: &str = "NW_TODAY";
+
+/// Today's date, or the `NW_TODAY` override when set.
+/// Every time-dependent command goes through here rather than the system clock.
+pub fn today() -> Result<NaiveDate, NwError> {
+    today_from(std::env::var(TODAY_ENV).ok())
+}
+
+fn today_from(override_date: Option<String>) -> Result<NaiveDate, NwError> {
+    match override_date {
+        Some(s) => NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+            .map_err(|_| NwError::InvalidDate(s)),
+        None => Ok(chrono::Local::now().date_naive()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::filter_by_range;
+    use crate::model::{HistoryRange, Snapshot};
+
+    #[test]
+    fn test_today_from_override() {
+        let today = today_from(Some("2025-02-28".to_string())).unwrap();
+        assert_eq!(today.to_string(), "2025-02-28");
+        assert!(today_from(Some("28/02/2025".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_nw_today_anchors_history_filter() {
+        let today = today_from(Some("2025-02-28".to_string())).unwrap().to_string();
+
+        let snapshots: Vec<Snapshot> = ["2024-02-27", "2024-03-01", "2025-02-28"]
+            .iter()
+            .map(|d| Snapshot { date: d.to_string(), ..Default::default() })
+            .collect();
+        let result = filter_by_range(&snapshots, HistoryRange::OneYear, &today, 1, false);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].date, "2024-03-01");
+    }
+}
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use chrono::{Datelike, NaiveDate};
 use crate::error::NwError;
-use crate::model::{HistoryRange, HistoryRow, Portfolio, ShowRow, Snapshot};
+use crate::model::{
+    AllocationBy, Asset, Attribution, CategoryDelta, Drawdown, GoalProgress, HistoryRange,
+    HistoryRow, HistoryStats, Portfolio, RateJump, RatePolicy, ShowRow, Snapshot, SnapshotDelta,
+    SnapshotEntry, SortOrder,
+};
 
 /// Convert a value in `currency` to USD using the snapshot's rate map.
 /// USD assets return `value` unchanged.
@@ -16,34 +21,258 @@ pub fn to_usd(value: f64, currency: &str, rates: &HashMap<String, f64>) -> Resul
         .ok_or_else(|| NwError::RateMissing(currency.to_string()))
 }
 
-/// Compute ShowRows from a snapshot. Unknown asset_ids in entries are silently skipped.
+/// Compute ShowRows from a snapshot. Unknown asset_ids in entries are silently skipped
+/// (`unknown_asset_rows` lists them instead).
 /// Returns (grand_total_usd, Vec<ShowRow>) where grand_total accounts for the category filter.
 pub fn compute_show_rows(
     snapshot: &Snapshot,
     portfolio: &Portfolio,
     category_filter: Option<&str>,
 ) -> Result<(f64, Vec<ShowRow>), NwError> {
-    let asset_map: HashMap<&str, &crate::model::Asset> =
+    let snapshot = with_derived_entries(snapshot, portfolio)?;
+    let snapshot = snapshot.as_ref();
+    let result = show_entries(snapshot, portfolio, category_filter)
+        .try_fold((0.0_f64, Vec::new()), |(total, mut rows), (entry, asset)| {
+            let row = show_row(entry, asset, snapshot, portfolio)?;
+            let usd_value = row.usd_value;
+            rows.push(row);
+            Ok((total + usd_value, rows))
+        });
+    result
+}
+
+/// (grand_total_usd, rows, (asset_name, currency) of holdings left out for lack of a rate).
+pub type LenientShowRows = (f64, Vec<ShowRow>, Vec<(String, String)>);
+
+/// Like `compute_show_rows`, but entries whose currency has no rate are collected
+/// as (asset_name, currency) and excluded from the total instead of failing.
+/// Any other error (e.g. lots that don't add up) still fails.
+pub fn compute_show_rows_lenient(
+    snapshot: &Snapshot,
+    portfolio: &Portfolio,
+    category_filter: Option<&str>,
+) -> Result<LenientShowRows, NwError> {
+    let snapshot = with_derived_entries(snapshot, portfolio)?;
+    let snapshot = snapshot.as_ref();
+    let result = show_entries(snapshot, portfolio, category_filter).try_fold(
+        (0.0_f64, Vec::new(), Vec::new()),
+        |(total, mut rows, mut missing), (entry, asset)| {
+            match show_row(entry, asset, snapshot, portfolio) {
+                Ok(row) => {
+                    let usd_value = row.usd_value;
+                    rows.push(row);
+                    Ok((total + usd_value, rows, missing))
+                }
+                Err(NwError::RateMissing(_)) => {
+                    missing.push((asset.name.clone(), asset.currency.clone()));
+                    Ok((total, rows, missing))
+                }
+                Err(e) => Err(e),
+            }
+        },
+    );
+    result
+}
+
+/// `snapshot` with an entry for each `percent_of` asset, valued as its share of the entry
+/// it derives from (none if that entry is missing). Stored entries for such assets are
+/// replaced. Borrowed unchanged when no asset is derived.
+pub fn with_derived_entries<'a>(
+    snapshot: &'a Snapshot,
+    portfolio: &Portfolio,
+) -> Result<Cow<'a, Snapshot>, NwError> {
+    let derived: Vec<&Asset> =
+        portfolio.assets.iter().filter(|a| a.percent_of.is_some()).collect();
+    if derived.is_empty() {
+        return Ok(Cow::Borrowed(snapshot));
+    }
+    let mut entries: Vec<SnapshotEntry> = snapshot
+        .entries
+        .iter()
+        .filter(|e| !derived.iter().any(|a| a.id == e.asset_id))
+        .cloned()
+        .collect();
+    for asset in derived {
+        let (source, fraction) = portfolio.derived_source(&asset.id)?;
+        if let Some(entry) = snapshot.entries.iter().find(|e| e.asset_id == source.id) {
+            let value = entry.native_value()? * fraction;
+            entries.push(SnapshotEntry { asset_id: asset.id.clone(), value, lots: Vec::new() });
+        }
+    }
+    Ok(Cow::Owned(Snapshot { entries, ..snapshot.clone() }))
+}
+
+/// Entries paired with their known asset, restricted to `category_filter`.
+fn show_entries<'a>(
+    snapshot: &'a Snapshot,
+    portfolio: &'a Portfolio,
+    category_filter: Option<&'a str>,
+) -> impl Iterator<Item = (&'a SnapshotEntry, &'a Asset)> {
+    let asset_map: HashMap<&str, &Asset> =
         portfolio.assets.iter().map(|a| (a.id.as_str(), a)).collect();
 
     snapshot.entries
         .iter()
-        .filter_map(|entry| asset_map.get(entry.asset_id.as_str()).map(|a| (entry, *a)))
-        .filter(|(_, asset)| category_filter.is_none_or(|f| asset.category == f))
-        .try_fold((0.0_f64, Vec::new()), |(total, mut rows), (entry, asset)| {
-            let usd_value = to_usd(entry.value, &asset.currency, &snapshot.rates)?;
-            rows.push(ShowRow {
-                asset_name: asset.name.clone(),
-                currency: asset.currency.clone(),
-                native_value: entry.value,
-                usd_value,
-                category: asset.category.clone(),
-            });
-            Ok((total + usd_value, rows))
+        .filter_map(move |entry| asset_map.get(entry.asset_id.as_str()).map(|a| (entry, *a)))
+        .filter(move |(_, asset)| category_filter.is_none_or(|f| asset.category == f))
+}
+
+fn show_row(
+    entry: &SnapshotEntry,
+    asset: &Asset,
+    snapshot: &Snapshot,
+    portfolio: &Portfolio,
+) -> Result<ShowRow, NwError> {
+    let native_value = entry.native_value()?;
+    let usd_value = to_usd(native_value, &asset.currency, &snapshot.rates)?;
+    Ok(ShowRow {
+        asset_id: asset.id.clone(),
+        asset_name: asset.name.clone(),
+        currency: asset.currency.clone(),
+        native_value,
+        native_decimals: portfolio.settings.decimals_for(&asset.currency),
+        usd_value,
+        category: asset.category.clone(),
+        change_usd: None,
+        change_pct: None,
+        gain_usd: unrealized_gain(usd_value, asset.cost_basis),
+        sort_order: asset.sort_order,
+    })
+}
+
+/// Category of the rows `unknown_asset_rows` synthesizes.
+pub const UNKNOWN_CATEGORY: &str = "unknown";
+
+/// Currency of the rows `unknown_asset_rows` synthesizes.
+pub const UNKNOWN_CURRENCY: &str = "?";
+
+/// Rows for entries whose asset no longer exists (`show --show-unknown`), named
+/// "(deleted: <id>)" in the `unknown` category so their native amount stays visible.
+/// The currency went with the asset, so they have no USD value and add nothing to totals.
+/// With a `category_filter` other than `unknown` there are none.
+pub fn unknown_asset_rows(
+    snapshot: &Snapshot,
+    portfolio: &Portfolio,
+    category_filter: Option<&str>,
+) -> Vec<ShowRow> {
+    if category_filter.is_some_and(|f| f != UNKNOWN_CATEGORY) {
+        return Vec::new();
+    }
+    snapshot
+        .entries
+        .iter()
+        .filter(|e| !portfolio.assets.iter().any(|a| a.id == e.asset_id))
+        .map(|entry| ShowRow {
+            asset_id: entry.asset_id.clone(),
+            asset_name: format!("(deleted: {})", entry.asset_id),
+            currency: UNKNOWN_CURRENCY.to_string(),
+            native_value: entry.value,
+            native_decimals: raw_decimals(entry.value),
+            usd_value: 0.0,
+            category: UNKNOWN_CATEGORY.to_string(),
+            change_usd: None,
+            change_pct: None,
+            gain_usd: None,
+            sort_order: None,
+        })
+        .collect()
+}
+
+/// Decimal places that show `value` as stored, up to `MAX_DECIMALS`, for amounts whose
+/// currency (and so its precision) is unknown.
+fn raw_decimals(value: f64) -> u32 {
+    let digits = value.abs().to_string().split_once('.').map_or(0, |(_, f)| f.len());
+    (digits as u32).min(crate::model::MAX_DECIMALS)
+}
+
+/// Zero-valued rows for assets that have no entry in `snapshot` (`show --show-missing`),
+/// restricted to `category_filter`. They add nothing to the snapshot total.
+pub fn missing_asset_rows(
+    snapshot: &Snapshot,
+    portfolio: &Portfolio,
+    category_filter: Option<&str>,
+) -> Vec<ShowRow> {
+    portfolio
+        .assets
+        .iter()
+        .filter(|asset| category_filter.is_none_or(|f| asset.category == f))
+        .filter(|asset| asset.percent_of.is_none())
+        .filter(|asset| !snapshot.entries.iter().any(|e| e.asset_id == asset.id))
+        .map(|asset| ShowRow {
+            asset_id: asset.id.clone(),
+            asset_name: asset.name.clone(),
+            currency: asset.currency.clone(),
+            native_value: 0.0,
+            native_decimals: portfolio.settings.decimals_for(&asset.currency),
+            usd_value: 0.0,
+            category: asset.category.clone(),
+            change_usd: None,
+            change_pct: None,
+            gain_usd: None,
+            sort_order: asset.sort_order,
         })
+        .collect()
+}
+
+/// Unrealized gain (negative for a loss) of a holding worth `usd_value`, if it has a basis.
+pub fn unrealized_gain(usd_value: f64, cost_basis: Option<f64>) -> Option<f64> {
+    cost_basis.map(|basis| usd_value - basis)
+}
+
+/// Fill each row's `change_usd` and `change_pct` with its change since the `compare`
+/// snapshot, valued at that snapshot's rates. Assets absent from `compare` count their
+/// full value as change and have no percentage. Deleted-asset rows (no currency to value
+/// them in) are left without a change.
+pub fn apply_comparison(rows: &mut [ShowRow], compare: &Snapshot) -> Result<(), NwError> {
+    for row in rows.iter_mut().filter(|r| r.currency != UNKNOWN_CURRENCY) {
+        let previous_usd = match compare.entries.iter().find(|e| e.asset_id == row.asset_id) {
+            Some(entry) => to_usd(entry.native_value()?, &row.currency, &compare.rates)?,
+            None => 0.0,
+        };
+        let (change_usd, change_pct) = compute_change(previous_usd, row.usd_value);
+        row.change_usd = Some(change_usd);
+        row.change_pct = (previous_usd != 0.0).then_some(change_pct);
+    }
+    Ok(())
+}
+
+/// Percentage change of a total whose change is `change_usd`; `None` from a zero baseline.
+pub fn change_pct_of(total_usd: f64, change_usd: f64) -> Option<f64> {
+    let previous = total_usd - change_usd;
+    (previous != 0.0).then(|| compute_change(previous, total_usd).1)
+}
+
+/// Revalue a snapshot's holdings using another rate map (e.g. the latest snapshot's),
+/// separating holdings changes from exchange-rate moves.
+/// Unknown asset_ids are skipped; a currency missing from `rates` is an error.
+pub fn total_at_rates(
+    snapshot: &Snapshot,
+    portfolio: &Portfolio,
+    rates: &HashMap<String, f64>,
+    category_filter: Option<&str>,
+) -> Result<f64, NwError> {
+    let revalued = Snapshot { rates: rates.clone(), ..snapshot.clone() };
+    compute_show_rows(&revalued, portfolio, category_filter).map(|(total, _)| total)
+}
+
+/// Round `value` to the nearest multiple of `n`; halves round away from zero.
+pub fn round_to_nearest(value: f64, n: f64) -> f64 {
+    (value / n).round() * n
 }
 
-/// Compute allocation percentages. Returns Vec<(category, pct)> sorted by pct descending.
+/// Round each row's USD amounts to the nearest `n` (`show --round-to`). Returns the sum
+/// of the rounded values, so subtotals and the total still add up.
+pub fn round_show_rows(rows: &mut [ShowRow], n: f64) -> f64 {
+    for row in rows.iter_mut() {
+        row.usd_value = round_to_nearest(row.usd_value, n);
+        row.change_usd = row.change_usd.map(|c| round_to_nearest(c, n));
+        row.gain_usd = row.gain_usd.map(|g| round_to_nearest(g, n));
+    }
+    rows.iter().map(|r| r.usd_value).sum()
+}
+
+/// Compute allocation percentages. Returns Vec<(category, pct)> sorted by pct descending,
+/// with equal percentages ordered by category name.
 pub fn compute_allocation(
     category_totals: &HashMap<String, f64>,
     grand_total: f64,
@@ -55,45 +284,180 @@ pub fn compute_allocation(
         .iter()
         .map(|(cat, total)| (cat.clone(), total / grand_total * 100.0))
         .collect();
-    result.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    result.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
     result
 }
 
+/// Whether `value` is more than `threshold_pct` percent of `grand_total`.
+pub fn exceeds_share(value: f64, grand_total: f64, threshold_pct: f64) -> bool {
+    grand_total > 0.0 && value / grand_total * 100.0 > threshold_pct
+}
+
+/// Round `value` to `figures` significant figures, e.g. 387.512 to 4 figures is 387.5.
+/// Goes through decimal formatting so the result is the closest double to the short form.
+pub fn round_sig_figs(value: f64, figures: u32) -> f64 {
+    if value == 0.0 || !value.is_finite() || figures == 0 {
+        return value;
+    }
+    let precision = figures as usize - 1;
+    format!("{:.*e}", precision, value).parse().unwrap_or(value)
+}
+
+/// Parse a percentage like "25%" or "25".
+pub fn parse_percent(s: &str) -> Result<f64, NwError> {
+    s.trim()
+        .trim_end_matches('%')
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .ok_or_else(|| NwError::InvalidPercent(s.to_string()))
+}
+
+/// Per-holding USD totals keyed by asset id, for allocation within one category
+/// (`asset_allocation_names` turns the ids into display names).
+pub fn compute_asset_totals(rows: &[ShowRow]) -> HashMap<String, f64> {
+    totals_by(rows, |row| &row.asset_id)
+}
+
+/// Replace the asset ids of an allocation from `compute_asset_totals` with the holdings'
+/// names, adding the id to a name that more than one holding shares.
+pub fn asset_allocation_names(
+    allocation: Vec<(String, f64)>,
+    rows: &[ShowRow],
+) -> Vec<(String, f64)> {
+    let name_of = |id: &str| rows.iter().find(|r| r.asset_id == id).map(|r| &r.asset_name);
+    allocation
+        .into_iter()
+        .map(|(id, pct)| {
+            let Some(name) = name_of(&id) else {
+                return (id, pct);
+            };
+            let shared = rows.iter().any(|r| &r.asset_name == name && r.asset_id != id);
+            let label = if shared { format!("{} ({})", name, id) } else { name.clone() };
+            (label, pct)
+        })
+        .collect()
+}
+
 /// Compute per-category USD totals from a slice of ShowRows.
 pub fn compute_category_totals(rows: &[ShowRow]) -> HashMap<String, f64> {
-    rows.iter().fold(HashMap::new(), |mut map, row| {
-        *map.entry(row.category.clone()).or_insert(0.0) += row.usd_value;
-        map
+    totals_by(rows, |row| &row.category)
+}
+
+/// Compute per-currency USD totals (currency exposure) from a slice of ShowRows.
+pub fn compute_currency_totals(rows: &[ShowRow]) -> HashMap<String, f64> {
+    totals_by(rows, |row| &row.currency)
+}
+
+/// Per-group USD totals for the allocation block, grouped by `by`.
+pub fn compute_allocation_totals(rows: &[ShowRow], by: AllocationBy) -> HashMap<String, f64> {
+    match by {
+        AllocationBy::Category => compute_category_totals(rows),
+        AllocationBy::Currency => compute_currency_totals(rows),
+    }
+}
+
+/// Percentage of `goal` that `current` has reached; 0 for a non-positive goal.
+pub fn compute_goal_progress(current: f64, goal: f64) -> f64 {
+    if goal <= 0.0 {
+        0.0
+    } else {
+        current / goal * 100.0
+    }
+}
+
+/// Progress toward each category goal, in category order. Categories with a goal but no
+/// holdings in `category_totals` report 0. With a `category_filter`, only its goal is kept.
+pub fn category_goal_progress(
+    category_totals: &HashMap<String, f64>,
+    goals: &BTreeMap<String, f64>,
+    category_filter: Option<&str>,
+) -> Vec<GoalProgress> {
+    goals
+        .iter()
+        .filter(|(category, _)| category_filter.is_none_or(|f| f == category.as_str()))
+        .map(|(category, goal)| {
+            let current_usd = category_totals.get(category).copied().unwrap_or(0.0);
+            GoalProgress {
+                category: category.clone(),
+                current_usd,
+                goal_usd: *goal,
+                pct: compute_goal_progress(current_usd, *goal),
+            }
+        })
+        .collect()
+}
+
+/// USD totals grouped by `key`.
+/// Values are summed in sorted order so the result does not depend on row order.
+fn totals_by(rows: &[ShowRow], key: impl Fn(&ShowRow) -> &String) -> HashMap<String, f64> {
+    let mut values: HashMap<String, Vec<f64>> = HashMap::new();
+    for row in rows {
+        values.entry(key(row).clone()).or_default().push(row.usd_value);
+    }
+    values
+        .into_iter()
+        .map(|(group, mut v)| {
+            v.sort_by(f64::total_cmp);
+            (group, v.iter().sum())
+        })
+        .collect()
+}
+
+/// First date (YYYY-MM-DD, inclusive) of `range` anchored at `today`, or None for ALL.
+/// `fiscal_start_month` (1–12) anchors the YTD range.
+pub fn range_cutoff(range: HistoryRange, today: &str, fiscal_start_month: u32) -> Option<String> {
+    if range == HistoryRange::All {
+        return None;
+    }
+    NaiveDate::parse_from_str(today, "%Y-%m-%d").ok().map(|d| {
+        let cutoff = match range {
+            HistoryRange::OneMonth  => subtract_months(d, 1),
+            HistoryRange::SixMonths => subtract_months(d, 6),
+            HistoryRange::OneYear   => subtract_years(d, 1),
+            HistoryRange::FiveYears => subtract_years(d, 5),
+            HistoryRange::YearToDate => fiscal_year_start(d, fiscal_start_month),
+            HistoryRange::All       => unreachable!(),
+        };
+        cutoff.format("%Y-%m-%d").to_string()
     })
 }
 
 /// Filter snapshots to those within the given range, anchored at `today` (YYYY-MM-DD).
+/// `fiscal_start_month` (1–12) anchors the YTD range. Drafts are left out unless
+/// `include_drafts` is set.
 pub fn filter_by_range<'a>(
     snapshots: &'a [Snapshot],
     range: HistoryRange,
     today: &str,
+    fiscal_start_month: u32,
+    include_drafts: bool,
 ) -> Vec<&'a Snapshot> {
-    let cutoff_str: Option<String> = if range == HistoryRange::All {
-        None
-    } else {
-        NaiveDate::parse_from_str(today, "%Y-%m-%d").ok().map(|d| {
-            let cutoff = match range {
-                HistoryRange::OneMonth  => subtract_months(d, 1),
-                HistoryRange::SixMonths => subtract_months(d, 6),
-                HistoryRange::OneYear   => subtract_years(d, 1),
-                HistoryRange::FiveYears => subtract_years(d, 5),
-                HistoryRange::All       => unreachable!(),
-            };
-            cutoff.format("%Y-%m-%d").to_string()
-        })
-    };
+    let cutoff_str = range_cutoff(range, today, fiscal_start_month);
+    visible_snapshots(snapshots, include_drafts)
+        .into_iter()
+        .filter(|s| cutoff_str.as_deref().is_none_or(|c| s.date.as_str() >= c))
+        .collect()
+}
 
+/// Keep snapshots whose note contains `needle`, ignoring case.
+pub fn filter_by_note<'a>(snapshots: Vec<&'a Snapshot>, needle: &str) -> Vec<&'a Snapshot> {
+    let needle = needle.to_lowercase();
     snapshots
-        .iter()
-        .filter(|s| cutoff_str.as_deref().is_none_or(|c| s.date.as_str() >= c))
+        .into_iter()
+        .filter(|s| s.note.as_ref().is_some_and(|n| n.to_lowercase().contains(&needle)))
         .collect()
 }
 
+/// First day of the fiscal year containing `date`: the most recent 1st of
+/// `start_month` on or before it (January when `start_month` is out of range).
+pub fn fiscal_year_start(date: NaiveDate, start_month: u32) -> NaiveDate {
+    let start_month = if (1..=12).contains(&start_month) { start_month } else { 1 };
+    let year = if date.month() >= start_month { date.year() } else { date.year() - 1 };
+    NaiveDate::from_ymd_opt(year, start_month, 1).expect("valid year/month")
+}
+
 fn subtract_months(date: NaiveDate, months: u32) -> NaiveDate {
     let mut year = date.year();
     let mut month = date.month() as i32 - months as i32;
@@ -128,37 +492,505 @@ fn days_in_month(year: i32, month: u32) -> u32 {
     }
 }
 
+/// Keep the `limit` most recent snapshots (all if `None`) and order them by date.
+/// Expects `snapshots` in ascending date order, as stored.
+pub fn select_snapshots(
+    snapshots: &[Snapshot],
+    order: SortOrder,
+    limit: Option<usize>,
+) -> Vec<&Snapshot> {
+    let skip = limit.map_or(0, |n| snapshots.len().saturating_sub(n));
+    let mut selected: Vec<&Snapshot> = snapshots.iter().skip(skip).collect();
+    if order == SortOrder::Desc {
+        selected.reverse();
+    }
+    selected
+}
+
+/// The most recent snapshot dated strictly before `date`, if any.
+pub fn previous_snapshot<'a>(snapshots: &'a [Snapshot], date: &str) -> Option<&'a Snapshot> {
+    snapshots
+        .iter()
+        .filter(|s| s.date.as_str() < date)
+        .max_by(|a, b| a.date.cmp(&b.date))
+}
+
+/// How many snapshots hold an entry for `asset_id`: (all of them, drafts among them).
+pub fn snapshot_reference_counts(portfolio: &Portfolio, asset_id: &str) -> (usize, usize) {
+    portfolio
+        .snapshots
+        .iter()
+        .filter(|s| s.entries.iter().any(|e| e.asset_id == asset_id))
+        .fold((0, 0), |(all, drafts), s| (all + 1, drafts + usize::from(s.draft)))
+}
+
+/// Dates of snapshots that hold `asset_id` but have no rate for the asset's currency.
+pub fn snapshots_missing_rate(portfolio: &Portfolio, asset_id: &str) -> Vec<String> {
+    let Some(asset) = portfolio.assets.iter().find(|a| a.id == asset_id) else {
+        return Vec::new();
+    };
+    if asset.currency == "USD" {
+        return Vec::new();
+    }
+    portfolio
+        .snapshots
+        .iter()
+        .filter(|s| s.entries.iter().any(|e| e.asset_id == asset_id))
+        .filter(|s| !s.rates.contains_key(&asset.currency))
+        .map(|s| s.date.clone())
+        .collect()
+}
+
+/// Rates offered as defaults for a new snapshot on `date`: those of the preceding
+/// snapshot, or of the earliest one when backfilling before all existing dates.
+pub fn rates_to_reuse<'a>(
+    snapshots: &'a [Snapshot],
+    date: &str,
+) -> Option<&'a HashMap<String, f64>> {
+    previous_snapshot(snapshots, date)
+        .or_else(|| snapshots.first())
+        .map(|s| &s.rates)
+}
+
 /// Compute total USD value of all entries in a snapshot (skipping unknown asset_ids).
 pub fn snapshot_total_usd(snapshot: &Snapshot, portfolio: &Portfolio) -> Result<f64, NwError> {
     let (total, _) = compute_show_rows(snapshot, portfolio, None)?;
     Ok(total)
 }
 
+/// Find snapshots whose `cached_total_usd` disagrees with a fresh computation.
+/// Returns (date, cached, computed). Snapshots without a cached total, or whose
+/// total cannot be computed, are skipped.
+pub fn cached_total_mismatches(portfolio: &Portfolio) -> Vec<(String, f64, f64)> {
+    portfolio
+        .snapshots
+        .iter()
+        .filter_map(|s| {
+            let cached = s.cached_total_usd?;
+            let computed = snapshot_total_usd(s, portfolio).ok()?;
+            ((cached - computed).abs() > 0.005).then(|| (s.date.clone(), cached, computed))
+        })
+        .collect()
+}
+
+/// Problems that make a portfolio file unfit to commit (`nw check`): duplicate asset ids,
+/// bad or unsorted snapshot dates, entries for unknown assets, missing rates, and lot
+/// sums that disagree with their value. Empty when the file is clean.
+pub fn check_problems(portfolio: &Portfolio) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut ids = std::collections::HashSet::new();
+    for asset in &portfolio.assets {
+        if !ids.insert(asset.id.as_str()) {
+            problems.push(format!("asset id '{}' is defined more than once", asset.id));
+        }
+    }
+
+    for pair in portfolio.snapshots.windows(2) {
+        if pair[0].date >= pair[1].date {
+            problems.push(format!(
+                "snapshots out of order: {} is listed before {}",
+                pair[0].date, pair[1].date
+            ));
+        }
+    }
+
+    let assets: HashMap<&str, &Asset> =
+        portfolio.assets.iter().map(|a| (a.id.as_str(), a)).collect();
+    for snapshot in &portfolio.snapshots {
+        let date = &snapshot.date;
+        if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            problems.push(format!("{date}: invalid date, expected YYYY-MM-DD"));
+        }
+        for entry in &snapshot.entries {
+            let Some(asset) = assets.get(entry.asset_id.as_str()) else {
+                problems.push(format!("{date}: entry for unknown asset '{}'", entry.asset_id));
+                continue;
+            };
+            if asset.currency != "USD" && !snapshot.rates.contains_key(&asset.currency) {
+                problems.push(format!(
+                    "{date}: no {} rate for asset '{}'",
+                    asset.currency, asset.id
+                ));
+            }
+            if let Err(e) = entry.native_value() {
+                problems.push(format!("{date}: {e}"));
+            }
+        }
+    }
+    problems
+}
+
+/// Resolve a negative offset from the end of the (ascending) snapshot list:
+/// -1 is the latest snapshot, -2 the one before it, and so on.
+pub fn snapshot_at_offset<'a>(
+    snapshots: &[&'a Snapshot],
+    index: i64,
+) -> Result<&'a Snapshot, NwError> {
+    let out_of_range = || NwError::SnapshotIndexOutOfRange { index, count: snapshots.len() };
+    if index >= 0 {
+        return Err(out_of_range());
+    }
+    let back = usize::try_from(index.unsigned_abs()).map_err(|_| out_of_range())?;
+    snapshots
+        .len()
+        .checked_sub(back)
+        .map(|i| snapshots[i])
+        .ok_or_else(out_of_range)
+}
+
+/// The most recent snapshot that has an entry for every asset, if any.
+pub fn latest_complete_snapshot<'a>(
+    snapshots: &[&'a Snapshot],
+    assets: &[Asset],
+) -> Option<&'a Snapshot> {
+    snapshots.iter().rev().copied().find(|s| {
+        assets
+            .iter()
+            .filter(|a| a.percent_of.is_none())
+            .all(|a| s.entries.iter().any(|e| e.asset_id == a.id))
+    })
+}
+
+/// Currencies in `snapshot.rates` that no non-zero entry is held in, sorted. Entries of
+/// unknown assets can't be matched to a currency and don't keep any rate in use.
+pub fn unused_rates(snapshot: &Snapshot, assets: &[Asset]) -> Vec<String> {
+    let used: std::collections::HashSet<&str> = snapshot
+        .entries
+        .iter()
+        .filter(|e| e.value != 0.0)
+        .filter_map(|e| assets.iter().find(|a| a.id == e.asset_id))
+        .map(|a| a.currency.as_str())
+        .collect();
+    let mut unused: Vec<String> =
+        snapshot.rates.keys().filter(|c| !used.contains(c.as_str())).cloned().collect();
+    unused.sort();
+    unused
+}
+
+/// Snapshots that count for display: all of them with `include_drafts`, else finalized only.
+pub fn visible_snapshots(snapshots: &[Snapshot], include_drafts: bool) -> Vec<&Snapshot> {
+    snapshots.iter().filter(|s| include_drafts || !s.draft).collect()
+}
+
+/// At most this many "did you mean" suggestions are offered.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Candidates within a small edit distance of `target`, closest first (ties alphabetical).
+/// The allowed distance grows with the target's length: 2, or a third of it for long ids.
+pub fn suggest_similar(target: &str, candidates: &[&str]) -> Vec<String> {
+    let target = target.to_lowercase();
+    let max_distance = (target.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|c| (levenshtein(&target, &c.to_lowercase()), *c))
+        .filter(|(d, _)| *d <= max_distance)
+        .collect();
+    scored.sort();
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, c)| c.to_string()).collect()
+}
+
+/// Number of single-character insertions, deletions, or substitutions turning `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            row[j + 1] = substitution.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// A rate moving by this factor or more (either direction) between snapshots is suspicious.
+pub const RATE_JUMP_FACTOR: f64 = 10.0;
+
+/// Flag rates that changed by an order of magnitude from the previous snapshot carrying
+/// the same currency — usually a typo (a misplaced decimal or an inverted rate).
+/// Expects `snapshots` in ascending date order.
+pub fn rate_jump_warnings(snapshots: &[Snapshot]) -> Vec<RateJump> {
+    let mut last_seen: HashMap<&str, (&str, f64)> = HashMap::new();
+    let mut jumps = Vec::new();
+    for snapshot in snapshots {
+        let mut currencies: Vec<(&String, &f64)> = snapshot.rates.iter().collect();
+        currencies.sort_by(|a, b| a.0.cmp(b.0));
+        for (currency, &rate) in currencies {
+            if let Some(&(previous_date, previous_rate)) = last_seen.get(currency.as_str()) {
+                let ratio = rate / previous_rate;
+                if ratio >= RATE_JUMP_FACTOR || ratio <= 1.0 / RATE_JUMP_FACTOR {
+                    jumps.push(RateJump {
+                        currency: currency.clone(),
+                        previous_date: previous_date.to_string(),
+                        previous_rate,
+                        date: snapshot.date.clone(),
+                        rate,
+                    });
+                }
+            }
+            last_seen.insert(currency, (&snapshot.date, rate));
+        }
+    }
+    jumps
+}
+
+/// Dates of snapshots with no entries at all — they record a $0 total that
+/// shows up in history as a total loss.
+pub fn empty_snapshot_dates(snapshots: &[Snapshot]) -> Vec<&str> {
+    snapshots
+        .iter()
+        .filter(|s| s.entries.is_empty())
+        .map(|s| s.date.as_str())
+        .collect()
+}
+
+/// Lowest, highest, and average total across `rows`; `None` when there are no rows.
+pub fn history_stats(rows: &[HistoryRow]) -> Option<HistoryStats> {
+    let first = rows.first()?;
+    let mut min = first;
+    let mut max = first;
+    for row in rows {
+        if row.total_usd < min.total_usd {
+            min = row;
+        }
+        if row.total_usd > max.total_usd {
+            max = row;
+        }
+    }
+    let average = rows.iter().map(|r| r.total_usd).sum::<f64>() / rows.len() as f64;
+    Some(HistoryStats {
+        min: (min.date.clone(), min.total_usd),
+        max: (max.date.clone(), max.total_usd),
+        average,
+    })
+}
+
+/// Maximum drawdown across `rows`: the steepest percentage fall from a running peak to a
+/// later total. A series that never falls has a zero drawdown at its first row.
+/// `None` when there are no rows.
+pub fn max_drawdown(rows: &[HistoryRow]) -> Option<Drawdown> {
+    let first = rows.first()?;
+    let mut peak = first;
+    let mut worst = (first, first, 0.0, 0.0);
+    for row in rows {
+        if row.total_usd > peak.total_usd {
+            peak = row;
+            continue;
+        }
+        let (change_usd, change_pct) = compute_change(peak.total_usd, row.total_usd);
+        if change_pct < worst.3 {
+            worst = (peak, row, change_usd, change_pct);
+        }
+    }
+    let (peak, trough, change_usd, change_pct) = worst;
+    Some(Drawdown {
+        peak: (peak.date.clone(), peak.total_usd),
+        trough: (trough.date.clone(), trough.total_usd),
+        change_usd,
+        change_pct,
+    })
+}
+
 /// Build HistoryRow list. First row has change = None.
+/// With a `category_filter`, each total only counts assets in that category.
+/// With `constant_rates`, every snapshot is valued at those rates instead of its own, so
+/// the series shows holdings growth without FX moves; a currency missing from them falls
+/// back to the snapshot's own rate.
 pub fn compute_history_rows(
     snapshots: &[&Snapshot],
     portfolio: &Portfolio,
+    category_filter: Option<&str>,
+    constant_rates: Option<&HashMap<String, f64>>,
 ) -> Result<Vec<HistoryRow>, NwError> {
     let totals: Vec<(String, f64)> = snapshots
         .iter()
-        .map(|s| snapshot_total_usd(s, portfolio).map(|t| (s.date.clone(), t)))
+        .map(|s| {
+            let total = match constant_rates {
+                Some(constant) => {
+                    let mut rates = s.rates.clone();
+                    rates.extend(constant.iter().map(|(c, r)| (c.clone(), *r)));
+                    total_at_rates(s, portfolio, &rates, category_filter)
+                }
+                None => compute_show_rows(s, portfolio, category_filter).map(|(t, _)| t),
+            };
+            total.map(|t| (s.date.clone(), t))
+        })
         .collect::<Result<_, _>>()?;
 
     Ok(totals
         .iter()
         .enumerate()
         .map(|(i, (date, total_usd))| {
-            let (change_usd, change_pct) = if i == 0 {
-                (None, None)
+            let (change_usd, change_pct, annualized_pct) = if i == 0 {
+                (None, None, None)
             } else {
-                let (cu, cp) = compute_change(totals[i - 1].1, *total_usd);
-                (Some(cu), Some(cp))
+                let (prev_date, prev_total) = &totals[i - 1];
+                let (cu, cp) = compute_change(*prev_total, *total_usd);
+                let annualized = days_between(prev_date, date)
+                    .filter(|d| *d > 0)
+                    .map(|d| annualize(cp, d));
+                (Some(cu), Some(cp), annualized)
             };
-            HistoryRow { date: date.clone(), total_usd: *total_usd, change_usd, change_pct }
+            HistoryRow {
+                date: date.clone(),
+                total_usd: *total_usd,
+                change_usd,
+                change_pct,
+                annualized_pct,
+                attribution: None,
+                event_usd: None,
+            }
         })
         .collect())
 }
 
+/// Net USD effect of a snapshot's events.
+pub fn event_total(snapshot: &Snapshot) -> f64 {
+    snapshot.events.iter().map(|e| e.amount).sum()
+}
+
+/// Net USD effect of the events of every snapshot dated after `after` up to and including
+/// `through`, so a change between two listed snapshots also counts events recorded on
+/// snapshots a filter left out in between.
+pub fn events_between(snapshots: &[&Snapshot], after: &str, through: &str) -> f64 {
+    snapshots
+        .iter()
+        .filter(|s| s.date.as_str() > after && s.date.as_str() <= through)
+        .map(|s| event_total(s))
+        .sum()
+}
+
+/// Annualize a percentage change that happened over `days` days (compounded).
+pub fn annualize(change_pct: f64, days: i64) -> f64 {
+    ((1.0 + change_pct / 100.0).powf(365.0 / days as f64) - 1.0) * 100.0
+}
+
+/// Days from `from` to `to` (both YYYY-MM-DD); `None` if either fails to parse.
+pub fn days_between(from: &str, to: &str) -> Option<i64> {
+    let from = NaiveDate::parse_from_str(from, "%Y-%m-%d").ok()?;
+    let to = NaiveDate::parse_from_str(to, "%Y-%m-%d").ok()?;
+    Some((to - from).num_days())
+}
+
+/// Combine a manually entered rate and a fetched one under `policy`. When only one
+/// source has a rate it is used regardless of policy; with neither there is no rate.
+// Nothing fetches rates yet, so only the tests call this until a second source exists
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn resolve_rate(manual: Option<f64>, fetched: Option<f64>, policy: RatePolicy) -> Option<f64> {
+    match (manual, fetched) {
+        (Some(manual), Some(fetched)) => Some(match policy {
+            RatePolicy::PreferManual => manual,
+            RatePolicy::PreferApi => fetched,
+            RatePolicy::Average => (manual + fetched) / 2.0,
+        }),
+        (manual, fetched) => manual.or(fetched),
+    }
+}
+
+/// The existing snapshot date closest to `date` and the distance to it in days.
+/// Earlier dates win ties; unparseable dates are ignored.
+pub fn nearest_snapshot_gap(snapshots: &[Snapshot], date: &str) -> Option<(String, i64)> {
+    snapshots
+        .iter()
+        .filter_map(|s| days_between(&s.date, date).map(|d| (s.date.clone(), d.abs())))
+        .min_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)))
+}
+
+/// Split the USD total change between two snapshots into a value part (native
+/// balances changed) and an FX part (rates moved), the sum of `fx_impact`.
+pub fn change_attribution(
+    prev: &Snapshot,
+    current: &Snapshot,
+    portfolio: &Portfolio,
+) -> Result<Attribution, NwError> {
+    let delta = snapshot_total_usd(current, portfolio)? - snapshot_total_usd(prev, portfolio)?;
+    let fx_usd = fx_impact(prev, current, portfolio)?.iter().map(|(_, usd)| usd).sum();
+    Ok(Attribution { value_usd: delta - fx_usd, fx_usd })
+}
+
+/// Per-currency USD change caused by rate moves alone: the previous balances revalued at
+/// the current rates, minus their previous USD value. Largest impact first (ties by
+/// currency). USD and currencies the current snapshot has no rate for are left out, as
+/// are unknown asset_ids.
+pub fn fx_impact(
+    prev: &Snapshot,
+    current: &Snapshot,
+    portfolio: &Portfolio,
+) -> Result<Vec<(String, f64)>, NwError> {
+    let prev = with_derived_entries(prev, portfolio)?;
+    let mut by_currency: BTreeMap<String, f64> = BTreeMap::new();
+    for (entry, asset) in show_entries(&prev, portfolio, None) {
+        if asset.currency == "USD" || !current.rates.contains_key(&asset.currency) {
+            continue;
+        }
+        let native = entry.native_value()?;
+        let at_prev = to_usd(native, &asset.currency, &prev.rates)?;
+        let at_current = to_usd(native, &asset.currency, &current.rates)?;
+        *by_currency.entry(asset.currency.clone()).or_insert(0.0) += at_current - at_prev;
+    }
+    let mut impact: Vec<(String, f64)> = by_currency.into_iter().collect();
+    impact.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+    Ok(impact)
+}
+
+/// Change from the second most recent to the most recent of `snapshots`, in total and per
+/// category. `None` when there are fewer than two snapshots.
+pub fn since_last_delta(
+    snapshots: &[&Snapshot],
+    portfolio: &Portfolio,
+) -> Result<Option<SnapshotDelta>, NwError> {
+    let [.., from, to] = snapshots else {
+        return Ok(None);
+    };
+    let (from_total, from_rows) = compute_show_rows(from, portfolio, None)?;
+    let (to_total, to_rows) = compute_show_rows(to, portfolio, None)?;
+    let from_categories = compute_category_totals(&from_rows);
+    let to_categories = compute_category_totals(&to_rows);
+
+    let names: std::collections::BTreeSet<&String> =
+        from_categories.keys().chain(to_categories.keys()).collect();
+    let categories = names
+        .into_iter()
+        .map(|category| {
+            let from_usd = from_categories.get(category).copied().unwrap_or(0.0);
+            let to_usd = to_categories.get(category).copied().unwrap_or(0.0);
+            let (change_usd, change_pct) = compute_change(from_usd, to_usd);
+            CategoryDelta { category: category.clone(), from_usd, to_usd, change_usd, change_pct }
+        })
+        .collect();
+    let (change_usd, change_pct) = compute_change(from_total, to_total);
+    Ok(Some(SnapshotDelta {
+        from: (from.date.clone(), from_total),
+        to: (to.date.clone(), to_total),
+        change_usd,
+        change_pct,
+        categories,
+        fx_impact: fx_impact(from, to, portfolio)?,
+    }))
+}
+
+/// Change from the earliest of `snapshots` to the snapshot on `current_date`, whose total
+/// as displayed is `current_total`: (first date, change in USD, change in percent, or None
+/// from a zero total). `None` with fewer than two snapshots, or when `current_date` is the
+/// earliest one.
+pub fn since_inception(
+    snapshots: &[&Snapshot],
+    current_date: &str,
+    current_total: f64,
+    portfolio: &Portfolio,
+) -> Result<Option<(String, f64, Option<f64>)>, NwError> {
+    let [first, _, ..] = snapshots else {
+        return Ok(None);
+    };
+    if first.date == current_date {
+        return Ok(None);
+    }
+    let first_total = snapshot_total_usd(first, portfolio)?;
+    let (change_usd, change_pct) = compute_change(first_total, current_total);
+    Ok(Some((first.date.clone(), change_usd, (first_total != 0.0).then_some(change_pct))))
+}
+
 /// Returns (change_usd, change_pct). If prev == 0, change_pct is 0.0.
 pub fn compute_change(prev: f64, current: f64) -> (f64, f64) {
     let change_usd = current - prev;
@@ -169,14 +1001,16 @@ pub fn compute_change(prev: f64, current: f64) -> (f64, f64) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{Asset, Portfolio, Snapshot, SnapshotEntry};
+    use crate::model::{
+        Asset, Lot, PercentOf, Portfolio, Snapshot, SnapshotEntry, SnapshotEvent,
+    };
 
     fn make_rates(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
         pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
     }
 
-    fn make_snapshot(date: &str) -> Snapshot {
-        Snapshot { date: date.to_string(), rates: HashMap::new(), entries: vec![] }
+    fn make_entry(asset_id: &str, value: f64) -> SnapshotEntry {
+        SnapshotEntry { asset_id: asset_id.to_string(), value, ..Default::default() }
     }
 
     // ---- to_usd ----
@@ -228,20 +1062,20 @@ mod tests {
 
     #[test]
     fn test_filter_all() {
-        let snapshots = vec![make_snapshot("2020-01-01"), make_snapshot("2025-02-28")];
-        let result = filter_by_range(&snapshots, HistoryRange::All, "2025-02-28");
+        let snapshots = vec![Snapshot::new("2020-01-01"), Snapshot::new("2025-02-28")];
+        let result = filter_by_range(&snapshots, HistoryRange::All, "2025-02-28", 1, false);
         assert_eq!(result.len(), 2);
     }
 
     #[test]
     fn test_filter_1y() {
         let snapshots = vec![
-            make_snapshot("2023-12-01"),
-            make_snapshot("2024-03-01"),
-            make_snapshot("2025-02-28"),
+            Snapshot::new("2023-12-01"),
+            Snapshot::new("2024-03-01"),
+            Snapshot::new("2025-02-28"),
         ];
         // today = 2025-02-28, 1Y cutoff = 2024-02-28
-        let result = filter_by_range(&snapshots, HistoryRange::OneYear, "2025-02-28");
+        let result = filter_by_range(&snapshots, HistoryRange::OneYear, "2025-02-28", 1, false);
         // 2023-12-01 is before cutoff, others are after
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].date, "2024-03-01");
@@ -251,12 +1085,12 @@ mod tests {
     #[test]
     fn test_filter_1m() {
         let snapshots = vec![
-            make_snapshot("2025-01-15"),
-            make_snapshot("2025-02-10"),
-            make_snapshot("2025-02-28"),
+            Snapshot::new("2025-01-15"),
+            Snapshot::new("2025-02-10"),
+            Snapshot::new("2025-02-28"),
         ];
         // today = 2025-02-28, 1M cutoff = 2025-01-28
-        let result = filter_by_range(&snapshots, HistoryRange::OneMonth, "2025-02-28");
+        let result = filter_by_range(&snapshots, HistoryRange::OneMonth, "2025-02-28", 1, false);
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].date, "2025-02-10");
     }
@@ -264,12 +1098,12 @@ mod tests {
     #[test]
     fn test_filter_6m() {
         let snapshots = vec![
-            make_snapshot("2024-07-01"),
-            make_snapshot("2024-09-01"),
-            make_snapshot("2025-02-28"),
+            Snapshot::new("2024-07-01"),
+            Snapshot::new("2024-09-01"),
+            Snapshot::new("2025-02-28"),
         ];
         // today = 2025-02-28, 6M cutoff = 2024-08-28
-        let result = filter_by_range(&snapshots, HistoryRange::SixMonths, "2025-02-28");
+        let result = filter_by_range(&snapshots, HistoryRange::SixMonths, "2025-02-28", 1, false);
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].date, "2024-09-01");
     }
@@ -277,31 +1111,154 @@ mod tests {
     #[test]
     fn test_filter_5y() {
         let snapshots = vec![
-            make_snapshot("2019-12-31"),
-            make_snapshot("2020-03-01"),
-            make_snapshot("2025-02-28"),
+            Snapshot::new("2019-12-31"),
+            Snapshot::new("2020-03-01"),
+            Snapshot::new("2025-02-28"),
         ];
         // today = 2025-02-28, 5Y cutoff = 2020-02-28
-        let result = filter_by_range(&snapshots, HistoryRange::FiveYears, "2025-02-28");
+        let result = filter_by_range(&snapshots, HistoryRange::FiveYears, "2025-02-28", 1, false);
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].date, "2020-03-01");
     }
 
-    // ---- snapshot sort order ----
+    #[test]
+    fn test_filter_ytd_calendar_year() {
+        let snapshots = vec![Snapshot::new("2024-12-31"), Snapshot::new("2025-01-01")];
+        let result = filter_by_range(&snapshots, HistoryRange::YearToDate, "2025-02-28", 1, false);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].date, "2025-01-01");
+    }
 
     #[test]
-    fn test_snapshot_sort_ascending() {
-        let mut snapshots = vec![
-            make_snapshot("2025-06-01"),
-            make_snapshot("2024-01-01"),
-            make_snapshot("2025-01-15"),
+    fn test_filter_ytd_april_fiscal_year_crosses_calendar_year() {
+        let snapshots = vec![
+            Snapshot::new("2024-03-31"),
+            Snapshot::new("2024-04-01"),
+            Snapshot::new("2024-12-31"),
+            Snapshot::new("2025-02-28"),
         ];
-        snapshots.sort_by(|a, b| a.date.cmp(&b.date));
-        assert_eq!(snapshots[0].date, "2024-01-01");
+        // Before April, the fiscal year began on 2024-04-01
+        let result = filter_by_range(&snapshots, HistoryRange::YearToDate, "2025-02-28", 4, false);
+        let dates: Vec<&str> = result.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2024-04-01", "2024-12-31", "2025-02-28"]);
+    }
+
+    #[test]
+    fn test_fiscal_year_start() {
+        let d = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(fiscal_year_start(d("2025-02-28"), 4), d("2024-04-01"));
+        assert_eq!(fiscal_year_start(d("2025-04-01"), 4), d("2025-04-01"));
+        assert_eq!(fiscal_year_start(d("2025-12-31"), 4), d("2025-04-01"));
+        assert_eq!(fiscal_year_start(d("2025-06-15"), 1), d("2025-01-01"));
+        assert_eq!(fiscal_year_start(d("2025-06-15"), 13), d("2025-01-01"));
+    }
+
+    // ---- select_snapshots ----
+
+    #[test]
+    fn test_select_snapshots_desc_limit() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01"),
+            Snapshot::new("2025-02-01"),
+            Snapshot::new("2025-03-01"),
+        ];
+        let result = select_snapshots(&snapshots, SortOrder::Desc, Some(2));
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].date, "2025-03-01");
+        assert_eq!(result[1].date, "2025-02-01");
+    }
+
+    #[test]
+    fn test_select_snapshots_asc_limit_exceeds_len() {
+        let snapshots = vec![Snapshot::new("2025-01-01"), Snapshot::new("2025-02-01")];
+        let result = select_snapshots(&snapshots, SortOrder::Asc, Some(5));
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].date, "2025-01-01");
+    }
+
+    // ---- previous_snapshot ----
+
+    #[test]
+    fn test_previous_snapshot() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01"),
+            Snapshot::new("2025-02-01"),
+            Snapshot::new("2025-03-01"),
+        ];
+        assert_eq!(previous_snapshot(&snapshots, "2025-02-15").unwrap().date, "2025-02-01");
+        assert_eq!(previous_snapshot(&snapshots, "2025-02-01").unwrap().date, "2025-01-01");
+        assert!(previous_snapshot(&snapshots, "2025-01-01").is_none());
+    }
+
+    // ---- rates_to_reuse ----
+
+    #[test]
+    fn test_rates_to_reuse_previous_snapshot() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01").with_rate("EUR", 0.90),
+            Snapshot::new("2025-02-01").with_rate("EUR", 0.92),
+        ];
+        let rates = rates_to_reuse(&snapshots, "2025-03-01").unwrap();
+        assert_eq!(rates["EUR"], 0.92);
+        let rates = rates_to_reuse(&snapshots, "2025-01-15").unwrap();
+        assert_eq!(rates["EUR"], 0.90);
+    }
+
+    #[test]
+    fn test_rates_to_reuse_backfill_and_empty() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01").with_rate("EUR", 0.90),
+        ];
+        assert_eq!(rates_to_reuse(&snapshots, "2024-06-01").unwrap()["EUR"], 0.90);
+        assert!(rates_to_reuse(&[], "2025-01-01").is_none());
+    }
+
+    // ---- snapshot sort order ----
+
+    #[test]
+    fn test_snapshot_sort_ascending() {
+        let mut snapshots = [
+            Snapshot::new("2025-06-01"),
+            Snapshot::new("2024-01-01"),
+            Snapshot::new("2025-01-15"),
+        ];
+        snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+        assert_eq!(snapshots[0].date, "2024-01-01");
         assert_eq!(snapshots[1].date, "2025-01-15");
         assert_eq!(snapshots[2].date, "2025-06-01");
     }
 
+    // ---- total_at_rates ----
+
+    #[test]
+    fn test_total_at_rates() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("cash", "Cash", "cash", "EUR")],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2024-01-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("cash", 1000.0);
+        let historical = snapshot_total_usd(&snapshot, &portfolio).unwrap();
+        let latest = total_at_rates(&snapshot, &portfolio, &make_rates(&[("EUR", 1.0)]), None)
+            .unwrap();
+        assert!((historical - 1250.0).abs() < 0.01);
+        assert!((latest - 1000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_total_at_rates_missing_currency() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("cash", "Cash", "cash", "EUR")],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2024-01-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("cash", 1000.0);
+        let result = total_at_rates(&snapshot, &portfolio, &make_rates(&[("CHF", 0.9)]), None);
+        assert!(matches!(result, Err(NwError::RateMissing(c)) if c == "EUR"));
+    }
+
     // ---- compute_allocation ----
 
     #[test]
@@ -316,6 +1273,182 @@ mod tests {
         assert!(alloc[0].1 > alloc[1].1);
     }
 
+    #[test]
+    fn test_compute_allocation_ties_sort_by_name() {
+        let totals = HashMap::from([
+            ("etf".to_string(), 250.0),
+            ("bank".to_string(), 250.0),
+            ("crypto".to_string(), 500.0),
+        ]);
+        let alloc = compute_allocation(&totals, 1000.0);
+        let order: Vec<&str> = alloc.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(order, vec!["crypto", "bank", "etf"]);
+    }
+
+    #[test]
+    fn test_currency_exposure_aggregates_by_asset_currency() {
+        let asset =
+            |id: &str, category: &str, currency: &str| Asset::new(id, id, category, currency);
+        let portfolio = Portfolio {
+            assets: vec![
+                asset("vti", "etf", "USD"),
+                asset("cash-eur", "cash", "EUR"),
+                asset("etf-eur", "etf", "EUR"),
+                asset("sav", "bank", "CHF"),
+            ],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.5)
+            .with_rate("CHF", 0.8)
+            .with_entry("vti", 5000.0)
+            .with_entry("cash-eur", 500.0)
+            .with_entry("etf-eur", 1000.0)
+            .with_entry("sav", 1600.0);
+        let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        let totals = compute_currency_totals(&rows);
+        assert_eq!(totals["USD"], 5000.0);
+        // Two EUR holdings in different categories: 1000 + 2000 USD
+        assert_eq!(totals["EUR"], 3000.0);
+        assert_eq!(totals["CHF"], 2000.0);
+
+        let exposure = compute_allocation(&totals, total);
+        assert_eq!(
+            exposure,
+            vec![
+                ("USD".to_string(), 50.0),
+                ("EUR".to_string(), 30.0),
+                ("CHF".to_string(), 20.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compute_goal_progress() {
+        assert_eq!(compute_goal_progress(5000.0, 20000.0), 25.0);
+        assert_eq!(compute_goal_progress(30000.0, 20000.0), 150.0);
+        assert_eq!(compute_goal_progress(100.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_category_goal_progress() {
+        let totals = HashMap::from([("crypto".to_string(), 5000.0), ("etf".to_string(), 900.0)]);
+        let goals = BTreeMap::from([
+            ("crypto".to_string(), 20000.0),
+            ("real-estate".to_string(), 100000.0),
+        ]);
+        let progress = category_goal_progress(&totals, &goals, None);
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[0].category, "crypto");
+        assert_eq!(progress[0].current_usd, 5000.0);
+        assert_eq!(progress[0].pct, 25.0);
+        // A goal with no holdings yet is at 0%
+        assert_eq!(progress[1].category, "real-estate");
+        assert_eq!(progress[1].current_usd, 0.0);
+        assert_eq!(progress[1].pct, 0.0);
+
+        let filtered = category_goal_progress(&totals, &goals, Some("crypto"));
+        assert_eq!(filtered.len(), 1);
+        assert!(category_goal_progress(&totals, &goals, Some("etf")).is_empty());
+    }
+
+    #[test]
+    fn test_allocation_totals_by_currency() {
+        let asset =
+            |id: &str, category: &str, currency: &str| Asset::new(id, id, category, currency);
+        let portfolio = Portfolio {
+            assets: vec![
+                asset("vti", "etf", "USD"),
+                asset("cash-usd", "cash", "USD"),
+                asset("etf-eur", "etf", "EUR"),
+            ],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.5)
+            .with_entry("vti", 500.0)
+            .with_entry("cash-usd", 250.0)
+            .with_entry("etf-eur", 125.0);
+        let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+
+        let by_currency = compute_allocation_totals(&rows, AllocationBy::Currency);
+        assert_eq!(
+            compute_allocation(&by_currency, total),
+            vec![("USD".to_string(), 75.0), ("EUR".to_string(), 25.0)]
+        );
+        let by_category = compute_allocation_totals(&rows, AllocationBy::Category);
+        assert_eq!(
+            compute_allocation(&by_category, total),
+            vec![("etf".to_string(), 75.0), ("cash".to_string(), 25.0)]
+        );
+    }
+
+    #[test]
+    fn test_category_totals_independent_of_row_order() {
+        let row = |value: f64| ShowRow {
+            asset_id: String::new(),
+            asset_name: String::new(),
+            currency: "USD".to_string(),
+            native_value: value,
+            native_decimals: 2,
+            usd_value: value,
+            category: "etf".to_string(),
+            change_usd: None,
+            change_pct: None,
+            gain_usd: None,
+            sort_order: None,
+        };
+        let forward = vec![row(0.1), row(0.2), row(0.3), row(1e16)];
+        let backward: Vec<ShowRow> = forward.iter().rev().map(|r| row(r.usd_value)).collect();
+        assert_eq!(
+            compute_category_totals(&forward)["etf"].to_bits(),
+            compute_category_totals(&backward)["etf"].to_bits()
+        );
+    }
+
+    #[test]
+    fn test_allocation_within_category_sums_to_100() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("btc", "Bitcoin", "crypto", "USD"),
+                Asset::new("eth", "Ether", "crypto", "USD"),
+                Asset::new("vti", "VTI", "etf", "USD"),
+            ],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_entry("btc", 3000.0)
+            .with_entry("eth", 1000.0)
+            .with_entry("vti", 6000.0);
+        let (subtotal, rows) = compute_show_rows(&snapshot, &portfolio, Some("crypto")).unwrap();
+        let alloc = compute_allocation(&compute_asset_totals(&rows), subtotal);
+        let alloc = asset_allocation_names(alloc, &rows);
+        assert_eq!(alloc, vec![("Bitcoin".to_string(), 75.0), ("Ether".to_string(), 25.0)]);
+        let sum: f64 = alloc.iter().map(|(_, pct)| pct).sum();
+        assert!((sum - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_allocation_within_keeps_same_named_holdings_apart() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("cash-a", "Cash", "cash", "USD"),
+                Asset::new("cash-b", "Cash", "cash", "USD"),
+            ],
+            ..Default::default()
+        };
+        let snapshot =
+            Snapshot::new("2025-01-01").with_entry("cash-a", 300.0).with_entry("cash-b", 100.0);
+        let (subtotal, rows) = compute_show_rows(&snapshot, &portfolio, Some("cash")).unwrap();
+        let totals = compute_asset_totals(&rows);
+        assert_eq!(totals.len(), 2);
+        let alloc = asset_allocation_names(compute_allocation(&totals, subtotal), &rows);
+        assert_eq!(
+            alloc,
+            vec![("Cash (cash-a)".to_string(), 75.0), ("Cash (cash-b)".to_string(), 25.0)]
+        );
+    }
+
     #[test]
     fn test_compute_allocation_zero_total() {
         let totals = HashMap::new();
@@ -323,24 +1456,86 @@ mod tests {
         assert!(alloc.is_empty());
     }
 
+    // ---- exceeds_share / parse_percent ----
+
+    #[test]
+    fn test_exceeds_share() {
+        assert!(exceeds_share(3000.0, 10000.0, 25.0));
+        assert!(!exceeds_share(2500.0, 10000.0, 25.0));
+        assert!(!exceeds_share(100.0, 0.0, 25.0));
+    }
+
+    #[test]
+    fn test_round_sig_figs() {
+        assert_eq!(round_sig_figs(387.512_345, 4), 387.5);
+        assert_eq!(round_sig_figs(0.923_456_789, 6), 0.923_457);
+        assert_eq!(round_sig_figs(0.000_012_345_6, 3), 0.000_012_3);
+        assert_eq!(round_sig_figs(151_234.6, 3), 151_000.0);
+        assert_eq!(round_sig_figs(0.0, 4), 0.0);
+        // Rounding a rate to 6 figures moves a converted value by well under a cent per 10k
+        let rate = 0.853_219_476_5;
+        let drift = (10_000.0 / rate - 10_000.0 / round_sig_figs(rate, 6)).abs();
+        assert!(drift < 0.01);
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("25%").unwrap(), 25.0);
+        assert_eq!(parse_percent("12.5").unwrap(), 12.5);
+        assert!(parse_percent("abc").is_err());
+        assert!(parse_percent("-5%").is_err());
+    }
+
+    // ---- round_to_nearest ----
+
+    #[test]
+    fn test_round_to_nearest() {
+        assert_eq!(round_to_nearest(12_449.99, 100.0), 12_400.0);
+        assert_eq!(round_to_nearest(12_450.0, 100.0), 12_500.0);
+        assert_eq!(round_to_nearest(-1_250.0, 100.0), -1_300.0);
+        assert_eq!(round_to_nearest(12_499.0, 1000.0), 12_000.0);
+        assert_eq!(round_to_nearest(12_500.0, 1000.0), 13_000.0);
+        assert_eq!(round_to_nearest(420.0, 1000.0), 0.0);
+    }
+
+    #[test]
+    fn test_round_show_rows_total_adds_up() {
+        let row = |id: &str, usd_value: f64| ShowRow {
+            asset_id: id.to_string(),
+            asset_name: id.to_string(),
+            currency: "USD".to_string(),
+            native_value: usd_value,
+            native_decimals: 2,
+            usd_value,
+            category: "cash".to_string(),
+            change_usd: Some(usd_value / 10.0),
+            change_pct: None,
+            gain_usd: None,
+            sort_order: None,
+        };
+        let mut rows = vec![row("a", 150.0), row("b", 150.0), row("c", 150.0)];
+        // Unrounded the total is 450, which would round to 500
+        let total = round_show_rows(&mut rows, 100.0);
+        assert_eq!(total, 600.0);
+        let values: Vec<f64> = rows.iter().map(|r| r.usd_value).collect();
+        assert_eq!(values, vec![200.0, 200.0, 200.0]);
+        assert_eq!(rows[0].change_usd, Some(0.0));
+
+        // Rounding down drifts the other way: 1,449 × 2 is 2,898 but the rows show 2,000
+        let mut rows = vec![row("a", 1_449.0), row("b", 1_449.0)];
+        assert_eq!(round_show_rows(&mut rows, 1000.0), 2_000.0);
+    }
+
     // ---- compute_show_rows ----
 
     #[test]
     fn test_compute_show_rows_usd_asset() {
         let portfolio = Portfolio {
-            assets: vec![Asset {
-                id: "vti".to_string(),
-                name: "VTI".to_string(),
-                category: "etf".to_string(),
-                currency: "USD".to_string(),
-            }],
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
             snapshots: vec![],
+            ..Default::default()
         };
-        let snapshot = Snapshot {
-            date: "2025-01-01".to_string(),
-            rates: HashMap::new(),
-            entries: vec![SnapshotEntry { asset_id: "vti".to_string(), value: 12500.0 }],
-        };
+        let snapshot = Snapshot::new("2025-01-01").with_entry("vti", 12500.0);
         let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
         assert!((total - 12500.0).abs() < 0.01);
         assert_eq!(rows.len(), 1);
@@ -350,22 +1545,13 @@ mod tests {
     #[test]
     fn test_compute_show_rows_foreign_asset() {
         let portfolio = Portfolio {
-            assets: vec![Asset {
-                id: "amd-bank".to_string(),
-                name: "Ameriabank".to_string(),
-                category: "bank".to_string(),
-                currency: "AMD".to_string(),
-            }],
+            assets: vec![Asset::new("amd-bank", "Ameriabank", "bank", "AMD")],
             snapshots: vec![],
+            ..Default::default()
         };
-        let snapshot = Snapshot {
-            date: "2025-01-01".to_string(),
-            rates: make_rates(&[("AMD", 387.5)]),
-            entries: vec![SnapshotEntry {
-                asset_id: "amd-bank".to_string(),
-                value: 2_500_000.0,
-            }],
-        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("AMD", 387.5)
+            .with_entry("amd-bank", 2_500_000.0);
         let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
         // 2,500,000 AMD / 387.5 = ~6451.6 USD
         assert!((total - 6451.6).abs() < 1.0);
@@ -373,13 +1559,90 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_show_rows_skips_unknown_asset() {
-        let portfolio = Portfolio { assets: vec![], snapshots: vec![] };
-        let snapshot = Snapshot {
-            date: "2025-01-01".to_string(),
-            rates: HashMap::new(),
-            entries: vec![SnapshotEntry { asset_id: "ghost".to_string(), value: 100.0 }],
+    fn test_compute_show_rows_derived_asset() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("house", "House", "property", "EUR"),
+                Asset {
+                    percent_of: Some(PercentOf { asset_id: "house".to_string(), fraction: 0.4 }),
+                    ..Asset::new("equity", "House equity", "property", "EUR")
+                },
+            ],
+            ..Default::default()
+        };
+        // A stored entry for the derived asset is replaced by the computed one
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("house", 500_000.0)
+            .with_entry("equity", 1.0);
+        let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        assert_eq!(rows.len(), 2);
+        let equity = rows.iter().find(|r| r.asset_id == "equity").unwrap();
+        assert!((equity.native_value - 200_000.0).abs() < 0.01);
+        assert!((equity.usd_value - 250_000.0).abs() < 0.01);
+        assert!((total - 875_000.0).abs() < 0.01);
+
+        // Without the parent's entry there is nothing to derive from
+        let snapshot = Snapshot::new("2025-02-01").with_rate("EUR", 0.8);
+        let (_, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_compute_show_rows_unrealized_gain() {
+        let asset = |id: &str, cost_basis: Option<f64>| Asset {
+            cost_basis,
+            ..Asset::new(id, id, "etf", "EUR")
+        };
+        let portfolio = Portfolio {
+            assets: vec![
+                asset("gain", Some(800.0)),
+                asset("loss", Some(1500.0)),
+                asset("plain", None),
+            ],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.5)
+            .with_entry("gain", 500.0)
+            .with_entry("loss", 500.0)
+            .with_entry("plain", 500.0);
+        let (_, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        // Each holding is worth 1000 USD; the basis is already in USD
+        let gains: Vec<Option<f64>> = rows.iter().map(|r| r.gain_usd).collect();
+        assert_eq!(gains, vec![Some(200.0), Some(-500.0), None]);
+    }
+
+    #[test]
+    fn test_missing_asset_rows_are_zero() {
+        let asset = |id: &str, category: &str| Asset {
+            cost_basis: Some(100.0),
+            ..Asset::new(id, id, category, "USD")
         };
+        let portfolio = Portfolio {
+            assets: vec![asset("vti", "etf"), asset("bnd", "etf"), asset("cash", "bank")],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01").with_entry("vti", 1000.0);
+        let missing = missing_asset_rows(&snapshot, &portfolio, None);
+        let ids: Vec<&str> = missing.iter().map(|r| r.asset_id.as_str()).collect();
+        assert_eq!(ids, vec!["bnd", "cash"]);
+        assert!(missing.iter().all(|r| r.usd_value == 0.0 && r.native_value == 0.0));
+        assert!(missing.iter().all(|r| r.gain_usd.is_none()));
+
+        let (total, _) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        let padded: f64 = missing.iter().map(|r| r.usd_value).sum::<f64>() + total;
+        assert_eq!(padded, total);
+
+        let etf_only = missing_asset_rows(&snapshot, &portfolio, Some("etf"));
+        assert_eq!(etf_only.len(), 1);
+        assert_eq!(etf_only[0].asset_id, "bnd");
+    }
+
+    #[test]
+    fn test_compute_show_rows_skips_unknown_asset() {
+        let portfolio = Portfolio::default();
+        let snapshot = Snapshot::new("2025-01-01").with_entry("ghost", 100.0);
         let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
         assert_eq!(total, 0.0);
         assert!(rows.is_empty());
@@ -389,32 +1652,810 @@ mod tests {
     fn test_compute_show_rows_category_filter() {
         let portfolio = Portfolio {
             assets: vec![
-                Asset {
-                    id: "vti".to_string(),
-                    name: "VTI".to_string(),
-                    category: "etf".to_string(),
-                    currency: "USD".to_string(),
-                },
-                Asset {
-                    id: "btc".to_string(),
-                    name: "Bitcoin".to_string(),
-                    category: "crypto".to_string(),
-                    currency: "USD".to_string(),
-                },
+                Asset::new("vti", "VTI", "etf", "USD"),
+                Asset::new("btc", "Bitcoin", "crypto", "USD"),
             ],
             snapshots: vec![],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_entry("vti", 12500.0)
+            .with_entry("btc", 3200.0);
+        let (total, rows) = compute_show_rows(&snapshot, &portfolio, Some("etf")).unwrap();
+        assert!((total - 12500.0).abs() < 0.01);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].asset_name, "VTI");
+    }
+
+    #[test]
+    fn test_compute_show_rows_sums_lots() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            ..Default::default()
         };
         let snapshot = Snapshot {
             date: "2025-01-01".to_string(),
-            rates: HashMap::new(),
-            entries: vec![
-                SnapshotEntry { asset_id: "vti".to_string(), value: 12500.0 },
-                SnapshotEntry { asset_id: "btc".to_string(), value: 3200.0 },
+            entries: vec![SnapshotEntry {
+                asset_id: "vti".to_string(),
+                value: 1750.0,
+                lots: vec![
+                    Lot { date: "2022-03-01".to_string(), value: 1000.0 },
+                    Lot { date: "2023-03-01".to_string(), value: 500.0 },
+                    Lot { date: "2024-03-01".to_string(), value: 250.0 },
+                ],
+            }],
+            ..Default::default()
+        };
+        let (total, rows) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        assert!((total - 1750.0).abs() < 0.01);
+        assert!((rows[0].native_value - 1750.0).abs() < 0.01);
+    }
+
+    // ---- compute_show_rows_lenient ----
+
+    #[test]
+    fn test_compute_show_rows_lenient_collects_missing_rates() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("vti", "VTI", "etf", "USD"),
+                Asset::new("cash", "EUR Cash", "cash", "EUR"),
             ],
+            ..Default::default()
         };
-        let (total, rows) = compute_show_rows(&snapshot, &portfolio, Some("etf")).unwrap();
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_entry("vti", 12500.0)
+            .with_entry("cash", 800.0);
+        assert!(compute_show_rows(&snapshot, &portfolio, None).is_err());
+
+        let (total, rows, missing) =
+            compute_show_rows_lenient(&snapshot, &portfolio, None).unwrap();
         assert!((total - 12500.0).abs() < 0.01);
         assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0].asset_name, "VTI");
+        assert_eq!(missing, vec![("EUR Cash".to_string(), "EUR".to_string())]);
+    }
+
+    #[test]
+    fn test_compute_show_rows_lenient_keeps_other_errors() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            ..Default::default()
+        };
+        let mut snapshot = Snapshot::new("2025-01-01").with_entry("vti", 1000.0);
+        snapshot.entries[0].lots = vec![Lot { date: "2024-06-01".to_string(), value: 400.0 }];
+        let result = compute_show_rows_lenient(&snapshot, &portfolio, None);
+        assert!(matches!(result, Err(NwError::LotSumMismatch { .. })));
+    }
+
+    // ---- compute_history_rows ----
+
+    #[test]
+    fn test_compute_history_rows_category_filter() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("vti", "VTI", "etf", "USD"),
+                Asset::new("btc", "Bitcoin", "crypto", "USD"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 10000.0).with_entry("btc", 2000.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 11000.0).with_entry("btc", 3000.0),
+            ],
+            ..Default::default()
+        };
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let rows = compute_history_rows(&snapshots, &portfolio, Some("crypto"), None).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!((rows[0].total_usd - 2000.0).abs() < 0.01);
+        assert!((rows[1].total_usd - 3000.0).abs() < 0.01);
+        assert!((rows[1].change_usd.unwrap() - 1000.0).abs() < 0.01);
+
+        let all = compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        assert!((all[1].total_usd - 14000.0).abs() < 0.01);
+    }
+
+    // ---- snapshots_missing_rate ----
+
+    #[test]
+    fn test_snapshots_missing_rate() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("cash", "Cash", "cash", "EUR")],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_rate("CHF", 0.9).with_entry("cash", 100.0),
+                Snapshot::new("2025-02-01").with_rate("EUR", 0.92).with_entry("cash", 100.0),
+                Snapshot::new("2025-03-01"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(snapshots_missing_rate(&portfolio, "cash"), vec!["2025-01-01".to_string()]);
+        assert!(snapshots_missing_rate(&portfolio, "ghost").is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_reference_counts_with_draft() {
+        let mut draft = Snapshot::new("2025-03-01").with_entry("vti", 300.0);
+        draft.draft = true;
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 100.0),
+                Snapshot::new("2025-02-01"),
+                draft,
+            ],
+            ..Default::default()
+        };
+        assert_eq!(snapshot_reference_counts(&portfolio, "vti"), (2, 1));
+        assert_eq!(snapshot_reference_counts(&portfolio, "ghost"), (0, 0));
+    }
+
+    #[test]
+    fn test_unknown_asset_rows_synthesized() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            ..Default::default()
+        };
+        let snapshot =
+            Snapshot::new("2025-01-01").with_entry("vti", 1000.0).with_entry("old-btc", 250.0);
+        let rows = unknown_asset_rows(&snapshot, &portfolio, None);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].asset_name, "(deleted: old-btc)");
+        assert_eq!(rows[0].category, UNKNOWN_CATEGORY);
+        assert_eq!(rows[0].native_value, 250.0);
+        assert_eq!(rows[0].native_decimals, 0);
+        assert_eq!(rows[0].usd_value, 0.0);
+        let fractional = Snapshot::new("2025-01-01").with_entry("old-btc", 0.1234);
+        assert_eq!(unknown_asset_rows(&fractional, &portfolio, None)[0].native_decimals, 4);
+
+        assert_eq!(unknown_asset_rows(&snapshot, &portfolio, Some("unknown")).len(), 1);
+        assert!(unknown_asset_rows(&snapshot, &portfolio, Some("etf")).is_empty());
+        // Known entries are still the only ones compute_show_rows counts
+        let (total, _) = compute_show_rows(&snapshot, &portfolio, None).unwrap();
+        assert_eq!(total, 1000.0);
+    }
+
+    // ---- apply_comparison ----
+
+    #[test]
+    fn test_apply_comparison_per_row_changes() {
+        let portfolio = eur_portfolio();
+        let current = Snapshot::new("2025-06-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("cash", 1000.0)
+            .with_entry("vti", 500.0);
+        let compare = Snapshot::new("2024-12-31").with_rate("EUR", 0.5).with_entry("cash", 1000.0);
+        let (_, mut rows) = compute_show_rows(&current, &portfolio, None).unwrap();
+        apply_comparison(&mut rows, &compare).unwrap();
+
+        let change = |id: &str| rows.iter().find(|r| r.asset_id == id).unwrap().change_usd;
+        // 1000 EUR: $2000 at 0.5 then $1250 at 0.8
+        assert!((change("cash").unwrap() - -750.0).abs() < 1e-9);
+        // Not in the comparison snapshot: the full value is the change
+        assert_eq!(change("vti"), Some(500.0));
+
+        let pct = |id: &str| rows.iter().find(|r| r.asset_id == id).unwrap().change_pct;
+        assert!((pct("cash").unwrap() - -37.5).abs() < 1e-9);
+        // A zero baseline has no percentage
+        assert_eq!(pct("vti"), None);
+    }
+
+    #[test]
+    fn test_apply_comparison_skips_deleted_assets() {
+        let portfolio = eur_portfolio();
+        let current = Snapshot::new("2025-06-01").with_entry("vti", 500.0).with_entry("old", 9.0);
+        let compare = Snapshot::new("2024-12-31").with_entry("old", 5.0);
+        let (_, mut rows) = compute_show_rows(&current, &portfolio, None).unwrap();
+        rows.extend(unknown_asset_rows(&current, &portfolio, None));
+        apply_comparison(&mut rows, &compare).unwrap();
+        let deleted = rows.iter().find(|r| r.asset_id == "old").unwrap();
+        assert_eq!((deleted.change_usd, deleted.change_pct), (None, None));
+    }
+
+    #[test]
+    fn test_change_pct_of_totals() {
+        assert!((change_pct_of(1750.0, -250.0).unwrap() - -12.5).abs() < 1e-9);
+        assert_eq!(change_pct_of(500.0, 500.0), None);
+    }
+
+    // ---- suggest_similar ----
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("vti", "vti"), 0);
+        assert_eq!(levenshtein("vtii", "vti"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest_similar_ranks_closest_first() {
+        let ids = ["vxus", "vti-brokerage", "vti", "vt", "btc"];
+        assert_eq!(suggest_similar("vtii", &ids), vec!["vti", "vt"]);
+        assert_eq!(suggest_similar("VTI-BROKERAG", &ids), vec!["vti-brokerage"]);
+        assert!(suggest_similar("savings-chf", &ids).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_similar_caps_and_breaks_ties_alphabetically() {
+        let ids = ["ab", "ac", "aa", "ad"];
+        assert_eq!(suggest_similar("a", &ids), vec!["aa", "ab", "ac"]);
+    }
+
+    // ---- empty_snapshot_dates ----
+
+    #[test]
+    fn test_empty_snapshot_dates() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01").with_entry("vti", 100.0),
+            Snapshot::new("2025-02-01"),
+        ];
+        assert_eq!(empty_snapshot_dates(&snapshots), vec!["2025-02-01"]);
+    }
+
+    // ---- drafts ----
+
+    fn make_draft(date: &str) -> Snapshot {
+        Snapshot { draft: true, ..Snapshot::new(date) }
+    }
+
+    #[test]
+    fn test_filter_by_range_excludes_drafts_by_default() {
+        let snapshots = vec![
+            Snapshot::new("2025-01-01"),
+            make_draft("2025-02-01"),
+            Snapshot::new("2025-03-01"),
+        ];
+        let result = filter_by_range(&snapshots, HistoryRange::All, "2025-03-31", 1, false);
+        let dates: Vec<&str> = result.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-01", "2025-03-01"]);
+    }
+
+    #[test]
+    fn test_filter_by_range_includes_drafts_on_request() {
+        let snapshots = vec![Snapshot::new("2025-01-01"), make_draft("2025-02-01")];
+        let result = filter_by_range(&snapshots, HistoryRange::All, "2025-03-31", 1, true);
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_by_note_measures_changes_between_matches() {
+        let noted = |date: &str, value: f64, note: Option<&str>| Snapshot {
+            entries: vec![make_entry("vti", value)],
+            note: note.map(str::to_string),
+            ..Snapshot::new(date)
+        };
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            snapshots: vec![
+                noted("2025-01-01", 1000.0, Some("Year-end BONUS paid")),
+                noted("2025-02-01", 5000.0, None),
+                noted("2025-03-01", 900.0, Some("quiet month")),
+                noted("2025-04-01", 1500.0, Some("spring bonus")),
+            ],
+            ..Default::default()
+        };
+        let all: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let matching = filter_by_note(all, "bonus");
+        let dates: Vec<&str> = matching.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-01", "2025-04-01"]);
+
+        // The change skips the non-matching snapshots in between
+        let rows = compute_history_rows(&matching, &portfolio, None, None).unwrap();
+        assert!((rows[1].change_usd.unwrap() - 500.0).abs() < 0.01);
+        assert!((rows[1].change_pct.unwrap() - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_visible_snapshots_skip_draft_latest() {
+        let snapshots = vec![Snapshot::new("2025-01-01"), make_draft("2025-02-01")];
+        let visible = visible_snapshots(&snapshots, false);
+        assert_eq!(visible.last().unwrap().date, "2025-01-01");
+        assert_eq!(snapshot_at_offset(&visible, -1).unwrap().date, "2025-01-01");
+        let all = visible_snapshots(&snapshots, true);
+        assert_eq!(all.last().unwrap().date, "2025-02-01");
+    }
+
+    #[test]
+    fn test_draft_flag_round_trip() {
+        let json = serde_json::to_string(&Snapshot::new("2025-01-01")).unwrap();
+        assert!(!json.contains("draft"));
+        let json = serde_json::to_string(&make_draft("2025-02-01")).unwrap();
+        let back: Snapshot = serde_json::from_str(&json).unwrap();
+        assert!(back.draft);
+    }
+
+    // ---- snapshot_at_offset ----
+
+    #[test]
+    fn test_snapshot_at_offset_counts_from_the_end() {
+        let snapshots = [
+            Snapshot::new("2025-01-01"),
+            Snapshot::new("2025-02-01"),
+            Snapshot::new("2025-03-01"),
+        ];
+        let snapshots: Vec<&Snapshot> = snapshots.iter().collect();
+        assert_eq!(snapshot_at_offset(&snapshots, -1).unwrap().date, "2025-03-01");
+        assert_eq!(snapshot_at_offset(&snapshots, -2).unwrap().date, "2025-02-01");
+        assert_eq!(snapshot_at_offset(&snapshots, -3).unwrap().date, "2025-01-01");
+    }
+
+    #[test]
+    fn test_snapshot_at_offset_out_of_range() {
+        let snapshot = Snapshot::new("2025-01-01");
+        let snapshots = vec![&snapshot];
+        assert!(matches!(
+            snapshot_at_offset(&snapshots, -2),
+            Err(NwError::SnapshotIndexOutOfRange { index: -2, count: 1 })
+        ));
+        assert!(snapshot_at_offset(&snapshots, 0).is_err());
+        assert!(snapshot_at_offset(&snapshots, 1).is_err());
+        assert!(snapshot_at_offset(&snapshots, i64::MIN).is_err());
+    }
+
+    #[test]
+    fn test_latest_complete_skips_partial_snapshots() {
+        let assets: Vec<Asset> = ["vti", "cash"]
+            .iter()
+            .map(|id| Asset::new(*id, *id, "etf", "USD"))
+            .collect();
+        let snapshots = [
+            Snapshot::new("2025-01-01").with_entry("vti", 1.0).with_entry("cash", 1.0),
+            Snapshot::new("2025-02-01").with_entry("vti", 1.0),
+        ];
+        // Latest is the partial February snapshot; latest-complete goes back to January
+        assert_eq!(snapshots.last().unwrap().date, "2025-02-01");
+        let snapshots: Vec<&Snapshot> = snapshots.iter().collect();
+        let complete = latest_complete_snapshot(&snapshots, &assets).unwrap();
+        assert_eq!(complete.date, "2025-01-01");
+        assert!(latest_complete_snapshot(&snapshots[1..], &assets).is_none());
+    }
+
+    // ---- rate_jump_warnings ----
+
+    fn make_rate_snapshot(date: &str, eur: f64) -> Snapshot {
+        Snapshot { rates: make_rates(&[("EUR", eur)]), ..Snapshot::new(date) }
+    }
+
+    #[test]
+    fn test_rate_jump_flags_tenfold_change() {
+        let snapshots = vec![
+            make_rate_snapshot("2025-01-01", 0.5),
+            make_rate_snapshot("2025-02-01", 5.0),
+            make_rate_snapshot("2025-03-01", 0.5),
+        ];
+        let jumps = rate_jump_warnings(&snapshots);
+        assert_eq!(jumps.len(), 2);
+        assert_eq!(jumps[0].currency, "EUR");
+        assert_eq!(jumps[0].previous_date, "2025-01-01");
+        assert_eq!(jumps[0].date, "2025-02-01");
+        // dropping back is also a jump
+        assert_eq!(jumps[1].date, "2025-03-01");
+    }
+
+    #[test]
+    fn test_rate_jump_ignores_normal_moves_and_gaps() {
+        let snapshots = vec![
+            make_rate_snapshot("2025-01-01", 0.92),
+            Snapshot::new("2025-02-01"),
+            make_rate_snapshot("2025-03-01", 1.5),
+        ];
+        assert!(rate_jump_warnings(&snapshots).is_empty());
+    }
+
+    // ---- resolve_rate ----
+
+    #[test]
+    fn test_resolve_rate_both_sources() {
+        assert_eq!(resolve_rate(Some(0.90), Some(0.92), RatePolicy::PreferManual), Some(0.90));
+        assert_eq!(resolve_rate(Some(0.90), Some(0.92), RatePolicy::PreferApi), Some(0.92));
+        let average = resolve_rate(Some(0.90), Some(0.92), RatePolicy::Average).unwrap();
+        assert!((average - 0.91).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_resolve_rate_one_source_ignores_policy() {
+        for policy in [RatePolicy::PreferManual, RatePolicy::PreferApi, RatePolicy::Average] {
+            assert_eq!(resolve_rate(Some(0.90), None, policy), Some(0.90));
+            assert_eq!(resolve_rate(None, Some(0.92), policy), Some(0.92));
+        }
+    }
+
+    #[test]
+    fn test_resolve_rate_neither_source() {
+        for policy in [RatePolicy::PreferManual, RatePolicy::PreferApi, RatePolicy::Average] {
+            assert_eq!(resolve_rate(None, None, policy), None);
+        }
+    }
+
+    // ---- nearest_snapshot_gap ----
+
+    #[test]
+    fn test_nearest_snapshot_gap_within_interval() {
+        let snapshots = vec![Snapshot::new("2025-01-01"), Snapshot::new("2025-02-01")];
+        // Nearest is the following snapshot, 3 days later: within a 7-day minimum
+        let (nearest, days) = nearest_snapshot_gap(&snapshots, "2025-01-29").unwrap();
+        assert_eq!((nearest.as_str(), days), ("2025-02-01", 3));
+        assert!(days < 7);
+    }
+
+    #[test]
+    fn test_nearest_snapshot_gap_outside_interval() {
+        let snapshots = vec![Snapshot::new("2025-01-01"), Snapshot::new("2025-02-01")];
+        let (nearest, days) = nearest_snapshot_gap(&snapshots, "2025-01-16").unwrap();
+        // Equidistant: the earlier snapshot is reported
+        assert_eq!((nearest.as_str(), days), ("2025-01-01", 15));
+        assert!(days >= 7);
+        assert!(nearest_snapshot_gap(&[], "2025-01-16").is_none());
+    }
+
+    // ---- history_stats ----
+
+    fn history_row(date: &str, total_usd: f64) -> HistoryRow {
+        HistoryRow {
+            date: date.to_string(),
+            total_usd,
+            change_usd: None,
+            change_pct: None,
+            annualized_pct: None,
+            attribution: None,
+            event_usd: None,
+        }
+    }
+
+    #[test]
+    fn test_history_stats_min_max_average() {
+        let rows = vec![
+            history_row("2025-01-01", 1000.0),
+            history_row("2025-02-01", 1500.0),
+            history_row("2025-03-01", 700.0),
+            history_row("2025-04-01", 1500.0),
+        ];
+        let stats = history_stats(&rows).unwrap();
+        assert_eq!(stats.min, ("2025-03-01".to_string(), 700.0));
+        // Tied maximum: the earlier date is reported
+        assert_eq!(stats.max, ("2025-02-01".to_string(), 1500.0));
+        assert_eq!(stats.average, 1175.0);
+    }
+
+    #[test]
+    fn test_history_stats_single_and_empty() {
+        let stats = history_stats(&[history_row("2025-01-01", 42.0)]).unwrap();
+        assert_eq!(stats.min, stats.max);
+        assert_eq!(stats.average, 42.0);
+        assert!(history_stats(&[]).is_none());
+    }
+
+    #[test]
+    fn test_max_drawdown_known_series() {
+        let rows = vec![
+            history_row("2025-01-01", 1000.0),
+            history_row("2025-02-01", 1200.0),
+            history_row("2025-03-01", 900.0),
+            history_row("2025-04-01", 1100.0),
+            history_row("2025-05-01", 1500.0),
+            history_row("2025-06-01", 1350.0),
+        ];
+        let drawdown = max_drawdown(&rows).unwrap();
+        // 1200 → 900 is -25%; the later 1500 → 1350 is only -10%
+        assert_eq!(drawdown.peak, ("2025-02-01".to_string(), 1200.0));
+        assert_eq!(drawdown.trough, ("2025-03-01".to_string(), 900.0));
+        assert_eq!(drawdown.change_usd, -300.0);
+        assert_eq!(drawdown.change_pct, -25.0);
+    }
+
+    #[test]
+    fn test_max_drawdown_rising_series_is_zero() {
+        let rows = vec![
+            history_row("2025-01-01", 1000.0),
+            history_row("2025-02-01", 1100.0),
+            history_row("2025-03-01", 1250.0),
+        ];
+        let drawdown = max_drawdown(&rows).unwrap();
+        assert_eq!(drawdown.change_usd, 0.0);
+        assert_eq!(drawdown.change_pct, 0.0);
+        assert_eq!(drawdown.peak, drawdown.trough);
+        assert!(max_drawdown(&[]).is_none());
+    }
+
+    #[test]
+    fn test_event_total_nets_events() {
+        let snapshot = Snapshot {
+            events: vec![
+                SnapshotEvent { kind: "deposit".to_string(), amount: 5000.0 },
+                SnapshotEvent { kind: "withdrawal".to_string(), amount: -1200.0 },
+            ],
+            ..Snapshot::new("2025-02-01")
+        };
+        assert_eq!(event_total(&snapshot), 3800.0);
+        assert_eq!(event_total(&Snapshot::new("2025-03-01")), 0.0);
+    }
+
+    #[test]
+    fn test_events_between_spans_skipped_snapshots() {
+        let with_event = |date: &str, amount: f64| Snapshot {
+            events: vec![SnapshotEvent { kind: "deposit".to_string(), amount }],
+            ..Snapshot::new(date)
+        };
+        let (jan, feb, mar) = (
+            with_event("2025-01-01", 100.0),
+            with_event("2025-02-01", 200.0),
+            with_event("2025-03-01", 400.0),
+        );
+        let all = [&jan, &feb, &mar];
+        assert_eq!(events_between(&all, "2025-01-01", "2025-03-01"), 600.0);
+        assert_eq!(events_between(&all, "2025-02-01", "2025-03-01"), 400.0);
+        assert_eq!(events_between(&all, "2025-03-01", "2025-03-01"), 0.0);
+    }
+
+    fn check_asset(id: &str, currency: &str) -> Asset {
+        Asset::new(id, id, "etf", currency)
+    }
+
+    #[test]
+    fn test_check_problems_clean_portfolio() {
+        let portfolio = Portfolio {
+            assets: vec![check_asset("vti", "USD")],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 1.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 2.0),
+            ],
+            ..Default::default()
+        };
+        assert!(check_problems(&portfolio).is_empty());
+    }
+
+    #[test]
+    fn test_check_problems_reports_each_issue() {
+        let portfolio = Portfolio {
+            assets: vec![
+                check_asset("vti", "USD"),
+                check_asset("vti", "USD"),
+                check_asset("eur-cash", "EUR"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-02-01").with_entry("ghost", 1.0).with_entry("eur-cash", 5.0),
+                Snapshot::new("2025-01-01"),
+                Snapshot::new("2025-13-01"),
+            ],
+            ..Default::default()
+        };
+        let problems = check_problems(&portfolio);
+        assert_eq!(problems.len(), 5, "{problems:?}");
+        assert!(problems[0].contains("'vti' is defined more than once"));
+        assert!(problems[1].contains("out of order"));
+        assert!(problems.iter().any(|p| p.contains("unknown asset 'ghost'")));
+        assert!(problems.iter().any(|p| p.contains("no EUR rate")));
+        assert!(problems.iter().any(|p| p.starts_with("2025-13-01: invalid date")));
+    }
+
+    // ---- annualize ----
+
+    #[test]
+    fn test_annualize_full_year_unchanged() {
+        assert!((annualize(10.0, 365) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_annualize_quarter() {
+        // 2% over a quarter compounds to ~8.27% a year
+        let pct = annualize(2.0, 91);
+        assert!((pct - 8.28).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_annualize_negative() {
+        let pct = annualize(-5.0, 182);
+        assert!(pct < -9.0 && pct > -10.0);
+    }
+
+    #[test]
+    fn test_history_rows_annualized_gap() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            snapshots: vec![
+                Snapshot::new("2024-01-01").with_entry("vti", 1000.0),
+                Snapshot::new("2024-12-31").with_entry("vti", 1100.0),
+            ],
+            ..Default::default()
+        };
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let rows = compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        assert!(rows[0].annualized_pct.is_none());
+        // 2024 is a leap year: 365 days between the two dates
+        assert!((rows[1].annualized_pct.unwrap() - 10.0).abs() < 0.01);
+        assert_eq!(days_between("2024-01-01", "2024-01-01"), Some(0));
+    }
+
+    // ---- change_attribution ----
+
+    fn eur_portfolio() -> Portfolio {
+        Portfolio {
+            assets: vec![
+                Asset::new("vti", "VTI", "etf", "USD"),
+                Asset::new("cash", "EUR Cash", "cash", "EUR"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_change_attribution_pure_fx() {
+        let portfolio = eur_portfolio();
+        let prev = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 1.0)
+            .with_entry("vti", 1000.0)
+            .with_entry("cash", 1000.0);
+        let current = Snapshot { rates: make_rates(&[("EUR", 0.8)]), ..prev.clone() };
+        let attr = change_attribution(&prev, &current, &portfolio).unwrap();
+        // 1000 EUR goes from 1000 USD to 1250 USD with no balance change
+        assert!((attr.fx_usd - 250.0).abs() < 0.01);
+        assert!(attr.value_usd.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_change_attribution_pure_value() {
+        let portfolio = eur_portfolio();
+        let prev = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("vti", 1000.0)
+            .with_entry("cash", 800.0);
+        let current = Snapshot::new("2025-02-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("vti", 1500.0)
+            .with_entry("cash", 1600.0);
+        let attr = change_attribution(&prev, &current, &portfolio).unwrap();
+        assert!(attr.fx_usd.abs() < 0.01);
+        assert!((attr.value_usd - 1500.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_since_last_delta() {
+        let mut portfolio = eur_portfolio();
+        portfolio.snapshots = vec![
+            Snapshot::new("2025-01-01").with_rate("EUR", 1.0).with_entry("vti", 500.0),
+            Snapshot::new("2025-02-01").with_rate("EUR", 1.0).with_entry("vti", 1000.0),
+            Snapshot::new("2025-03-01")
+                .with_rate("EUR", 0.8)
+                .with_entry("vti", 1100.0)
+                .with_entry("cash", 400.0),
+        ];
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let delta = since_last_delta(&snapshots, &portfolio).unwrap().unwrap();
+        assert_eq!(delta.from, ("2025-02-01".to_string(), 1000.0));
+        assert_eq!(delta.to, ("2025-03-01".to_string(), 1600.0));
+        assert!((delta.change_usd - 600.0).abs() < 0.01);
+        assert!((delta.change_pct - 60.0).abs() < 0.01);
+
+        // A category held only in the latest snapshot starts from zero
+        let cash = &delta.categories[0];
+        assert_eq!(cash.category, "cash");
+        assert_eq!((cash.from_usd, cash.to_usd, cash.change_pct), (0.0, 500.0, 0.0));
+        let etf = &delta.categories[1];
+        assert!((etf.change_usd - 100.0).abs() < 0.01);
+        assert!((etf.change_pct - 10.0).abs() < 0.01);
+
+        assert!(since_last_delta(&snapshots[..1], &portfolio).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unused_rates_pruned() {
+        let mut portfolio = eur_portfolio();
+        portfolio.assets.push(Asset::new("yen", "Yen", "cash", "JPY"));
+        let mut snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.9)
+            .with_rate("JPY", 150.0)
+            .with_entry("vti", 1000.0)
+            .with_entry("cash", 500.0)
+            .with_entry("yen", 0.0);
+        assert_eq!(unused_rates(&snapshot, &portfolio.assets), vec!["JPY"]);
+
+        snapshot.entries.retain(|e| e.asset_id != "cash");
+        let unused = unused_rates(&snapshot, &portfolio.assets);
+        assert_eq!(unused, vec!["EUR", "JPY"]);
+        snapshot.rates.retain(|c, _| !unused.contains(c));
+        assert!(snapshot.rates.is_empty());
+        assert!(unused_rates(&snapshot, &portfolio.assets).is_empty());
+    }
+
+    #[test]
+    fn test_fx_impact_single_currency() {
+        let portfolio = eur_portfolio();
+        let prev = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 1.0)
+            .with_entry("vti", 1000.0)
+            .with_entry("cash", 1000.0);
+        // EUR strengthens and the EUR balance grows; only the rate move counts
+        let current = Snapshot::new("2025-02-01")
+            .with_rate("EUR", 0.8)
+            .with_entry("vti", 1200.0)
+            .with_entry("cash", 1500.0);
+        let impact = fx_impact(&prev, &current, &portfolio).unwrap();
+        assert_eq!(impact.len(), 1);
+        assert_eq!(impact[0].0, "EUR");
+        assert!((impact[0].1 - 250.0).abs() < 0.01);
+
+        let unchanged = Snapshot { date: "2025-03-01".to_string(), ..prev.clone() };
+        assert_eq!(fx_impact(&prev, &unchanged, &portfolio).unwrap(), vec![("EUR".into(), 0.0)]);
+    }
+
+    #[test]
+    fn test_since_inception() {
+        let mut portfolio = eur_portfolio();
+        portfolio.snapshots = vec![
+            Snapshot::new("2025-01-01").with_rate("EUR", 1.0).with_entry("vti", 800.0),
+            Snapshot::new("2025-02-01").with_rate("EUR", 1.0).with_entry("vti", 1000.0),
+            Snapshot::new("2025-03-01").with_rate("EUR", 0.8).with_entry("cash", 400.0),
+        ];
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let (first, change_usd, change_pct) =
+            since_inception(&snapshots, "2025-02-01", 1000.0, &portfolio).unwrap().unwrap();
+        assert_eq!(first, "2025-01-01");
+        assert!((change_usd - 200.0).abs() < 0.01);
+        assert!((change_pct.unwrap() - 25.0).abs() < 0.01);
+
+        // Measured against the total as shown (e.g. rounded), not recomputed
+        let (_, change_usd, _) =
+            since_inception(&snapshots, "2025-03-01", 500.0, &portfolio).unwrap().unwrap();
+        assert!((change_usd - -300.0).abs() < 0.01);
+
+        let first = since_inception(&snapshots, "2025-01-01", 800.0, &portfolio).unwrap();
+        assert!(first.is_none());
+        let alone = since_inception(&snapshots[..1], "2025-01-01", 800.0, &portfolio).unwrap();
+        assert!(alone.is_none());
+    }
+
+    #[test]
+    fn test_history_rows_at_constant_latest_rates() {
+        let mut portfolio = eur_portfolio();
+        portfolio.assets.push(Asset::new("gbp", "GBP Cash", "cash", "GBP"));
+        portfolio.snapshots = vec![
+            Snapshot::new("2025-01-01")
+                .with_rate("EUR", 1.0)
+                .with_rate("GBP", 0.5)
+                .with_entry("cash", 1000.0)
+                .with_entry("gbp", 100.0),
+            Snapshot::new("2025-02-01").with_rate("EUR", 0.8).with_entry("cash", 1000.0),
+        ];
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+
+        let stored = compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        // 1000 EUR + 200 USD of GBP, then 1250 USD of EUR after the rate move
+        assert!((stored[0].total_usd - 1200.0).abs() < 0.01);
+        assert!((stored[1].total_usd - 1250.0).abs() < 0.01);
+
+        let latest = &portfolio.snapshots[1].rates;
+        let constant = compute_history_rows(&snapshots, &portfolio, None, Some(latest)).unwrap();
+        // EUR is revalued at 0.8; the latest has no GBP rate, so the first keeps its own
+        assert!((constant[0].total_usd - 1450.0).abs() < 0.01);
+        assert!((constant[1].total_usd - 1250.0).abs() < 0.01);
+        assert!((constant[1].change_usd.unwrap() + 200.0).abs() < 0.01);
+    }
+
+    // ---- cached_total_mismatches ----
+
+    #[test]
+    fn test_cached_total_mismatch_detected() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            snapshots: vec![
+                Snapshot {
+                    date: "2025-01-01".to_string(),
+                    rates: HashMap::new(),
+                    entries: vec![make_entry("vti", 12500.0)],
+                    cached_total_usd: Some(12500.0),
+                    ..Default::default()
+                },
+                Snapshot {
+                    date: "2025-02-01".to_string(),
+                    rates: HashMap::new(),
+                    entries: vec![make_entry("vti", 13000.0)],
+                    cached_total_usd: Some(99999.0),
+                    ..Default::default()
+                },
+                Snapshot::new("2025-03-01").with_entry("vti", 14000.0),
+            ],
+            ..Default::default()
+        };
+        let mismatches = cached_total_mismatches(&portfolio);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].0, "2025-02-01");
+        assert!((mismatches[0].2 - 13000.0).abs() < 0.01);
     }
 }
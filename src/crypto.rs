@@ -0,0 +1,71 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+
+/// File header identifying the encrypted format (and its version).
+const MAGIC: &[u8] = b"NWENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Encrypt `plaintext` with AES-256-GCM under a key derived from `passphrase` (Argon2id).
+/// Layout: MAGIC | salt | nonce | ciphertext+tag. A fresh salt and nonce are used each time.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, &salt));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .expect("AES-GCM encryption of an in-memory buffer cannot fail");
+
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    out
+}
+
+/// Decrypt data produced by `encrypt`.
+/// Returns `None` for a wrong passphrase, a tampered file, or an unknown header.
+pub fn decrypt(data: &[u8], passphrase: &str) -> Option<Vec<u8>> {
+    let rest = data.strip_prefix(MAGIC)?;
+    if rest.len() < SALT_LEN + NONCE_LEN {
+        return None;
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, salt));
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .expect("32-byte output and 16-byte salt are within Argon2 limits");
+    key.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let plaintext = br#"{"assets":[],"snapshots":[]}"#;
+        let data = encrypt(plaintext, "correct horse");
+        assert!(data.starts_with(MAGIC));
+        assert_ne!(&data[MAGIC.len()..], &plaintext[..]);
+        assert_eq!(decrypt(&data, "correct horse").unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_passphrase_and_garbage() {
+        let data = encrypt(b"secret", "right");
+        assert!(decrypt(&data, "wrong").is_none());
+        assert!(decrypt(b"{\"assets\":[]}", "right").is_none());
+        assert!(decrypt(MAGIC, "right").is_none());
+    }
+}
@@ -1,31 +1,65 @@
-use std::collections::BTreeMap;
-use comfy_table::{Cell, Table};
-use crate::model::{Asset, HistoryRow, ShowRow, Snapshot};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+use chrono::format::StrftimeItems;
+use chrono::NaiveDate;
+use comfy_table::{Attribute, Cell, Color, Table};
+use crate::compute;
+use crate::error::NwError;
+use crate::model::{
+    Asset, CategoryColor, GoalProgress, HistoryRow, Portfolio, Settings, ShowRow, Snapshot,
+    SnapshotDelta,
+};
 
 // ---- Number formatting ----
 
-fn fmt_currency(value: f64) -> String {
+pub fn fmt_currency(value: f64) -> String {
+    fmt_amount(value, 2)
+}
+
+/// Format with thousands separators and a fixed number of decimal places.
+/// The integer part stays an `f64` and is printed digit by digit, so values beyond the
+/// `u64` range are grouped correctly instead of saturating.
+pub fn fmt_amount(value: f64, decimals: u32) -> String {
+    let scale = 10_u64.pow(decimals);
     let abs = value.abs();
-    let mut int_part = abs.floor() as u64;
-    let mut frac = ((abs - abs.floor()) * 100.0).round() as u64;
-    if frac == 100 {
+    let mut int_part = abs.floor();
+    let mut frac = ((abs - int_part) * scale as f64).round() as u64;
+    if frac == scale {
+        // Only reachable below 2^53, where adding one is exact
         frac = 0;
-        int_part += 1;
+        int_part += 1.0;
     }
 
-    let int_str = fmt_with_commas(int_part);
+    let int_str = fmt_with_commas(&format!("{:.0}", int_part));
+    let sign = if value < 0.0 { "-" } else { "" };
 
-    if value < 0.0 {
-        format!("-{}.{:02}", int_str, frac)
+    if decimals == 0 {
+        format!("{}{}", sign, int_str)
     } else {
-        format!("{}.{:02}", int_str, frac)
+        format!("{}{}.{:0width$}", sign, int_str, frac, width = decimals as usize)
     }
 }
 
-fn fmt_with_commas(n: u64) -> String {
-    let s = n.to_string();
-    let len = s.len();
-    s.chars()
+/// Pad formatted numbers on the right so that, once right-aligned in a column, their
+/// decimal points line up even when the number of decimals differs (e.g. JPY and BTC).
+fn align_decimals(values: Vec<String>) -> Vec<String> {
+    let fraction_width = |s: &str| s.find('.').map_or(0, |i| s[i..].chars().count());
+    let widest = values.iter().map(|s| fraction_width(s)).max().unwrap_or(0);
+    values
+        .into_iter()
+        .map(|s| {
+            let pad = widest - fraction_width(&s);
+            format!("{}{}", s, " ".repeat(pad))
+        })
+        .collect()
+}
+
+/// Insert a comma every three digits from the right of a string of digits.
+fn fmt_with_commas(digits: &str) -> String {
+    let len = digits.len();
+    digits
+        .chars()
         .enumerate()
         .flat_map(|(i, c)| {
             let comma = (i > 0 && (len - i).is_multiple_of(3)).then_some(',');
@@ -34,7 +68,7 @@ fn fmt_with_commas(n: u64) -> String {
         .collect()
 }
 
-fn fmt_change(value: f64) -> String {
+pub fn fmt_change(value: f64) -> String {
     if value >= 0.0 {
         format!("+{}", fmt_currency(value))
     } else {
@@ -42,7 +76,7 @@ fn fmt_change(value: f64) -> String {
     }
 }
 
-fn fmt_pct(value: f64) -> String {
+pub fn fmt_pct(value: f64) -> String {
     if value >= 0.0 {
         format!("+{:.2}%", value)
     } else {
@@ -50,79 +84,445 @@ fn fmt_pct(value: f64) -> String {
     }
 }
 
+/// Percentage cell for a `--compare` change: "new" when there was nothing to compare to.
+fn fmt_change_pct(change_usd: Option<f64>, change_pct: Option<f64>) -> String {
+    match (change_usd, change_pct) {
+        (_, Some(pct)) => fmt_pct(pct),
+        (Some(_), None) => "new".to_string(),
+        (None, None) => "—".to_string(),
+    }
+}
+
+// ---- Color ----
+
+/// Color is used only on a terminal, and never with `--no-color` or `NO_COLOR` set.
+pub fn color_enabled(no_color_flag: bool) -> bool {
+    !no_color_flag && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+// ---- Date formatting ----
+
+/// Reject strftime patterns chrono cannot render.
+pub fn validate_date_format(pattern: &str) -> Result<(), NwError> {
+    StrftimeItems::new(pattern)
+        .parse()
+        .map(|_| ())
+        .map_err(|_| NwError::InvalidDateFormat(pattern.to_string()))
+}
+
+/// Render a stored ISO date with an optional strftime pattern.
+/// Falls back to the stored string if either the date or the pattern is unusable.
+fn fmt_date(date: &str, pattern: Option<&str>) -> String {
+    let Some(pattern) = pattern else {
+        return date.to_string();
+    };
+    let Ok(parsed) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        return date.to_string();
+    };
+    let mut out = String::new();
+    match write!(out, "{}", parsed.format(pattern)) {
+        Ok(()) => out,
+        Err(_) => date.to_string(),
+    }
+}
+
 // ---- nw show ----
 
+/// Presentation options for `print_show`.
+#[derive(Default)]
+pub struct ShowOptions<'a> {
+    pub category_filter: Option<&'a str>,
+    /// Hide rows below this USD value (still counted in totals).
+    pub min_value: Option<f64>,
+    pub date_format: Option<&'a str>,
+    /// Flag assets whose share of the total exceeds this percentage.
+    pub highlight_above: Option<f64>,
+    /// Total revalued at another snapshot's rates, with that snapshot's date.
+    pub at_latest_rates: Option<(&'a str, f64)>,
+    /// (asset name, currency) of holdings excluded for lack of a rate.
+    pub missing_rates: &'a [(String, String)],
+    pub color: bool,
+    /// Source of category header colors (used only when `color` is set).
+    pub settings: Option<&'a Settings>,
+    /// Date of the `--compare` snapshot; adds a change column from `ShowRow::change_usd`.
+    pub compare_date: Option<&'a str>,
+    /// `allocation` holds holdings' shares of the filtered category rather than categories.
+    pub allocation_within: bool,
+    /// Progress toward category goals, listed after the totals.
+    pub goals: &'a [GoalProgress],
+    /// Skip the allocation block.
+    pub no_allocation: bool,
+    /// Print only the allocation block, without the category tables and totals.
+    pub allocation_only: bool,
+    /// (first snapshot date, change in USD, change in percent) for the lifetime summary.
+    pub since_inception: Option<(&'a str, f64, Option<f64>)>,
+}
+
 pub fn print_show(
+    out: &mut impl io::Write,
     rows: Vec<ShowRow>,
     grand_total: f64,
     allocation: Vec<(String, f64)>,
     date: &str,
-    category_filter: Option<&str>,
-) {
-    if category_filter.is_some() {
-        println!("NET WORTH — {}", date);
+    opts: &ShowOptions,
+) -> io::Result<()> {
+    let date = fmt_date(date, opts.date_format);
+    if opts.category_filter.is_some() {
+        writeln!(out, "NET WORTH — {}", date)?;
     } else {
-        println!("CURRENT NET WORTH — {}", date);
+        writeln!(out, "CURRENT NET WORTH — {}", date)?;
+    }
+    if opts.allocation_only {
+        return print_allocation(out, &allocation, opts);
     }
 
+    let with_gain = rows.iter().any(|r| r.gain_usd.is_some());
+    let total_change: f64 = rows.iter().filter_map(|r| r.change_usd).sum();
+    let deleted = rows.iter().filter(|r| r.currency == compute::UNKNOWN_CURRENCY).count();
+
     // Group rows by category (BTreeMap for stable alphabetical order)
     let mut by_category: BTreeMap<String, Vec<ShowRow>> = BTreeMap::new();
     for row in rows {
         by_category.entry(row.category.clone()).or_default().push(row);
     }
+    // Ordered assets first by (order, name); the rest keep entry order (stable sort)
+    for cat_rows in by_category.values_mut() {
+        cat_rows.sort_by_key(|r| match r.sort_order {
+            Some(order) => (false, order, r.asset_name.clone()),
+            None => (true, 0, String::new()),
+        });
+    }
 
+    let mut hidden_count = 0;
+    let mut highlighted = false;
     for (category, cat_rows) in &by_category {
-        println!();
-        println!("{}", category.to_uppercase());
+        // Subtotals include hidden rows so they still add up to the total
+        let subtotal: f64 = cat_rows.iter().map(|r| r.usd_value).sum();
+        let unvalued = cat_rows.iter().all(|r| r.currency == compute::UNKNOWN_CURRENCY);
+        let (visible, hidden) = split_by_min_value(cat_rows, opts.min_value);
+        hidden_count += hidden;
+        if visible.is_empty() {
+            continue;
+        }
+
+        let header_color = opts
+            .settings
+            .filter(|_| opts.color)
+            .and_then(|s| s.color_for_category(category));
+        writeln!(out)?;
+        writeln!(out, "{}", category_header(category, header_color))?;
 
         let mut table = Table::new();
         table.load_preset(comfy_table::presets::NOTHING);
-        table.set_header(vec!["  Name", "Currency", "Value (native)", "Value (USD)"]);
+        let mut header = vec![
+            "  Name".to_string(),
+            "Currency".to_string(),
+            "Value (native)".to_string(),
+            "Value (USD)".to_string(),
+        ];
+        if let Some(compare_date) = opts.compare_date {
+            header.push(format!("Δ vs {}", fmt_date(compare_date, opts.date_format)));
+            header.push("Δ %".to_string());
+        }
+        if with_gain {
+            header.push("Gain (USD)".to_string());
+        }
+        table.set_header(header);
 
-        let mut subtotal = 0.0;
-        for row in cat_rows {
-            subtotal += row.usd_value;
-            table.add_row(vec![
-                Cell::new(format!("  {}", row.asset_name)),
+        if opts.color {
+            table.enforce_styling();
+        }
+
+        let natives = align_decimals(
+            visible.iter().map(|r| fmt_amount(r.native_value, r.native_decimals)).collect(),
+        );
+        for (row, native) in visible.into_iter().zip(natives) {
+            let concentrated = opts
+                .highlight_above
+                .is_some_and(|t| compute::exceeds_share(row.usd_value, grand_total, t));
+            highlighted |= concentrated;
+            let name_cell = match (concentrated, opts.color) {
+                (true, true) => Cell::new(format!("  {}", row.asset_name))
+                    .add_attribute(Attribute::Bold)
+                    .fg(Color::Yellow),
+                (true, false) => Cell::new(format!("  {} (!)", row.asset_name)),
+                (false, _) => Cell::new(format!("  {}", row.asset_name)),
+            };
+            let mut cells = vec![
+                name_cell,
                 Cell::new(&row.currency),
-                Cell::new(fmt_currency(row.native_value)).set_alignment(
-                    comfy_table::CellAlignment::Right,
-                ),
-                Cell::new(fmt_currency(row.usd_value))
+                Cell::new(native).set_alignment(comfy_table::CellAlignment::Right),
+                Cell::new(usd_cell(row.usd_value, row.currency == compute::UNKNOWN_CURRENCY))
                     .set_alignment(comfy_table::CellAlignment::Right),
-            ]);
+            ];
+            if opts.compare_date.is_some() {
+                let change = row.change_usd.map(fmt_change).unwrap_or_else(|| "—".to_string());
+                cells.push(Cell::new(change).set_alignment(comfy_table::CellAlignment::Right));
+                let pct = fmt_change_pct(row.change_usd, row.change_pct);
+                cells.push(Cell::new(pct).set_alignment(comfy_table::CellAlignment::Right));
+            }
+            if with_gain {
+                let gain = row.gain_usd.map(fmt_change).unwrap_or_default();
+                cells.push(Cell::new(gain).set_alignment(comfy_table::CellAlignment::Right));
+            }
+            table.add_row(cells);
         }
-        table.add_row(vec![
+        let mut subtotal_cells = vec![
             Cell::new("  Subtotal"),
             Cell::new(""),
             Cell::new(""),
-            Cell::new(fmt_currency(subtotal)).set_alignment(comfy_table::CellAlignment::Right),
-        ]);
+            Cell::new(usd_cell(subtotal, unvalued))
+                .set_alignment(comfy_table::CellAlignment::Right),
+        ];
+        if opts.compare_date.is_some() {
+            let change: Option<f64> = (!unvalued)
+                .then(|| cat_rows.iter().filter_map(|r| r.change_usd).sum());
+            let change_cell = Cell::new(change.map(fmt_change).unwrap_or_else(|| "—".to_string()))
+                .set_alignment(comfy_table::CellAlignment::Right);
+            subtotal_cells.push(change_cell);
+            let pct = change.and_then(|c| compute::change_pct_of(subtotal, c));
+            let pct = fmt_change_pct(change, pct);
+            subtotal_cells.push(Cell::new(pct).set_alignment(comfy_table::CellAlignment::Right));
+        }
+        if with_gain {
+            // Only holdings with a recorded basis contribute to the category's gain
+            let gains: Vec<f64> = cat_rows.iter().filter_map(|r| r.gain_usd).collect();
+            let gain = if gains.is_empty() {
+                String::new()
+            } else {
+                fmt_change(gains.iter().sum())
+            };
+            subtotal_cells.push(Cell::new(gain).set_alignment(comfy_table::CellAlignment::Right));
+        }
+        table.add_row(subtotal_cells);
+
+        writeln!(out, "{table}")?;
+    }
 
-        println!("{table}");
+    writeln!(out)?;
+    match opts.compare_date {
+        Some(compare_date) => writeln!(
+            out,
+            "TOTAL  {}  ({}, {} vs {})",
+            fmt_currency(grand_total),
+            fmt_change(total_change),
+            fmt_change_pct(Some(total_change), compute::change_pct_of(grand_total, total_change)),
+            fmt_date(compare_date, opts.date_format)
+        )?,
+        None => writeln!(out, "TOTAL  {}", fmt_currency(grand_total))?,
+    }
+    if let Some((latest_date, total)) = opts.at_latest_rates {
+        writeln!(
+            out,
+            "TOTAL AT {} RATES  {}",
+            fmt_date(latest_date, opts.date_format),
+            fmt_currency(total)
+        )?;
+    }
+
+    if hidden_count > 0 {
+        writeln!(out, "({} holdings below threshold hidden)", hidden_count)?;
+    }
+    if deleted > 0 {
+        writeln!(out, "({} deleted-asset entries not included in total)", deleted)?;
+    }
+
+    if !opts.missing_rates.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "WARNING: rate missing — excluded from total")?;
+        for (name, currency) in opts.missing_rates {
+            writeln!(out, "  {} ({})", name, currency)?;
+        }
+    }
+
+    if let (true, Some(threshold)) = (highlighted, opts.highlight_above) {
+        let marker = if opts.color { "Highlighted" } else { "(!)" };
+        writeln!(out, "{} holdings exceed {}% of the total", marker, threshold)?;
     }
 
-    println!();
-    println!("TOTAL  {}", fmt_currency(grand_total));
+    if !opts.goals.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "GOALS")?;
+        for goal in opts.goals {
+            writeln!(
+                out,
+                "  {:<12} {:>12} of {:>12}  {:>6.1}%",
+                goal.category.to_uppercase(),
+                fmt_currency(goal.current_usd),
+                fmt_currency(goal.goal_usd),
+                goal.pct
+            )?;
+        }
+    }
 
-    if category_filter.is_none() && !allocation.is_empty() {
-        println!();
-        println!("ALLOCATION");
-        for (cat, pct) in &allocation {
-            println!("  {:<12} {:>6.1}%", cat.to_uppercase(), pct);
+    if !opts.no_allocation {
+        print_allocation(out, &allocation, opts)?;
+    }
+
+    if let Some((first_date, change, pct)) = opts.since_inception {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Since first snapshot ({}): {} ({})",
+            fmt_date(first_date, opts.date_format),
+            fmt_change(change),
+            fmt_change_pct(Some(change), pct)
+        )?;
+    }
+    Ok(())
+}
+
+fn print_allocation(
+    out: &mut impl io::Write,
+    allocation: &[(String, f64)],
+    opts: &ShowOptions,
+) -> io::Result<()> {
+    if allocation.is_empty() {
+        return Ok(());
+    }
+    match (opts.allocation_within, opts.category_filter) {
+        (true, Some(category)) => {
+            writeln!(out)?;
+            writeln!(out, "ALLOCATION WITHIN {}", category.to_uppercase())?;
+            for (name, pct) in allocation {
+                writeln!(out, "  {:<20} {:>6.1}%", name, pct)?;
+            }
+        }
+        (false, None) => {
+            writeln!(out)?;
+            writeln!(out, "ALLOCATION")?;
+            for (cat, pct) in allocation {
+                writeln!(out, "  {:<12} {:>6.1}%", cat.to_uppercase(), pct)?;
+            }
         }
+        _ => {}
     }
+    Ok(())
+}
+
+/// Upper-cased category name, wrapped in the ANSI color escape when one is given.
+fn category_header(category: &str, color: Option<CategoryColor>) -> String {
+    let name = category.to_uppercase();
+    let code = match color {
+        None => return name,
+        Some(CategoryColor::Red) => 31,
+        Some(CategoryColor::Green) => 32,
+        Some(CategoryColor::Yellow) => 33,
+        Some(CategoryColor::Blue) => 34,
+        Some(CategoryColor::Magenta) => 35,
+        Some(CategoryColor::Cyan) => 36,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, name)
+}
+
+/// A USD amount for the show table, or "—" for rows with no USD value (deleted assets).
+fn usd_cell(value: f64, unvalued: bool) -> String {
+    if unvalued {
+        "—".to_string()
+    } else {
+        fmt_currency(value)
+    }
+}
+
+/// Split rows into those to display and a count of rows below `min_value` (USD).
+fn split_by_min_value(rows: &[ShowRow], min_value: Option<f64>) -> (Vec<&ShowRow>, usize) {
+    let visible: Vec<&ShowRow> = rows
+        .iter()
+        .filter(|r| min_value.is_none_or(|m| r.usd_value >= m))
+        .collect();
+    let hidden = rows.len() - visible.len();
+    (visible, hidden)
 }
 
 // ---- nw history ----
 
-pub fn print_history(rows: Vec<HistoryRow>, range_label: &str) {
-    println!("NET WORTH HISTORY — {}", range_label);
-    println!();
+/// Presentation options for `print_history`.
+#[derive(Default)]
+pub struct HistoryOptions<'a> {
+    pub date_format: Option<&'a str>,
+    /// Add an annualized change column.
+    pub annualized: bool,
+    /// Add value/FX attribution columns.
+    pub attribution: bool,
+    /// Add the maximum drawdown to the footer.
+    pub drawdown: bool,
+    /// Add event-driven and organic change columns.
+    pub events: bool,
+    /// Rows are filtered by note, so changes span the gaps between them.
+    pub note_filter: bool,
+    /// Draw a line chart of the totals instead of the table.
+    pub plot: bool,
+}
+
+/// Size of the `history --plot` chart area in characters, without the axes.
+const CHART_HEIGHT: usize = 10;
+const CHART_WIDTH: usize = 60;
+
+pub fn print_history(
+    out: &mut impl io::Write,
+    rows: Vec<HistoryRow>,
+    range_label: &str,
+    opts: &HistoryOptions,
+) -> io::Result<()> {
+    writeln!(out, "NET WORTH HISTORY — {}", range_label)?;
+    writeln!(out)?;
+
+    // With a single row the stats would only repeat it
+    let stats = if rows.len() > 1 { compute::history_stats(&rows) } else { None };
+    let drawdown = if opts.drawdown { compute::max_drawdown(&rows) } else { None };
+
+    if opts.plot {
+        let points: Vec<(String, f64)> = rows
+            .iter()
+            .map(|r| (fmt_date(&r.date, opts.date_format), r.total_usd))
+            .collect();
+        write!(out, "{}", render_line_chart(&points, CHART_HEIGHT, CHART_WIDTH))?;
+    } else {
+        writeln!(out, "{}", history_table(rows, opts))?;
+    }
+    if opts.note_filter {
+        writeln!(out, "Changes are between the listed snapshots only.")?;
+    }
+
+    if stats.is_some() || drawdown.is_some() {
+        writeln!(out)?;
+    }
+    if let Some(stats) = stats {
+        for (label, (date, total)) in [("Low", &stats.min), ("High", &stats.max)] {
+            let date = fmt_date(date, opts.date_format);
+            writeln!(out, "{:<8} {:>12}  ({})", label, fmt_currency(*total), date)?;
+        }
+        writeln!(out, "{:<8} {:>12}", "Average", fmt_currency(stats.average))?;
+    }
+    if let Some(drawdown) = drawdown {
+        let amount = fmt_currency(drawdown.change_usd);
+        if drawdown.change_usd < 0.0 {
+            let peak = fmt_date(&drawdown.peak.0, opts.date_format);
+            let trough = fmt_date(&drawdown.trough.0, opts.date_format);
+            let pct = fmt_pct(drawdown.change_pct);
+            writeln!(out, "{:<8} {:>12}  ({}, {} → {})", "Drawdown", amount, pct, peak, trough)?;
+        } else {
+            writeln!(out, "{:<8} {:>12}  (no decline)", "Drawdown", amount)?;
+        }
+    }
+    Ok(())
+}
 
+/// The `history` table: one row per snapshot with the change columns `opts` asks for.
+fn history_table(rows: Vec<HistoryRow>, opts: &HistoryOptions) -> Table {
     let mut table = Table::new();
     table.load_preset(comfy_table::presets::NOTHING);
-    table.set_header(vec!["Date", "Total (USD)", "Change (USD)", "Change %"]);
+    let mut header = vec!["Date", "Total (USD)", "Change (USD)", "Change %"];
+    if opts.annualized {
+        header.push("Annualized %");
+    }
+    if opts.attribution {
+        header.extend(["Value Δ (USD)", "FX Δ (USD)"]);
+    }
+    if opts.events {
+        header.extend(["Events (USD)", "Organic (USD)"]);
+    }
+    table.set_header(header);
 
     for row in rows {
         let change_usd = row
@@ -133,49 +533,361 @@ pub fn print_history(rows: Vec<HistoryRow>, range_label: &str) {
             .change_pct
             .map(fmt_pct)
             .unwrap_or_else(|| "—".to_string());
-        table.add_row(vec![
-            Cell::new(&row.date),
+        let mut cells = vec![
+            Cell::new(fmt_date(&row.date, opts.date_format)),
             Cell::new(fmt_currency(row.total_usd))
                 .set_alignment(comfy_table::CellAlignment::Right),
             Cell::new(change_usd).set_alignment(comfy_table::CellAlignment::Right),
             Cell::new(change_pct).set_alignment(comfy_table::CellAlignment::Right),
+        ];
+        if opts.annualized {
+            let annualized = row
+                .annualized_pct
+                .map(fmt_pct)
+                .unwrap_or_else(|| "—".to_string());
+            cells.push(Cell::new(annualized).set_alignment(comfy_table::CellAlignment::Right));
+        }
+        if opts.attribution {
+            let (value, fx) = row
+                .attribution
+                .map(|a| (fmt_change(a.value_usd), fmt_change(a.fx_usd)))
+                .unwrap_or_else(|| ("—".to_string(), "—".to_string()));
+            cells.push(Cell::new(value).set_alignment(comfy_table::CellAlignment::Right));
+            cells.push(Cell::new(fx).set_alignment(comfy_table::CellAlignment::Right));
+        }
+        if opts.events {
+            let (events, organic) = match (row.event_usd, row.change_usd) {
+                (Some(events), Some(change)) => (fmt_change(events), fmt_change(change - events)),
+                _ => ("—".to_string(), "—".to_string()),
+            };
+            cells.push(Cell::new(events).set_alignment(comfy_table::CellAlignment::Right));
+            cells.push(Cell::new(organic).set_alignment(comfy_table::CellAlignment::Right));
+        }
+        table.add_row(cells);
+    }
+    table
+}
+
+/// Plot `points` (label, value) as a line chart of `height` rows by `width` columns. The
+/// y-axis shows the rounded top, middle, and bottom values; the x-axis the first, middle,
+/// and last labels where they fit. Data points are `*` and the line between them `.`.
+/// A flat series runs along the middle row; a single point sits in the first column.
+pub fn render_line_chart(points: &[(String, f64)], height: usize, width: usize) -> String {
+    if points.is_empty() || height == 0 || width == 0 {
+        return String::new();
+    }
+    let values: Vec<f64> = points.iter().map(|(_, v)| *v).collect();
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let middle = (height - 1) / 2;
+    let row_of = |v: f64| match max - min {
+        0.0 => middle,
+        span => ((max - v) / span * (height - 1) as f64).round() as usize,
+    };
+    let last = values.len() - 1;
+    // Rounded to the nearest column; a single point has no spread
+    let col_of = |i: usize| (i * (width - 1) + last / 2).checked_div(last).unwrap_or(0);
+
+    let mut grid = vec![vec![' '; width]; height];
+    if last > 0 {
+        // Each column samples the straight line between its neighbouring points
+        let line = (0..width).map(|col| {
+            let t = if width > 1 { (col * last) as f64 / (width - 1) as f64 } else { 0.0 };
+            let i = (t.floor() as usize).min(last - 1);
+            row_of(values[i] + (values[i + 1] - values[i]) * (t - i as f64))
+        });
+        for (col, row) in line.enumerate() {
+            grid[row][col] = '.';
+        }
+    }
+    for (i, &v) in values.iter().enumerate() {
+        grid[row_of(v)][col_of(i)] = '*';
+    }
+
+    let mut labels = vec![String::new(); height];
+    if max == min {
+        labels[middle] = fmt_amount(max, 0);
+    } else {
+        labels[0] = fmt_amount(max, 0);
+        labels[height - 1] = fmt_amount(min, 0);
+        if middle > 0 {
+            let value = max - (max - min) * middle as f64 / (height - 1) as f64;
+            labels[middle] = fmt_amount(value, 0);
+        }
+    }
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+    let mut chart = String::new();
+    for (label, row) in labels.iter().zip(&grid) {
+        let row: String = row.iter().collect();
+        let _ = writeln!(chart, "{:>w$} |{}", label, row, w = label_width);
+    }
+    let _ = writeln!(chart, "{:>w$} +{}", "", "-".repeat(width), w = label_width);
+
+    // First and last labels at the ends, the middle one centered under its point
+    let mut axis: Vec<char> = Vec::new();
+    let mut place = |label: &str, start: usize| {
+        let fits = axis.len() <= start.saturating_sub(1) || (axis.is_empty() && start == 0);
+        if fits {
+            axis.resize(start, ' ');
+            axis.extend(label.chars());
+        }
+    };
+    let label_len = |i: usize| points[i].0.chars().count();
+    place(&points[0].0, 0);
+    if last >= 2 {
+        let mid = last / 2;
+        place(&points[mid].0, col_of(mid).saturating_sub(label_len(mid) / 2));
+    }
+    if last >= 1 {
+        place(&points[last].0, width.saturating_sub(label_len(last)));
+    }
+    let axis: String = axis.into_iter().collect();
+    let _ = writeln!(chart, "{:>w$}  {}", "", axis, w = label_width);
+    chart
+}
+
+/// Snapshot selection for `history --explain`: the range cutoff, then every snapshot
+/// marked as included or with the reason it was left out.
+pub fn print_range_explain(
+    out: &mut impl io::Write,
+    cutoff: Option<&str>,
+    snapshots: &[Snapshot],
+    included: &[&Snapshot],
+) -> io::Result<()> {
+    match cutoff {
+        Some(c) => writeln!(out, "Cutoff: {} (snapshots on or after this date are included)", c)?,
+        None => writeln!(out, "Cutoff: none (all dates are included)")?,
+    }
+    for s in snapshots {
+        let status = if included.iter().any(|i| std::ptr::eq(*i, s)) {
+            "included"
+        } else if cutoff.is_some_and(|c| s.date.as_str() < c) {
+            "excluded (before cutoff)"
+        } else if s.draft {
+            "excluded (draft)"
+        } else {
+            "excluded (note filter)"
+        };
+        writeln!(out, "  {}  {}", s.date, status)?;
+    }
+    writeln!(out)
+}
+
+/// Two-snapshot comparison for `history --since-last`.
+pub fn print_since_last(
+    out: &mut impl io::Write,
+    delta: &SnapshotDelta,
+    date_format: Option<&str>,
+) -> io::Result<()> {
+    let from = fmt_date(&delta.from.0, date_format);
+    let to = fmt_date(&delta.to.0, date_format);
+    writeln!(out, "CHANGE SINCE LAST SNAPSHOT — {} → {}", from, to)?;
+    writeln!(out)?;
+
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    let header =
+        vec!["Category".to_string(), from.clone(), to, "Change (USD)".into(), "Change %".into()];
+    table.set_header(header);
+    let rows = delta
+        .categories
+        .iter()
+        .map(|c| (c.category.to_uppercase(), c.from_usd, c.to_usd, c.change_usd, c.change_pct))
+        .chain(std::iter::once((
+            "TOTAL".to_string(),
+            delta.from.1,
+            delta.to.1,
+            delta.change_usd,
+            delta.change_pct,
+        )));
+    for (name, from_usd, to_usd, change_usd, change_pct) in rows {
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(fmt_currency(from_usd)).set_alignment(comfy_table::CellAlignment::Right),
+            Cell::new(fmt_currency(to_usd)).set_alignment(comfy_table::CellAlignment::Right),
+            Cell::new(fmt_change(change_usd)).set_alignment(comfy_table::CellAlignment::Right),
+            Cell::new(fmt_change_pct(Some(change_usd), (from_usd != 0.0).then_some(change_pct)))
+                .set_alignment(comfy_table::CellAlignment::Right),
+        ]);
+    }
+    writeln!(out, "{table}")?;
+
+    if !delta.fx_impact.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "FX IMPACT (balances held at {} levels)", from)?;
+        for (currency, usd) in &delta.fx_impact {
+            writeln!(out, "  {:<5} {:>12}", currency, fmt_change(*usd))?;
+        }
+    }
+    Ok(())
+}
+
+// ---- nw total ----
+
+/// `nw total --oneline`: whole dollars and, when given, the percent change with an arrow,
+/// e.g. "$128,430 ▲ +2.3%". With `color` the arrow and change are green or red.
+pub fn fmt_oneline(total: f64, change_pct: Option<f64>, color: bool) -> String {
+    let sign = if total < 0.0 { "-" } else { "" };
+    let amount = format!("{}${}", sign, fmt_amount(total.abs(), 0));
+    let Some(pct) = change_pct else {
+        return amount;
+    };
+    let (arrow, code) = if pct >= 0.0 { ("▲", 32) } else { ("▼", 31) };
+    let trend = format!("{} {}{:.1}%", arrow, if pct >= 0.0 { "+" } else { "" }, pct);
+    if color {
+        format!("{} \x1b[{}m{}\x1b[0m", amount, code, trend)
+    } else {
+        format!("{} {}", amount, trend)
+    }
+}
+
+// ---- nw exposure ----
+
+/// Currency exposure table: `exposure` is (currency, percent) sorted by share, `totals`
+/// holds each currency's USD value.
+pub fn print_exposure(
+    out: &mut impl io::Write,
+    exposure: &[(String, f64)],
+    totals: &HashMap<String, f64>,
+    date: &str,
+    date_format: Option<&str>,
+) -> io::Result<()> {
+    writeln!(out, "CURRENCY EXPOSURE — {}", fmt_date(date, date_format))?;
+    writeln!(out)?;
+
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    table.set_header(vec!["Currency", "Value (USD)", "Share"]);
+    for (currency, pct) in exposure {
+        let value = totals.get(currency).copied().unwrap_or(0.0);
+        table.add_row(vec![
+            Cell::new(currency),
+            Cell::new(fmt_currency(value)).set_alignment(comfy_table::CellAlignment::Right),
+            Cell::new(format!("{:.1}%", pct)).set_alignment(comfy_table::CellAlignment::Right),
         ]);
     }
 
-    println!("{table}");
+    writeln!(out, "{table}")?;
+    Ok(())
 }
 
 // ---- nw asset list ----
 
-pub fn print_asset_list(assets: &[Asset]) {
+pub fn print_asset_list(out: &mut impl io::Write, assets: &[Asset]) -> io::Result<()> {
     if assets.is_empty() {
-        println!("No assets yet.");
-        return;
+        writeln!(out, "No assets yet.")?;
+        return Ok(());
     }
 
     let mut table = Table::new();
     table.load_preset(comfy_table::presets::NOTHING);
-    table.set_header(vec!["ID", "Name", "Category", "Currency"]);
+    let with_notes = assets.iter().any(|a| a.note.is_some());
+    let mut header = vec!["ID", "Name", "Category", "Currency"];
+    if with_notes {
+        header.push("Note");
+    }
+    table.set_header(header);
 
     for asset in assets {
-        table.add_row(vec![&asset.id, &asset.name, &asset.category, &asset.currency]);
+        let mut row = vec![
+            asset.id.clone(),
+            asset.name.clone(),
+            asset.category.clone(),
+            asset.currency.clone(),
+        ];
+        if with_notes {
+            row.push(asset.note.as_deref().map_or_else(String::new, note_preview));
+        }
+        table.add_row(row);
     }
 
-    println!("{table}");
+    writeln!(out, "{table}")?;
+    Ok(())
+}
+
+/// Longest note shown in `asset list`; `asset show` prints it in full.
+const NOTE_PREVIEW_CHARS: usize = 30;
+
+/// First line of a note, cut to `NOTE_PREVIEW_CHARS` with an ellipsis when longer.
+fn note_preview(note: &str) -> String {
+    let first_line = note.lines().next().unwrap_or("");
+    let truncated = first_line.chars().count() > NOTE_PREVIEW_CHARS
+        || note.trim_end().len() > first_line.len();
+    if truncated {
+        let cut: String = first_line.chars().take(NOTE_PREVIEW_CHARS - 1).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        first_line.to_string()
+    }
+}
+
+// ---- nw asset show ----
+
+/// An asset's plausible value range, e.g. "1,000.00 – 5,000.00" or "at most 5,000.00".
+fn fmt_bounds(asset: &Asset) -> String {
+    match (asset.min_value, asset.max_value) {
+        (Some(min), Some(max)) => format!("{} – {}", fmt_currency(min), fmt_currency(max)),
+        (Some(min), None) => format!("at least {}", fmt_currency(min)),
+        (None, Some(max)) => format!("at most {}", fmt_currency(max)),
+        (None, None) => "any".to_string(),
+    }
+}
+
+pub fn print_asset_detail(
+    out: &mut impl io::Write,
+    asset: &Asset,
+    portfolio: &Portfolio,
+) -> io::Result<()> {
+    let snapshot_count = portfolio
+        .snapshots
+        .iter()
+        .filter(|s| s.entries.iter().any(|e| e.asset_id == asset.id))
+        .count();
+    writeln!(out, "ID:         {}", asset.id)?;
+    writeln!(out, "Name:       {}", asset.name)?;
+    writeln!(out, "Category:   {}", asset.category)?;
+    writeln!(out, "Currency:   {}", asset.currency)?;
+    if let Some(basis) = asset.cost_basis {
+        writeln!(out, "Cost basis: {}", fmt_currency(basis))?;
+    }
+    if asset.min_value.is_some() || asset.max_value.is_some() {
+        writeln!(out, "Range:      {} {}", fmt_bounds(asset), asset.currency)?;
+    }
+    if let Some(order) = asset.sort_order {
+        writeln!(out, "Order:      {}", order)?;
+    }
+    if let Some(percent_of) = &asset.percent_of {
+        let percent = percent_of.fraction * 100.0;
+        writeln!(out, "Derived:    {:.2}% of {}", percent, percent_of.asset_id)?;
+    }
+    writeln!(out, "Snapshots:  {}", snapshot_count)?;
+    if let Some(note) = &asset.note {
+        writeln!(out)?;
+        writeln!(out, "{}", note)?;
+    }
+    Ok(())
 }
 
 // ---- nw snapshot list ----
 
-pub fn print_snapshot_list(snapshots: &[Snapshot]) {
+pub fn print_snapshot_list(
+    out: &mut impl io::Write,
+    snapshots: &[&Snapshot],
+    portfolio: &Portfolio,
+) -> io::Result<()> {
+    let assets = &portfolio.assets;
     if snapshots.is_empty() {
-        println!("No snapshots yet.");
-        return;
+        writeln!(out, "No snapshots yet.")?;
+        return Ok(());
     }
 
     let mut table = Table::new();
     table.load_preset(comfy_table::presets::NOTHING);
-    table.set_header(vec!["Date", "Entries", "Currencies"]);
+    table.set_header(vec!["Date", "Total (USD)", "Entries", "Currencies", "Rate source"]);
 
+    let mut any_partial = false;
+    let mut any_unavailable = false;
     for snapshot in snapshots {
         let mut currencies: Vec<&str> = snapshot.rates.keys().map(|s| s.as_str()).collect();
         currencies.sort();
@@ -184,12 +896,667 @@ pub fn print_snapshot_list(snapshots: &[Snapshot]) {
         } else {
             format!("USD, {}", currencies.join(", "))
         };
+        // Only entries for assets that still exist count toward completeness
+        let known = snapshot
+            .entries
+            .iter()
+            .filter(|e| assets.iter().any(|a| a.id == e.asset_id))
+            .count();
+        any_partial |= known < assets.len();
+        // A missing rate (or bad lots) makes the total unknowable, not the listing
+        let total = match compute::snapshot_total_usd(snapshot, portfolio) {
+            Ok(total) => fmt_currency(total),
+            Err(_) => {
+                any_unavailable = true;
+                "—".to_string()
+            }
+        };
         table.add_row(vec![
-            snapshot.date.clone(),
-            snapshot.entries.len().to_string(),
-            currencies_str,
+            Cell::new(match (snapshot.draft, snapshot.locked) {
+                (true, _) => format!("{} (draft)", snapshot.date),
+                (false, true) => format!("{} (locked)", snapshot.date),
+                (false, false) => snapshot.date.clone(),
+            }),
+            Cell::new(total).set_alignment(comfy_table::CellAlignment::Right),
+            Cell::new(fmt_entry_ratio(known, assets.len())),
+            Cell::new(currencies_str),
+            Cell::new(snapshot.rate_source.as_deref().unwrap_or("—")),
         ]);
     }
 
-    println!("{table}");
-}
\ No newline at end of file
+    writeln!(out, "{table}")?;
+
+    if any_partial {
+        writeln!(out)?;
+        writeln!(out, "* partial snapshot: some assets have no value")?;
+    }
+    if any_unavailable {
+        if !any_partial {
+            writeln!(out)?;
+        }
+        writeln!(out, "— total unavailable: a rate is missing or lots do not add up")?;
+    }
+    Ok(())
+}
+
+/// Format "entries/total", marking incomplete snapshots with a trailing `*`.
+fn fmt_entry_ratio(entries: usize, total: usize) -> String {
+    if entries < total {
+        format!("{}/{}*", entries, total)
+    } else {
+        format!("{}/{}", entries, total)
+    }
+}
+
+// ---- nw version ----
+
+pub fn print_version(path: &std::path::Path, portfolio: &Portfolio) {
+    print!("{}", version_report(path, portfolio));
+}
+
+fn version_report(path: &std::path::Path, portfolio: &Portfolio) -> String {
+    format!(
+        "nw {}\nData:      {}\nAssets:    {}\nSnapshots: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        path.display(),
+        portfolio.assets.len(),
+        portfolio.snapshots.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_row(name: &str, usd_value: f64) -> ShowRow {
+        ShowRow {
+            asset_id: name.to_lowercase(),
+            asset_name: name.to_string(),
+            currency: "USD".to_string(),
+            native_value: usd_value,
+            native_decimals: 2,
+            usd_value,
+            category: "etf".to_string(),
+            change_usd: None,
+            change_pct: None,
+            gain_usd: None,
+            sort_order: None,
+        }
+    }
+
+    #[test]
+    fn test_show_orders_assets_by_sort_order() {
+        let ordered = |name: &str, order: i64| ShowRow {
+            sort_order: Some(order),
+            ..make_row(name, 100.0)
+        };
+        let rows = vec![
+            make_row("Brokerage", 100.0),
+            ordered("Savings", 2),
+            make_row("Crypto", 100.0),
+            ordered("Checking", 1),
+            ordered("Bonds", 2),
+        ];
+        let mut out = Vec::new();
+        print_show(&mut out, rows, 500.0, vec![], "2025-07-01", &ShowOptions::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let names: Vec<&str> = out
+            .lines()
+            .filter_map(|l| l.split_whitespace().next())
+            .filter(|w| ["Brokerage", "Savings", "Crypto", "Checking", "Bonds"].contains(w))
+            .collect();
+        // Ties on the order are broken by name; unordered rows keep their entry order
+        assert_eq!(names, vec!["Checking", "Bonds", "Savings", "Brokerage", "Crypto"]);
+    }
+
+    // ---- render_line_chart ----
+
+    #[test]
+    fn test_line_chart_dimensions_and_max_on_top() {
+        let points: Vec<(String, f64)> = [100.0, 300.0, 250.0, 400.0, 150.0]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (format!("2025-0{}-01", i + 1), *v))
+            .collect();
+        let chart = render_line_chart(&points, 8, 40);
+        let lines: Vec<&str> = chart.lines().collect();
+        // Grid rows, the x-axis, and its labels
+        assert_eq!(lines.len(), 10);
+        for line in &lines[..8] {
+            let (_, row) = line.split_once('|').unwrap();
+            assert_eq!(row.chars().count(), 40);
+        }
+        assert!(lines[0].starts_with("400 |"));
+        assert_eq!(lines[0].matches('*').count(), 1);
+        // 400 is the fourth of five points: column round(3 * 39 / 4) = 29
+        assert_eq!(lines[0].split_once('|').unwrap().1.find('*'), Some(29));
+        assert!(lines[7].starts_with("100 |*"));
+        assert!(lines[9].trim_start().starts_with("2025-01-01"));
+        assert!(lines[9].ends_with("2025-05-01"));
+        assert!(lines[9].contains("2025-03-01"));
+    }
+
+    #[test]
+    fn test_line_chart_flat_and_single_point() {
+        let flat = vec![("a".to_string(), 50.0), ("b".to_string(), 50.0)];
+        let chart = render_line_chart(&flat, 5, 10);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[2], "50 |*........*");
+        assert!(lines.iter().take(5).enumerate().all(|(i, l)| i == 2 || !l.contains('*')));
+
+        let single = vec![("2025-01-01".to_string(), 1234.0)];
+        let chart = render_line_chart(&single, 3, 12);
+        assert_eq!(chart.matches('*').count(), 1);
+        assert!(chart.lines().nth(1).unwrap().starts_with("1,234 |*"));
+        assert_eq!(chart.lines().last().unwrap().trim(), "2025-01-01");
+    }
+
+    // ---- fmt_oneline ----
+
+    #[test]
+    fn test_fmt_oneline() {
+        assert_eq!(fmt_oneline(128_430.4, Some(2.345), false), "$128,430 ▲ +2.3%");
+        assert_eq!(fmt_oneline(99_500.0, Some(-1.25), false), "$99,500 ▼ -1.2%");
+        assert_eq!(fmt_oneline(-1_200.0, Some(0.0), false), "-$1,200 ▲ +0.0%");
+        // A single snapshot has no trend
+        assert_eq!(fmt_oneline(128_430.0, None, false), "$128,430");
+        assert_eq!(fmt_oneline(500.0, Some(-3.0), true), "$500 \x1b[31m▼ -3.0%\x1b[0m");
+    }
+
+    // ---- print_range_explain ----
+
+    #[test]
+    fn test_range_explain_lists_cutoff_and_partition() {
+        use crate::compute::{filter_by_range, range_cutoff};
+        use crate::model::HistoryRange;
+        let mut draft = Snapshot::new("2025-01-31");
+        draft.draft = true;
+        let snapshots = vec![
+            Snapshot::new("2024-01-31"),
+            Snapshot::new("2024-02-28"),
+            draft,
+            Snapshot::new("2025-02-28"),
+        ];
+        let cutoff = range_cutoff(HistoryRange::OneYear, "2025-02-28", 1);
+        let included = filter_by_range(&snapshots, HistoryRange::OneYear, "2025-02-28", 1, false);
+        let mut out = Vec::new();
+        print_range_explain(&mut out, cutoff.as_deref(), &snapshots, &included).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Cutoff: 2024-02-28 (snapshots on or after this date are included)\n\
+             \x20 2024-01-31  excluded (before cutoff)\n\
+             \x20 2024-02-28  included\n\
+             \x20 2025-01-31  excluded (draft)\n\
+             \x20 2025-02-28  included\n\n"
+        );
+    }
+
+    // ---- align_decimals ----
+
+    #[test]
+    fn test_align_decimals_pads_fractions() {
+        let values = vec!["50,000".to_string(), "9,500.00".to_string(), "0.05000000".to_string()];
+        assert_eq!(align_decimals(values), vec!["50,000         ", "9,500.00      ", "0.05000000"]);
+    }
+
+    #[test]
+    fn test_show_notes_deleted_asset_entries_left_out() {
+        let deleted = ShowRow {
+            asset_name: "(deleted: old-btc)".to_string(),
+            currency: compute::UNKNOWN_CURRENCY.to_string(),
+            native_value: 0.5,
+            native_decimals: 1,
+            usd_value: 0.0,
+            category: compute::UNKNOWN_CATEGORY.to_string(),
+            ..make_row("old-btc", 0.0)
+        };
+        let rows = vec![make_row("Brokerage", 100.0), deleted];
+        let mut out = Vec::new();
+        print_show(&mut out, rows, 100.0, vec![], "2025-07-01", &ShowOptions::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let line = out.lines().find(|l| l.contains("(deleted: old-btc)")).unwrap();
+        assert!(line.contains("0.5") && line.contains('—'));
+        assert!(out.contains("TOTAL  100.00"));
+        assert!(out.contains("(1 deleted-asset entries not included in total)"));
+    }
+
+    #[test]
+    fn test_show_native_column_aligns_decimal_points() {
+        let row = |name: &str, currency: &str, value: f64, decimals: u32| ShowRow {
+            currency: currency.to_string(),
+            native_value: value,
+            native_decimals: decimals,
+            category: "cash".to_string(),
+            ..make_row(name, 100.0)
+        };
+        let rows = vec![
+            row("Dollars", "USD", 1234.5, 2),
+            row("Yen", "JPY", 1_500_000.0, 0),
+            row("Bitcoin", "BTC", 0.05, 8),
+        ];
+        let mut out = Vec::new();
+        print_show(&mut out, rows, 300.0, vec![], "2025-07-01", &ShowOptions::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let line = |name: &str| out.lines().find(|l| l.contains(name)).unwrap().to_string();
+        // Where the integer part ends: at the decimal point, or the last digit without one
+        let point = |l: String, value: &str| {
+            let start = l.find(value).unwrap();
+            start + value.find('.').unwrap_or(value.len())
+        };
+        let dollars = point(line("Dollars"), "1,234.50");
+        assert_eq!(point(line("Bitcoin"), "0.05000000"), dollars);
+        assert_eq!(point(line("Yen"), "1,500,000"), dollars);
+    }
+
+    // ---- category_header ----
+
+    #[test]
+    fn test_category_header_uses_assigned_color() {
+        let mut settings = Settings::default();
+        settings.category_colors.insert("crypto".to_string(), CategoryColor::Magenta);
+        let color = settings.color_for_category("crypto");
+        assert_eq!(category_header("crypto", color), "\x1b[35mCRYPTO\x1b[0m");
+        assert_eq!(category_header("etf", settings.color_for_category("etf")), "ETF");
+    }
+
+    // ---- split_by_min_value ----
+
+    #[test]
+    fn test_split_by_min_value_hides_small_rows() {
+        let rows = vec![
+            make_row("Big", 5000.0),
+            make_row("Small", 200.0),
+            make_row("Edge", 1000.0),
+        ];
+        let (visible, hidden) = split_by_min_value(&rows, Some(1000.0));
+        assert_eq!(hidden, 1);
+        assert_eq!(visible.len(), 2);
+        assert_eq!(visible[0].asset_name, "Big");
+        assert_eq!(visible[1].asset_name, "Edge");
+    }
+
+    #[test]
+    fn test_split_by_min_value_no_threshold() {
+        let rows = vec![make_row("Big", 5000.0), make_row("Small", 200.0)];
+        let (visible, hidden) = split_by_min_value(&rows, None);
+        assert_eq!(hidden, 0);
+        assert_eq!(visible.len(), 2);
+    }
+
+    // ---- fmt_amount ----
+
+    #[test]
+    fn test_fmt_amount_two_decimals() {
+        assert_eq!(fmt_currency(1234567.891), "1,234,567.89");
+        assert_eq!(fmt_currency(-0.999), "-1.00");
+    }
+
+    #[test]
+    fn test_fmt_amount_zero_decimals() {
+        assert_eq!(fmt_amount(2_500_000.0, 0), "2,500,000");
+        assert_eq!(fmt_amount(1499.6, 0), "1,500");
+    }
+
+    #[test]
+    fn test_fmt_amount_many_decimals() {
+        assert_eq!(fmt_amount(0.12345678, 8), "0.12345678");
+    }
+
+    #[test]
+    fn test_fmt_amount_near_u64_max() {
+        // 2^64 - 2048, the largest f64 below u64::MAX + 1
+        assert_eq!(fmt_currency(18_446_744_073_709_549_568.0), "18,446,744,073,709,549,568.00");
+        assert_eq!(fmt_amount(-1e19, 0), "-10,000,000,000,000,000,000");
+    }
+
+    #[test]
+    fn test_fmt_amount_above_u64_range() {
+        // 2^64 used to saturate to u64::MAX (…,615)
+        assert_eq!(fmt_currency(18_446_744_073_709_551_616.0), "18,446,744,073,709,551,616.00");
+        assert_eq!(fmt_amount(2_f64.powi(80), 0), "1,208,925,819,614,629,174,706,176");
+        assert_eq!(fmt_currency(-2.5e21), "-2,500,000,000,000,000,000,000.00");
+    }
+
+    // ---- fmt_entry_ratio ----
+
+    #[test]
+    fn test_fmt_entry_ratio() {
+        assert_eq!(fmt_entry_ratio(8, 8), "8/8");
+        assert_eq!(fmt_entry_ratio(5, 8), "5/8*");
+    }
+
+    // ---- version_report ----
+
+    #[test]
+    fn test_version_report() {
+        let portfolio = Portfolio {
+            assets: vec![Asset::new("vti", "VTI", "etf", "USD")],
+            ..Default::default()
+        };
+        let report = version_report(std::path::Path::new("/tmp/portfolio.json"), &portfolio);
+        assert!(report.contains(env!("CARGO_PKG_VERSION")));
+        assert!(report.contains("Assets:    1"));
+        assert!(report.contains("/tmp/portfolio.json"));
+    }
+
+    // ---- fmt_date ----
+
+    #[test]
+    fn test_fmt_date_default_iso() {
+        assert_eq!(fmt_date("2025-02-28", None), "2025-02-28");
+    }
+
+    #[test]
+    fn test_fmt_date_month_name() {
+        assert_eq!(fmt_date("2025-02-28", Some("%b %d, %Y")), "Feb 28, 2025");
+    }
+
+    #[test]
+    fn test_fmt_date_day_first() {
+        assert_eq!(fmt_date("2025-02-08", Some("%d/%m/%Y")), "08/02/2025");
+    }
+
+    #[test]
+    fn test_validate_date_format_rejects_invalid() {
+        assert!(validate_date_format("%Y-%m-%d").is_ok());
+        assert!(validate_date_format("%Q").is_err());
+    }
+
+    // ---- golden output ----
+    //
+    // Rendered output is compared with `tests/golden/<name>.txt`, ignoring trailing
+    // whitespace. Run with `UPDATE_GOLDEN=1` to rewrite the files after intended changes.
+
+    fn assert_golden(name: &str, output: Vec<u8>) {
+        let normalize = |text: &str| -> String {
+            text.lines().map(|l| format!("{}\n", l.trim_end())).collect()
+        };
+        let actual = normalize(&String::from_utf8(output).unwrap());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{name}.txt"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("missing golden file {}: {e}", path.display()));
+        assert_eq!(actual, normalize(&expected), "output differs from {}", path.display());
+    }
+
+    /// Three categories, a zero-decimal currency, and a drop in the last snapshot.
+    fn golden_portfolio() -> Portfolio {
+        Portfolio {
+            assets: vec![
+                Asset::new("vti", "VTI", "etf", "USD"),
+                Asset::new("savings", "Savings Account", "bank", "CHF"),
+                Asset::new("yen", "Yen Cash", "cash", "JPY"),
+                Asset::new("btc", "Bitcoin", "crypto", "BTC"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01")
+                    .with_rate("CHF", 0.9)
+                    .with_rate("JPY", 150.0)
+                    .with_rate("BTC", 0.00001)
+                    .with_entry("vti", 10000.0)
+                    .with_entry("savings", 9000.0)
+                    .with_entry("yen", 150000.0),
+                Snapshot::new("2025-04-01")
+                    .with_rate("CHF", 0.88)
+                    .with_rate("JPY", 145.0)
+                    .with_rate("BTC", 0.00001)
+                    .with_entry("vti", 12500.0)
+                    .with_entry("savings", 9000.0)
+                    .with_entry("yen", 50000.0)
+                    .with_entry("btc", 0.05),
+                Snapshot::new("2025-07-01")
+                    .with_rate("CHF", 0.8)
+                    .with_rate("JPY", 145.0)
+                    .with_rate("BTC", 0.00002)
+                    .with_entry("vti", 11000.0)
+                    .with_entry("savings", 9500.0)
+                    .with_entry("btc", 0.05),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn render_show(portfolio: &Portfolio, date: &str, opts: &ShowOptions) -> Vec<u8> {
+        let snapshot = portfolio.snapshots.iter().find(|s| s.date == date).unwrap();
+        let (total, mut rows) = compute::compute_show_rows(snapshot, portfolio, None).unwrap();
+        if opts.compare_date.is_some() {
+            compute::apply_comparison(&mut rows, &portfolio.snapshots[0]).unwrap();
+        }
+        let allocation =
+            compute::compute_allocation(&compute::compute_category_totals(&rows), total);
+        let mut out = Vec::new();
+        print_show(&mut out, rows, total, allocation, date, opts).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_show_since_inception_line() {
+        let opts = ShowOptions {
+            since_inception: Some(("2023-01-01", 34_200.0, Some(58.2))),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print_show(&mut out, vec![make_row("VTI", 100.0)], 100.0, vec![], "2025-07-01", &opts)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("\nSince first snapshot (2023-01-01): +34,200.00 (+58.20%)\n"));
+    }
+
+    #[test]
+    fn test_golden_show() {
+        let portfolio = golden_portfolio();
+        let out = render_show(&portfolio, "2025-04-01", &ShowOptions::default());
+        assert_golden("show", out);
+    }
+
+    #[test]
+    fn test_golden_show_options() {
+        let portfolio = golden_portfolio();
+        let opts = ShowOptions {
+            min_value: Some(500.0),
+            highlight_above: Some(40.0),
+            at_latest_rates: Some(("2025-07-01", 32_000.0)),
+            date_format: Some("%b %d, %Y"),
+            ..Default::default()
+        };
+        assert_golden("show_options", render_show(&portfolio, "2025-04-01", &opts));
+    }
+
+    #[test]
+    fn test_golden_show_compare() {
+        let portfolio = golden_portfolio();
+        let opts = ShowOptions { compare_date: Some("2025-01-01"), ..Default::default() };
+        assert_golden("show_compare", render_show(&portfolio, "2025-07-01", &opts));
+    }
+
+    #[test]
+    fn test_golden_show_cost_basis() {
+        let mut portfolio = golden_portfolio();
+        portfolio.assets[0].cost_basis = Some(9000.0);
+        portfolio.assets[3].cost_basis = Some(3000.0);
+        let out = render_show(&portfolio, "2025-07-01", &ShowOptions::default());
+        assert_golden("show_cost_basis", out);
+    }
+
+    #[test]
+    fn test_golden_show_allocation_within() {
+        let mut portfolio = golden_portfolio();
+        portfolio.assets.push(Asset::new("wallet", "Wallet", "cash", "USD"));
+        portfolio.snapshots[1].entries.push(crate::model::SnapshotEntry {
+            asset_id: "wallet".to_string(),
+            value: 655.17,
+            ..Default::default()
+        });
+        let snapshot = &portfolio.snapshots[1];
+        let (subtotal, rows) =
+            compute::compute_show_rows(snapshot, &portfolio, Some("cash")).unwrap();
+        let totals = compute::compute_asset_totals(&rows);
+        let allocation = compute::compute_allocation(&totals, subtotal);
+        let allocation = compute::asset_allocation_names(allocation, &rows);
+        let opts = ShowOptions {
+            category_filter: Some("cash"),
+            allocation_within: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print_show(&mut out, rows, subtotal, allocation, &snapshot.date, &opts).unwrap();
+        assert_golden("show_allocation_within", out);
+    }
+
+    #[test]
+    fn test_golden_show_goals() {
+        let portfolio = golden_portfolio();
+        let goals = vec![
+            GoalProgress {
+                category: "crypto".to_string(),
+                current_usd: 2_500.0,
+                goal_usd: 20_000.0,
+                pct: 12.5,
+            },
+            GoalProgress {
+                category: "real-estate".to_string(),
+                current_usd: 0.0,
+                goal_usd: 100_000.0,
+                pct: 0.0,
+            },
+        ];
+        let opts = ShowOptions { goals: &goals, ..Default::default() };
+        assert_golden("show_goals", render_show(&portfolio, "2025-07-01", &opts));
+    }
+
+    #[test]
+    fn test_show_no_allocation_skips_allocation_block() {
+        let portfolio = golden_portfolio();
+        let opts = ShowOptions { no_allocation: true, ..Default::default() };
+        let out = String::from_utf8(render_show(&portfolio, "2025-07-01", &opts)).unwrap();
+        assert!(out.contains("Subtotal"));
+        assert!(out.contains("TOTAL  25,375.00"));
+        assert!(!out.contains("ALLOCATION"));
+    }
+
+    #[test]
+    fn test_show_allocation_only_skips_tables() {
+        let portfolio = golden_portfolio();
+        let opts = ShowOptions { allocation_only: true, ..Default::default() };
+        let out = String::from_utf8(render_show(&portfolio, "2025-07-01", &opts)).unwrap();
+        assert!(out.starts_with("CURRENT NET WORTH — 2025-07-01\n"));
+        assert!(out.contains("ALLOCATION\n"));
+        assert!(!out.contains("Subtotal"));
+        assert!(!out.contains("TOTAL"));
+    }
+
+    #[test]
+    fn test_golden_show_missing_rates_and_empty() {
+        let missing = vec![("Bitcoin".to_string(), "BTC".to_string())];
+        let opts = ShowOptions { missing_rates: &missing, ..Default::default() };
+        let mut out = Vec::new();
+        print_show(&mut out, vec![], 0.0, vec![], "2025-07-01", &opts).unwrap();
+        assert_golden("show_missing_rates_empty", out);
+    }
+
+    #[test]
+    fn test_golden_history() {
+        let portfolio = golden_portfolio();
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let mut rows = compute::compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        for (row, pair) in rows.iter_mut().skip(1).zip(snapshots.windows(2)) {
+            row.attribution =
+                Some(compute::change_attribution(pair[0], pair[1], &portfolio).unwrap());
+        }
+        let mut out = Vec::new();
+        let opts = HistoryOptions { annualized: true, attribution: true, ..Default::default() };
+        print_history(&mut out, rows, "ALL", &opts).unwrap();
+        assert_golden("history", out);
+    }
+
+    #[test]
+    fn test_golden_history_drawdown() {
+        let portfolio = golden_portfolio();
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let rows = compute::compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        let mut out = Vec::new();
+        let opts = HistoryOptions { drawdown: true, ..Default::default() };
+        print_history(&mut out, rows, "ALL", &opts).unwrap();
+        assert_golden("history_drawdown", out);
+    }
+
+    #[test]
+    fn test_golden_since_last() {
+        let portfolio = golden_portfolio();
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let delta = compute::since_last_delta(&snapshots, &portfolio).unwrap().unwrap();
+        let mut out = Vec::new();
+        print_since_last(&mut out, &delta, None).unwrap();
+        assert_golden("since_last", out);
+    }
+
+    #[test]
+    fn test_golden_history_single_row() {
+        let portfolio = golden_portfolio();
+        let snapshots = vec![&portfolio.snapshots[0]];
+        let rows = compute::compute_history_rows(&snapshots, &portfolio, None, None).unwrap();
+        let mut out = Vec::new();
+        print_history(&mut out, rows, "1M", &HistoryOptions::default()).unwrap();
+        assert_golden("history_single_row", out);
+    }
+
+    #[test]
+    fn test_golden_exposure() {
+        let portfolio = golden_portfolio();
+        let snapshot = &portfolio.snapshots[2];
+        let (total, rows) = compute::compute_show_rows(snapshot, &portfolio, None).unwrap();
+        let totals = compute::compute_currency_totals(&rows);
+        let exposure = compute::compute_allocation(&totals, total);
+        let mut out = Vec::new();
+        print_exposure(&mut out, &exposure, &totals, &snapshot.date, None).unwrap();
+        assert_golden("exposure", out);
+    }
+
+    #[test]
+    fn test_golden_asset_list() {
+        let mut out = Vec::new();
+        print_asset_list(&mut out, &golden_portfolio().assets).unwrap();
+        print_asset_list(&mut out, &[]).unwrap();
+        assert_golden("asset_list", out);
+    }
+
+    #[test]
+    fn test_golden_asset_list_notes() {
+        let mut assets = golden_portfolio().assets;
+        assets[0].note = Some("Core holding, rebalanced every January".to_string());
+        assets[1].note = Some("Emergency fund".to_string());
+        let mut out = Vec::new();
+        print_asset_list(&mut out, &assets).unwrap();
+        print_asset_detail(&mut out, &assets[0], &golden_portfolio()).unwrap();
+        assert_golden("asset_list_notes", out);
+    }
+
+    #[test]
+    fn test_note_preview_truncates() {
+        assert_eq!(note_preview("Emergency fund"), "Emergency fund");
+        assert_eq!(
+            note_preview("Core holding, rebalanced every January"),
+            "Core holding, rebalanced ever…"
+        );
+        assert_eq!(note_preview("Two\nlines"), "Two…");
+    }
+
+    #[test]
+    fn test_golden_snapshot_list() {
+        let mut portfolio = golden_portfolio();
+        portfolio.snapshots[1].rate_source = Some("manual".to_string());
+        // No JPY rate: the total cannot be computed
+        portfolio.snapshots[0].rates.remove("JPY");
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let mut out = Vec::new();
+        print_snapshot_list(&mut out, &snapshots, &portfolio).unwrap();
+        print_snapshot_list(&mut out, &[], &portfolio).unwrap();
+        assert_golden("snapshot_list", out);
+    }
+}
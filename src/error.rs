@@ -5,15 +5,30 @@ pub enum NwError {
     #[error("asset id '{0}' already exists")]
     DuplicateAssetId(String),
 
-    #[error("asset id '{0}' not found")]
-    AssetNotFound(String),
+    #[error("asset id '{id}' not found{}", did_you_mean(.suggestions))]
+    AssetNotFound { id: String, suggestions: Vec<String> },
 
     #[error("snapshot for date '{0}' already exists")]
     SnapshotAlreadyExists(String),
 
+    #[error(
+        "snapshot for {date} is {days} day(s) from the one on {nearest}; \
+         the minimum interval is {min_days} day(s)"
+    )]
+    SnapshotTooClose { date: String, nearest: String, days: i64, min_days: u32 },
+
     #[error("snapshot for date '{0}' not found")]
     SnapshotNotFound(String),
 
+    #[error("snapshot for {0} is locked; pass --force to change it anyway")]
+    SnapshotLocked(String),
+
+    #[error("no asset uses currency '{0}'")]
+    CurrencyNotFound(String),
+
+    #[error("invalid currency code '{0}'")]
+    InvalidCurrency(String),
+
     #[error("USD is the base currency and cannot have a rate")]
     UsdRateRejected,
 
@@ -22,9 +37,107 @@ pub enum NwError {
     #[error("invalid date format '{0}': expected YYYY-MM-DD")]
     InvalidDate(String),
 
-    #[error("invalid history range '{0}': expected 1M, 6M, 1Y, 5Y, or ALL")]
+    #[error("invalid date format pattern '{0}'")]
+    InvalidDateFormat(String),
+
+    #[error("invalid cash flow '{0}': expected YYYY-MM-DD=AMOUNT")]
+    InvalidCashFlow(String),
+
+    #[error("invalid percentage '{0}': expected a non-negative number like 25%")]
+    InvalidPercent(String),
+
+    #[error("invalid history range '{0}': expected 1M, 6M, 1Y, 5Y, YTD, or ALL")]
     InvalidHistoryRange(String),
 
+    #[error("invalid export format '{0}': expected allocation-csv or html")]
+    InvalidExportFormat(String),
+
+    #[error("invalid allocation grouping '{0}': expected category or currency")]
+    InvalidAllocationBy(String),
+
+    #[error("invalid output format '{0}': expected text or json")]
+    InvalidOutputFormat(String),
+
+    #[error("invalid sort order '{0}': expected asc or desc")]
+    InvalidSortOrder(String),
+
+    #[error("snapshot index {index} is out of range: expected -1 to -{count}")]
+    SnapshotIndexOutOfRange { index: i64, count: usize },
+
+    #[error(
+        "invalid category color '{0}': expected CATEGORY=COLOR with red, green, yellow, \
+         blue, magenta, cyan, or none"
+    )]
+    InvalidColor(String),
+
+    #[error("invalid category goal '{0}': expected CATEGORY=AMOUNT in USD, or CATEGORY=none")]
+    InvalidGoal(String),
+
+    #[error("invalid default snapshot '{0}': expected latest or latest-complete")]
+    InvalidDefaultShow(String),
+
+    #[error("invalid cost basis '{0}': expected a non-negative USD amount or none")]
+    InvalidCostBasis(String),
+
+    #[error("invalid value bound '{0}': expected a non-negative amount or none")]
+    InvalidValueBound(String),
+
+    #[error("minimum value {min} is above maximum value {max}")]
+    ValueBoundsReversed { min: f64, max: f64 },
+
+    #[error("invalid asset order '{0}': expected a whole number or none")]
+    InvalidAssetOrder(String),
+
+    #[error("invalid percent-of '{0}': expected PARENT=PERCENT, e.g. house=40%, or none")]
+    InvalidPercentOf(String),
+
+    #[error("asset '{0}' is derived from itself through percent-of")]
+    DerivedAssetCycle(String),
+
+    #[error(
+        "asset '{id}' has assets derived from it ({}); clear their percent-of first",
+        derived.join(", ")
+    )]
+    AssetHasDerived { id: String, derived: Vec<String> },
+
+    #[error(
+        "asset '{id}' ({currency}) can't be a percentage of '{parent}' ({parent_currency}): \
+         the currencies differ"
+    )]
+    DerivedCurrencyMismatch {
+        id: String,
+        currency: String,
+        parent: String,
+        parent_currency: String,
+    },
+
+    #[error("invalid rate policy '{0}': expected prefer-manual, prefer-api, or average")]
+    InvalidRatePolicy(String),
+
+    #[error("invalid event '{0}': expected KIND:AMOUNT in USD, e.g. deposit:5000")]
+    InvalidEvent(String),
+
+    #[error("invalid month {0}: expected 1 to 12")]
+    InvalidMonth(u32),
+
+    #[error("invalid decimal places {0}: expected 0 to {max}", max = crate::model::MAX_DECIMALS)]
+    InvalidDecimals(u32),
+
+    #[error("import line {line}: {message}")]
+    InvalidImport { line: usize, message: String },
+
+    #[error("asset '{id}' is {theirs} in the imported snapshot but {ours} here")]
+    ImportCurrencyMismatch { id: String, theirs: String, ours: String },
+
+    #[error("invalid snapshot bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("asset id '{id}' from included file {path} is already defined")]
+    IncludeConflict { id: String, path: String },
+
+    #[error("include cycle: {0} includes itself")]
+    IncludeCycle(String),
+
     #[error("failed to read portfolio file at {path}: {source}")]
     ReadFile {
         path: String,
@@ -49,11 +162,178 @@ pub enum NwError {
         source: serde_json::Error,
     },
 
+    #[error("read-only mode (--read-only or NW_READONLY) refuses to modify the portfolio")]
+    ReadOnly,
+
     #[error("could not determine config directory")]
     NoConfigDir,
 
     #[error("no rate found for currency '{0}'")]
     RateMissing(String),
-    // #[error("no snapshots found in portfolio")]
-    // NoSnapshots,
+
+    #[error("lots for asset '{asset_id}' sum to {sum} but the entry value is {value}")]
+    LotSumMismatch {
+        asset_id: String,
+        sum: f64,
+        value: f64,
+    },
+
+    #[cfg(feature = "encryption")]
+    #[error("could not decrypt {0}: wrong passphrase or corrupted file")]
+    Decryption(String),
+
+    #[error("no snapshots found in portfolio")]
+    NoSnapshots,
+
+    #[error("no snapshots in range {0}")]
+    NoSnapshotsInRange(String),
+
+    #[error("portfolio check found {0} problem(s)")]
+    CheckFailed(usize),
+}
+
+/// Exit status for `--fail-on-empty` when there is nothing to show, so scripts can tell
+/// "no data" apart from other failures (status 1).
+pub const EMPTY_EXIT_CODE: u8 = 3;
+
+/// Exit status for `nw check` when the file loads but has problems.
+pub const CHECK_FAILED_EXIT_CODE: u8 = 4;
+
+impl NwError {
+    /// Stable machine-readable code for the error, matching the variant name.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NwError::DuplicateAssetId(_) => "DuplicateAssetId",
+            NwError::AssetNotFound { .. } => "AssetNotFound",
+            NwError::SnapshotAlreadyExists(_) => "SnapshotAlreadyExists",
+            NwError::SnapshotTooClose { .. } => "SnapshotTooClose",
+            NwError::SnapshotNotFound(_) => "SnapshotNotFound",
+            NwError::SnapshotLocked(_) => "SnapshotLocked",
+            NwError::CurrencyNotFound(_) => "CurrencyNotFound",
+            NwError::InvalidCurrency(_) => "InvalidCurrency",
+            NwError::UsdRateRejected => "UsdRateRejected",
+            NwError::InvalidDate(_) => "InvalidDate",
+            NwError::InvalidDateFormat(_) => "InvalidDateFormat",
+            NwError::InvalidCashFlow(_) => "InvalidCashFlow",
+            NwError::InvalidPercent(_) => "InvalidPercent",
+            NwError::InvalidHistoryRange(_) => "InvalidHistoryRange",
+            NwError::InvalidExportFormat(_) => "InvalidExportFormat",
+            NwError::InvalidAllocationBy(_) => "InvalidAllocationBy",
+            NwError::InvalidOutputFormat(_) => "InvalidOutputFormat",
+            NwError::InvalidSortOrder(_) => "InvalidSortOrder",
+            NwError::SnapshotIndexOutOfRange { .. } => "SnapshotIndexOutOfRange",
+            NwError::InvalidColor(_) => "InvalidColor",
+            NwError::InvalidGoal(_) => "InvalidGoal",
+            NwError::InvalidDefaultShow(_) => "InvalidDefaultShow",
+            NwError::InvalidCostBasis(_) => "InvalidCostBasis",
+            NwError::InvalidValueBound(_) => "InvalidValueBound",
+            NwError::ValueBoundsReversed { .. } => "ValueBoundsReversed",
+            NwError::InvalidAssetOrder(_) => "InvalidAssetOrder",
+            NwError::InvalidPercentOf(_) => "InvalidPercentOf",
+            NwError::DerivedAssetCycle(_) => "DerivedAssetCycle",
+            NwError::AssetHasDerived { .. } => "AssetHasDerived",
+            NwError::DerivedCurrencyMismatch { .. } => "DerivedCurrencyMismatch",
+            NwError::InvalidRatePolicy(_) => "InvalidRatePolicy",
+            NwError::InvalidEvent(_) => "InvalidEvent",
+            NwError::InvalidMonth(_) => "InvalidMonth",
+            NwError::InvalidDecimals(_) => "InvalidDecimals",
+            NwError::InvalidImport { .. } => "InvalidImport",
+            NwError::ImportCurrencyMismatch { .. } => "ImportCurrencyMismatch",
+            NwError::InvalidBundle(_) => "InvalidBundle",
+            NwError::IncludeConflict { .. } => "IncludeConflict",
+            NwError::IncludeCycle(_) => "IncludeCycle",
+            NwError::ReadFile { .. } => "ReadFile",
+            NwError::WriteFile { .. } => "WriteFile",
+            NwError::MalformedJson { .. } => "MalformedJson",
+            NwError::SerializeJson { .. } => "SerializeJson",
+            NwError::ReadOnly => "ReadOnly",
+            NwError::NoConfigDir => "NoConfigDir",
+            NwError::RateMissing(_) => "RateMissing",
+            NwError::LotSumMismatch { .. } => "LotSumMismatch",
+            #[cfg(feature = "encryption")]
+            NwError::Decryption(_) => "Decryption",
+            NwError::NoSnapshots => "NoSnapshots",
+            NwError::NoSnapshotsInRange(_) => "NoSnapshotsInRange",
+            NwError::CheckFailed(_) => "CheckFailed",
+        }
+    }
+
+    /// Process exit status for this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            NwError::NoSnapshots | NwError::NoSnapshotsInRange(_) => EMPTY_EXIT_CODE,
+            NwError::CheckFailed(_) => CHECK_FAILED_EXIT_CODE,
+            _ => 1,
+        }
+    }
+}
+
+/// "; did you mean 'x'?" suffix for not-found messages, empty without suggestions.
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [one] => format!("; did you mean '{}'?", one),
+        many => {
+            let quoted: Vec<String> = many.iter().map(|s| format!("'{}'", s)).collect();
+            format!("; did you mean one of {}?", quoted.join(", "))
+        }
+    }
+}
+
+/// Render an error as `{"error": "...", "code": "..."}` for JSON consumers.
+/// Errors not originating from `NwError` get the code `Other`.
+pub fn json_error(err: &anyhow::Error) -> String {
+    let code = err.downcast_ref::<NwError>().map_or("Other", NwError::code);
+    serde_json::json!({ "error": format!("{err:#}"), "code": code }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_not_found_lists_suggestions() {
+        let err = |suggestions: &[&str]| NwError::AssetNotFound {
+            id: "vtii".to_string(),
+            suggestions: suggestions.iter().map(|s| s.to_string()).collect(),
+        };
+        assert_eq!(err(&[]).to_string(), "asset id 'vtii' not found");
+        assert_eq!(err(&["vti"]).to_string(), "asset id 'vtii' not found; did you mean 'vti'?");
+        assert_eq!(
+            err(&["vti", "vtis"]).to_string(),
+            "asset id 'vtii' not found; did you mean one of 'vti', 'vtis'?"
+        );
+    }
+
+    #[test]
+    fn test_code_matches_variant() {
+        let not_found = NwError::AssetNotFound { id: "x".to_string(), suggestions: vec![] };
+        assert_eq!(not_found.code(), "AssetNotFound");
+        assert_eq!(NwError::RateMissing("EUR".to_string()).code(), "RateMissing");
+        assert_eq!(NwError::NoConfigDir.code(), "NoConfigDir");
+    }
+
+    #[test]
+    fn test_exit_code_distinguishes_empty_results() {
+        assert_eq!(NwError::NoSnapshots.exit_code(), EMPTY_EXIT_CODE);
+        assert_eq!(NwError::NoSnapshotsInRange("1M".to_string()).exit_code(), EMPTY_EXIT_CODE);
+        assert_eq!(NwError::RateMissing("EUR".to_string()).exit_code(), 1);
+        assert_ne!(EMPTY_EXIT_CODE, 1);
+        assert_eq!(NwError::CheckFailed(2).exit_code(), CHECK_FAILED_EXIT_CODE);
+    }
+
+    #[test]
+    fn test_json_error_shape() {
+        let err: anyhow::Error = NwError::SnapshotNotFound("2025-01-01".to_string()).into();
+        let value: serde_json::Value = serde_json::from_str(&json_error(&err)).unwrap();
+        assert_eq!(value["code"], "SnapshotNotFound");
+        assert_eq!(value["error"], "snapshot for date '2025-01-01' not found");
+    }
+
+    #[test]
+    fn test_json_error_non_nw_error() {
+        let err = anyhow::anyhow!("boom");
+        let value: serde_json::Value = serde_json::from_str(&json_error(&err)).unwrap();
+        assert_eq!(value["code"], "Other");
+    }
 }
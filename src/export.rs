@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use serde::{Deserialize, Serialize};
+use crate::compute;
+use crate::display::{fmt_amount, fmt_change, fmt_currency, fmt_pct};
+use crate::error::NwError;
+use crate::model::{Asset, Portfolio, ShowRow, Snapshot};
+
+/// Allocation over time: one row per snapshot, one column per category.
+pub struct AllocationMatrix {
+    /// Union of categories across all snapshots, alphabetical.
+    pub categories: Vec<String>,
+    /// (date, percentage per category in `categories` order; 0 where absent).
+    pub rows: Vec<(String, Vec<f64>)>,
+}
+
+/// Build the date × category allocation matrix for the given snapshots.
+pub fn allocation_matrix(
+    snapshots: &[&Snapshot],
+    portfolio: &Portfolio,
+) -> Result<AllocationMatrix, NwError> {
+    let allocations: Vec<(String, Vec<(String, f64)>)> = snapshots
+        .iter()
+        .map(|s| {
+            let (total, rows) = compute::compute_show_rows(s, portfolio, None)?;
+            let totals = compute::compute_category_totals(&rows);
+            Ok((s.date.clone(), compute::compute_allocation(&totals, total)))
+        })
+        .collect::<Result<_, NwError>>()?;
+
+    let categories: Vec<String> = allocations
+        .iter()
+        .flat_map(|(_, alloc)| alloc.iter().map(|(cat, _)| cat.clone()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let rows = allocations
+        .into_iter()
+        .map(|(date, alloc)| {
+            let pcts = categories
+                .iter()
+                .map(|cat| alloc.iter().find(|(c, _)| c == cat).map_or(0.0, |(_, p)| *p))
+                .collect();
+            (date, pcts)
+        })
+        .collect();
+
+    Ok(AllocationMatrix { categories, rows })
+}
+
+/// Render the matrix as CSV with a `date` column followed by one column per category.
+pub fn allocation_csv(matrix: &AllocationMatrix) -> String {
+    let mut out = String::from("date");
+    for cat in &matrix.categories {
+        out.push(',');
+        out.push_str(&csv_field(cat));
+    }
+    out.push('\n');
+    for (date, pcts) in &matrix.rows {
+        out.push_str(date);
+        for pct in pcts {
+            out.push_str(&format!(",{:.2}", pct));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Inline styles for `html_report`, so the page needs no other files.
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { padding: 0.25em 0.75em; text-align: left; }
+th { border-bottom: 1px solid #999; }
+.num { text-align: right; font-variant-numeric: tabular-nums; }
+tr.subtotal td { border-top: 1px solid #ccc; font-weight: bold; }
+p.total { font-size: 1.25em; font-weight: bold; }
+";
+
+/// A standalone HTML page for `export --kind html`: `latest` as `show` prints it
+/// (holdings by category, the total, and the allocation), then a history table of
+/// `history`. No scripts or external files, so it can be mailed as is.
+pub fn html_report(
+    latest: &Snapshot,
+    history: &[&Snapshot],
+    portfolio: &Portfolio,
+) -> Result<String, NwError> {
+    let (total, rows) = compute::compute_show_rows(latest, portfolio, None)?;
+    let mut by_category: BTreeMap<&str, Vec<&ShowRow>> = BTreeMap::new();
+    for row in &rows {
+        by_category.entry(&row.category).or_default().push(row);
+    }
+
+    let title = format!("Net worth — {}", html_escape(&latest.date));
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{title}</title>\n<style>\n{HTML_STYLE}</style>\n"));
+    out.push_str(&format!("</head>\n<body>\n<h1>{title}</h1>\n"));
+
+    let header = ["Name", "Currency", "Value (native)", "Value (USD)"];
+    for (category, cat_rows) in &by_category {
+        out.push_str(&format!("<h2>{}</h2>\n<table>\n", html_escape(&category.to_uppercase())));
+        out.push_str(&html_row("th", &header, 2));
+        for row in cat_rows {
+            out.push_str(&html_row(
+                "td",
+                &[
+                    &html_escape(&row.asset_name),
+                    &html_escape(&row.currency),
+                    &fmt_amount(row.native_value, row.native_decimals),
+                    &fmt_currency(row.usd_value),
+                ],
+                2,
+            ));
+        }
+        let subtotal: f64 = cat_rows.iter().map(|r| r.usd_value).sum();
+        out.push_str(&format!(
+            "<tr class=\"subtotal\"><td>Subtotal</td><td></td><td></td>\
+             <td class=\"num\">{}</td></tr>\n</table>\n",
+            fmt_currency(subtotal)
+        ));
+    }
+    out.push_str(&format!("<p class=\"total\">Total: {} USD</p>\n", fmt_currency(total)));
+
+    let allocation =
+        compute::compute_allocation(&compute::compute_category_totals(&rows), total);
+    if !allocation.is_empty() {
+        out.push_str("<h2>Allocation</h2>\n<table>\n");
+        out.push_str(&html_row("th", &["Category", "Share"], 1));
+        for (category, pct) in &allocation {
+            let share = format!("{:.1}%", pct);
+            out.push_str(&html_row("td", &[&html_escape(&category.to_uppercase()), &share], 1));
+        }
+        out.push_str("</table>\n");
+    }
+
+    let history_rows = compute::compute_history_rows(history, portfolio, None, None)?;
+    if !history_rows.is_empty() {
+        out.push_str("<h2>History</h2>\n<table>\n");
+        out.push_str(&html_row("th", &["Date", "Total (USD)", "Change (USD)", "Change %"], 3));
+        for row in &history_rows {
+            let change = row.change_usd.map_or_else(|| "—".to_string(), fmt_change);
+            let pct = row.change_pct.map_or_else(|| "—".to_string(), fmt_pct);
+            out.push_str(&html_row(
+                "td",
+                &[&html_escape(&row.date), &fmt_currency(row.total_usd), &change, &pct],
+                3,
+            ));
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    Ok(out)
+}
+
+/// One table row of `tag` cells; the last `numeric` cells are right-aligned numbers.
+fn html_row(tag: &str, cells: &[&str], numeric: usize) -> String {
+    let first_numeric = cells.len().saturating_sub(numeric);
+    let mut row = String::from("<tr>");
+    for (i, cell) in cells.iter().enumerate() {
+        let class = if i >= first_numeric { " class=\"num\"" } else { "" };
+        row.push_str(&format!("<{tag}{class}>{cell}</{tag}>"));
+    }
+    row.push_str("</tr>\n");
+    row
+}
+
+/// Escape text for use in HTML element content.
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A copy of `portfolio` that is safe to share: assets become "Asset 1", "Asset 2", ...
+/// (ids `asset-1`, ...), notes and include paths are dropped, and every value, cost
+/// basis, value bound, event amount, and category goal is multiplied by `scale`.
+/// Categories, currencies, rates, and dates are kept, so allocations and the proportions
+/// between holdings are unchanged.
+pub fn anonymize(portfolio: &Portfolio, scale: f64) -> Portfolio {
+    let mut anon = portfolio.clone();
+    anon.includes.clear();
+    for goal in anon.settings.category_goals.values_mut() {
+        *goal *= scale;
+    }
+    let mut ids = HashMap::new();
+    for (i, asset) in anon.assets.iter_mut().enumerate() {
+        let id = format!("asset-{}", i + 1);
+        ids.insert(std::mem::replace(&mut asset.id, id.clone()), id);
+        asset.name = format!("Asset {}", i + 1);
+        asset.note = None;
+        asset.cost_basis = asset.cost_basis.map(|basis| basis * scale);
+        asset.min_value = asset.min_value.map(|min| min * scale);
+        asset.max_value = asset.max_value.map(|max| max * scale);
+    }
+    // A parent can come after the asset derived from it, so remap once all ids are known
+    for percent_of in anon.assets.iter_mut().filter_map(|a| a.percent_of.as_mut()) {
+        if let Some(id) = ids.get(&percent_of.asset_id) {
+            percent_of.asset_id = id.clone();
+        }
+    }
+    for snapshot in &mut anon.snapshots {
+        snapshot.cached_total_usd = None;
+        snapshot.note = None;
+        for event in &mut snapshot.events {
+            event.amount *= scale;
+        }
+        for entry in &mut snapshot.entries {
+            if let Some(id) = ids.get(&entry.asset_id) {
+                entry.asset_id = id.clone();
+            }
+            entry.value *= scale;
+            for lot in &mut entry.lots {
+                lot.value *= scale;
+            }
+        }
+    }
+    anon
+}
+
+/// A random factor between 0.5 and 2 for `anonymize`, different on every run.
+pub fn random_scale() -> f64 {
+    let bits = RandomState::new().hash_one(std::time::SystemTime::now());
+    0.5 + 1.5 * (bits % 1_000_000) as f64 / 1_000_000.0
+}
+
+/// One snapshot plus the assets its entries reference, so the file stands on its own
+/// (`snapshot export` / `snapshot import-one`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotBundle {
+    pub assets: Vec<Asset>,
+    pub snapshot: Snapshot,
+}
+
+/// Bundle the snapshot on `date` with the assets it references.
+pub fn snapshot_bundle(portfolio: &Portfolio, date: &str) -> Result<SnapshotBundle, NwError> {
+    let snapshot = portfolio
+        .snapshots
+        .iter()
+        .find(|s| s.date == date)
+        .ok_or_else(|| NwError::SnapshotNotFound(date.to_string()))?;
+    let assets = portfolio
+        .assets
+        .iter()
+        .filter(|a| snapshot.entries.iter().any(|e| e.asset_id == a.id))
+        .cloned()
+        .collect();
+    let mut snapshot = snapshot.clone();
+    // The receiving portfolio recomputes its own cache on save
+    snapshot.cached_total_usd = None;
+    Ok(SnapshotBundle { assets, snapshot })
+}
+
+/// Add a bundled snapshot to `portfolio`, creating any of its assets that don't exist yet.
+/// Existing assets are kept as they are, but must use the same currency as the bundle's.
+/// Bundle assets get the same id and currency checks as `asset add`, and every entry must
+/// reference an asset in the bundle or the portfolio. Returns the number of assets added.
+pub fn import_snapshot_bundle(
+    portfolio: &mut Portfolio,
+    bundle: SnapshotBundle,
+) -> Result<usize, NwError> {
+    let date = &bundle.snapshot.date;
+    if portfolio.snapshots.iter().any(|s| &s.date == date) {
+        return Err(NwError::SnapshotAlreadyExists(date.clone()));
+    }
+    let mut added: Vec<Asset> = Vec::new();
+    for mut asset in bundle.assets {
+        if asset.id.is_empty() || asset.id.trim() != asset.id {
+            let message = format!("asset id '{}' is blank or padded", asset.id);
+            return Err(NwError::InvalidBundle(message));
+        }
+        if added.iter().any(|a| a.id == asset.id) {
+            return Err(NwError::DuplicateAssetId(asset.id));
+        }
+        asset.currency = crate::model::validate_currency(&asset.currency)?;
+        match portfolio.assets.iter().find(|a| a.id == asset.id) {
+            Some(ours) if ours.currency != asset.currency => {
+                return Err(NwError::ImportCurrencyMismatch {
+                    id: asset.id,
+                    theirs: asset.currency,
+                    ours: ours.currency.clone(),
+                });
+            }
+            Some(_) => {}
+            None => added.push(asset),
+        }
+    }
+    let known = |id: &str| portfolio.assets.iter().chain(&added).any(|a| a.id == id);
+    if let Some(entry) = bundle.snapshot.entries.iter().find(|e| !known(&e.asset_id)) {
+        return Err(NwError::InvalidBundle(format!(
+            "entry for '{}' has no matching asset",
+            entry.asset_id
+        )));
+    }
+    let count = added.len();
+    portfolio.assets.extend(added);
+    portfolio.snapshots.push(bundle.snapshot);
+    Ok(count)
+}
+
+/// Quote a CSV field if it contains a separator, quote, or newline.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Asset, PercentOf};
+
+    #[test]
+    fn test_allocation_matrix_fills_missing_categories() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("vti", "vti", "etf", "USD"),
+                Asset::new("btc", "btc", "crypto", "USD"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 1000.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 750.0).with_entry("btc", 250.0),
+            ],
+            ..Default::default()
+        };
+        let snapshots: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let matrix = allocation_matrix(&snapshots, &portfolio).unwrap();
+
+        assert_eq!(matrix.categories, vec!["crypto", "etf"]);
+        assert_eq!(matrix.rows[0].0, "2025-01-01");
+        assert_eq!(matrix.rows[0].1, vec![0.0, 100.0]);
+        assert_eq!(matrix.rows[1].1, vec![25.0, 75.0]);
+
+        let csv = allocation_csv(&matrix);
+        assert_eq!(csv, "date,crypto,etf\n2025-01-01,0.00,100.00\n2025-02-01,25.00,75.00\n");
+    }
+
+    #[test]
+    fn test_html_report_has_total_and_categories() {
+        let portfolio = Portfolio {
+            assets: vec![
+                Asset::new("vti", "vti", "etf", "USD"),
+                Asset::new("btc", "Coins <cold>", "crypto", "USD"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 1000.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 12_000.0).with_entry("btc", 500.5),
+            ],
+            ..Default::default()
+        };
+        let history: Vec<&Snapshot> = portfolio.snapshots.iter().collect();
+        let html = html_report(&portfolio.snapshots[1], &history, &portfolio).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Net worth — 2025-02-01</h1>"));
+        assert!(html.contains("<h2>CRYPTO</h2>") && html.contains("<h2>ETF</h2>"));
+        assert!(html.contains("<p class=\"total\">Total: 12,500.50 USD</p>"));
+        assert!(html.contains("<td>Coins &lt;cold&gt;</td>"));
+        assert!(html.contains("<td class=\"num\">+11,500.50</td>"));
+        assert!(!html.contains("<script") && !html.contains("<link"));
+    }
+
+    #[test]
+    fn test_snapshot_bundle_round_trip() {
+        let source = Portfolio {
+            assets: vec![
+                Asset::new("vti", "vti", "etf", "USD"),
+                Asset::new("btc", "btc", "crypto", "USD"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 1000.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 750.0).with_entry("btc", 250.0),
+            ],
+            ..Default::default()
+        };
+        let bundle = snapshot_bundle(&source, "2025-01-01").unwrap();
+        // Only the assets the snapshot references travel with it
+        assert_eq!(bundle.assets.len(), 1);
+        let json = serde_json::to_string_pretty(&bundle).unwrap();
+
+        let mut target = Portfolio {
+            assets: vec![Asset::new("btc", "btc", "crypto", "USD")],
+            ..Default::default()
+        };
+        let bundle: SnapshotBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(import_snapshot_bundle(&mut target, bundle).unwrap(), 1);
+        assert_eq!(target.assets.len(), 2);
+        assert_eq!(target.snapshots.len(), 1);
+        assert_eq!(target.snapshots[0].date, "2025-01-01");
+        assert_eq!(target.snapshots[0].entries[0].value, 1000.0);
+
+        let again = snapshot_bundle(&source, "2025-01-01").unwrap();
+        let duplicate = import_snapshot_bundle(&mut target, again);
+        assert!(matches!(duplicate, Err(NwError::SnapshotAlreadyExists(_))));
+        assert!(matches!(
+            snapshot_bundle(&source, "2025-03-01"),
+            Err(NwError::SnapshotNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_snapshot_bundle_rejects_currency_mismatch() {
+        let source = Portfolio {
+            assets: vec![Asset::new("vti", "vti", "etf", "USD")],
+            snapshots: vec![Snapshot::new("2025-01-01").with_entry("vti", 1000.0)],
+            ..Default::default()
+        };
+        let mut eur = Asset::new("vti", "vti", "etf", "USD");
+        eur.currency = "EUR".to_string();
+        let mut target = Portfolio { assets: vec![eur], ..Default::default() };
+        let bundle = snapshot_bundle(&source, "2025-01-01").unwrap();
+        let result = import_snapshot_bundle(&mut target, bundle);
+        assert!(matches!(result, Err(NwError::ImportCurrencyMismatch { .. })));
+        assert!(target.snapshots.is_empty());
+    }
+
+    #[test]
+    fn test_import_snapshot_bundle_validates_assets_and_entries() {
+        let bundle = |assets: Vec<Asset>, entries: &[(&str, f64)]| SnapshotBundle {
+            assets,
+            snapshot: entries
+                .iter()
+                .fold(Snapshot::new("2025-01-01"), |s, (id, v)| s.with_entry(*id, *v)),
+        };
+        let btc = Asset::new("btc", "btc", "crypto", "USD");
+        let mut target = Portfolio { assets: vec![btc], ..Default::default() };
+
+        let mut lowercase = Asset::new("vti", "vti", "etf", "USD");
+        lowercase.currency = "usd".to_string();
+        let ok = bundle(vec![lowercase], &[("vti", 1000.0), ("btc", 250.0)]);
+        assert_eq!(import_snapshot_bundle(&mut target, ok).unwrap(), 1);
+        assert_eq!(target.assets[1].currency, "USD");
+
+        let mut target = Portfolio::default();
+        let mut bad_currency = Asset::new("vti", "vti", "etf", "USD");
+        bad_currency.currency = "dollars".to_string();
+        let result = import_snapshot_bundle(&mut target, bundle(vec![bad_currency], &[]));
+        assert!(matches!(result, Err(NwError::InvalidCurrency(_))));
+
+        let padded = bundle(vec![Asset::new(" vti", " vti", "etf", "USD")], &[]);
+        let result = import_snapshot_bundle(&mut target, padded);
+        assert!(matches!(result, Err(NwError::InvalidBundle(_))));
+
+        let vti = Asset::new("vti", "vti", "etf", "USD");
+        let twice = bundle(vec![vti.clone(), vti.clone()], &[]);
+        let result = import_snapshot_bundle(&mut target, twice);
+        assert!(matches!(result, Err(NwError::DuplicateAssetId(_))));
+
+        let dangling = bundle(vec![vti], &[("vti", 1.0), ("gone", 2.0)]);
+        let result = import_snapshot_bundle(&mut target, dangling);
+        assert!(matches!(result, Err(NwError::InvalidBundle(_))));
+        assert!(target.assets.is_empty() && target.snapshots.is_empty());
+    }
+
+    #[test]
+    fn test_anonymize_keeps_proportions() {
+        let mut vti = Asset::new("vti", "vti", "etf", "USD");
+        vti.note = Some("Brokerage account 1234".to_string());
+        vti.cost_basis = Some(800.0);
+        vti.min_value = Some(500.0);
+        vti.max_value = Some(2000.0);
+        let portfolio = Portfolio {
+            assets: vec![vti, Asset::new("btc", "btc", "crypto", "USD")],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_entry("vti", 1000.0),
+                Snapshot::new("2025-02-01").with_entry("vti", 750.0).with_entry("btc", 250.0),
+            ],
+            ..Default::default()
+        };
+        let anon = anonymize(&portfolio, 1.37);
+
+        assert_eq!(anon.assets[0].id, "asset-1");
+        assert_eq!(anon.assets[1].name, "Asset 2");
+        assert!(anon.assets[0].note.is_none());
+        assert_eq!(anon.snapshots[1].entries[1].asset_id, "asset-2");
+        assert_eq!(anon.assets[0].category, "etf");
+
+        let values = |p: &Portfolio| -> Vec<f64> {
+            p.snapshots.iter().flat_map(|s| s.entries.iter().map(|e| e.value)).collect()
+        };
+        let (before, after) = (values(&portfolio), values(&anon));
+        for (b, a) in before.iter().zip(&after) {
+            assert_ne!(a, b);
+            assert!((a / after[0] - b / before[0]).abs() < 1e-12);
+        }
+        let basis = anon.assets[0].cost_basis.unwrap();
+        assert!((basis / after[0] - 0.8).abs() < 1e-12);
+        let (min, max) = (anon.assets[0].min_value.unwrap(), anon.assets[0].max_value.unwrap());
+        assert!((min / after[0] - 0.5).abs() < 1e-12);
+        assert!((max / after[0] - 2.0).abs() < 1e-12);
+
+        let snapshots: Vec<&Snapshot> = anon.snapshots.iter().collect();
+        let matrix = allocation_matrix(&snapshots, &anon).unwrap();
+        assert_eq!(matrix.rows[1].1, vec![25.0, 75.0]);
+    }
+
+    #[test]
+    fn test_anonymize_scales_category_goals() {
+        let mut portfolio = Portfolio::default();
+        portfolio.settings.category_goals.insert("etf".to_string(), 10_000.0);
+        let anon = anonymize(&portfolio, 1.5);
+        assert_eq!(anon.settings.category_goals["etf"], 15_000.0);
+    }
+
+    #[test]
+    fn test_anonymize_remaps_percent_of() {
+        let mut equity = Asset::new("equity", "equity", "property", "USD");
+        equity.percent_of = Some(PercentOf { asset_id: "house".to_string(), fraction: 0.4 });
+        let portfolio = Portfolio {
+            assets: vec![equity, Asset::new("house", "house", "property", "USD")],
+            snapshots: vec![Snapshot::new("2025-01-01").with_entry("house", 1000.0)],
+            ..Default::default()
+        };
+        let anon = anonymize(&portfolio, 2.0);
+        assert_eq!(anon.assets[0].percent_of.as_ref().unwrap().asset_id, "asset-2");
+        let snapshots: Vec<&Snapshot> = anon.snapshots.iter().collect();
+        let matrix = allocation_matrix(&snapshots, &anon).unwrap();
+        assert_eq!(matrix.rows[0].1, vec![100.0]);
+    }
+
+    #[test]
+    fn test_random_scale_in_range() {
+        let scale = random_scale();
+        assert!((0.5..2.0).contains(&scale));
+    }
+
+    #[test]
+    fn test_csv_field_quotes_separators() {
+        assert_eq!(csv_field("etf"), "etf");
+        assert_eq!(csv_field("real estate, us"), "\"real estate, us\"");
+    }
+}
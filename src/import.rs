@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use chrono::NaiveDate;
+use crate::compute;
+use crate::error::NwError;
+use crate::model::{Portfolio, Snapshot, SnapshotEntry};
+
+/// Recorded as a snapshot's `rate_source` when its rates came from an import file.
+pub const IMPORT_RATE_SOURCE: &str = "import";
+
+/// Header prefix marking an exchange-rate column, e.g. `rate:EUR`.
+const RATE_PREFIX: &str = "rate:";
+
+enum Column {
+    Asset(String),
+    Rate(String),
+}
+
+/// The cells of one snapshot, each with the file line it came from.
+struct Record {
+    line: usize,
+    date: String,
+    cells: Vec<(usize, String)>,
+}
+
+/// Build snapshots from a wide CSV of balances: a `date` column, one column per asset id
+/// holding native values, and optional `rate:XXX` columns ("1 USD = N XXX").
+/// Blank cells leave that asset (or rate) out of the row's snapshot.
+/// The whole file is validated first; any error aborts the import with nothing returned.
+pub fn snapshots_from_csv(contents: &str, portfolio: &Portfolio) -> Result<Vec<Snapshot>, NwError> {
+    let mut lines = csv_lines(contents);
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| invalid(1, "file is empty".to_string()))?;
+    let fields = split_csv_line(header);
+    if !fields.first().is_some_and(|f| f.eq_ignore_ascii_case("date")) {
+        return Err(invalid(1, "first column must be 'date'".to_string()));
+    }
+    let columns = parse_columns(&fields[1..], |_| 1, portfolio)?;
+
+    let mut records = Vec::new();
+    for (line_no, line) in lines {
+        let mut fields = split_csv_line(line);
+        if fields.len() != columns.len() + 1 {
+            return Err(invalid(
+                line_no,
+                format!("expected {} fields, found {}", columns.len() + 1, fields.len()),
+            ));
+        }
+        let date = fields.remove(0);
+        let cells = fields.into_iter().map(|cell| (line_no, cell)).collect();
+        records.push(Record { line: line_no, date, cells });
+    }
+    build_snapshots(&columns, records, portfolio)
+}
+
+/// Build snapshots from the transposed layout spreadsheets often export: a header row of
+/// dates (its first cell is a free label), then one row per asset id and `rate:XXX` rows.
+/// Merged cells only keep their value in the first cell, so a blank cell in a rate row
+/// repeats the rate to its left; blank asset cells leave the asset out of that snapshot.
+/// Every row must have as many cells as the header.
+pub fn snapshots_from_transposed_csv(
+    contents: &str,
+    portfolio: &Portfolio,
+) -> Result<Vec<Snapshot>, NwError> {
+    let mut lines = csv_lines(contents);
+    let (header_line, header) = lines
+        .next()
+        .ok_or_else(|| invalid(1, "file is empty".to_string()))?;
+    let dates: Vec<String> = split_csv_line(header).into_iter().skip(1).collect();
+
+    let mut labels = Vec::new();
+    let mut rows = Vec::new();
+    for (line_no, line) in lines {
+        let mut fields = split_csv_line(line);
+        if fields.len() != dates.len() + 1 {
+            return Err(invalid(
+                line_no,
+                format!("expected {} fields, found {}", dates.len() + 1, fields.len()),
+            ));
+        }
+        let label = fields.remove(0);
+        if label.starts_with(RATE_PREFIX) {
+            for i in 1..fields.len() {
+                if fields[i].is_empty() {
+                    fields[i] = fields[i - 1].clone();
+                }
+            }
+        }
+        labels.push((line_no, label));
+        rows.push((line_no, fields));
+    }
+    let names: Vec<String> = labels.iter().map(|(_, name)| name.clone()).collect();
+    let columns = parse_columns(&names, |i| labels[i].0, portfolio)?;
+
+    let records = dates
+        .into_iter()
+        .enumerate()
+        .map(|(i, date)| Record {
+            line: header_line,
+            date,
+            cells: rows.iter().map(|(line_no, cells)| (*line_no, cells[i].clone())).collect(),
+        })
+        .collect();
+    build_snapshots(&columns, records, portfolio)
+}
+
+/// Non-empty lines with their 1-based line numbers, skipping a leading byte order mark.
+fn csv_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .filter(|(_, l)| !l.trim().is_empty())
+}
+
+/// Validate each record and turn it into a snapshot; `cells` follow `columns`.
+fn build_snapshots(
+    columns: &[Column],
+    records: Vec<Record>,
+    portfolio: &Portfolio,
+) -> Result<Vec<Snapshot>, NwError> {
+    let mut seen_dates = HashSet::new();
+    let mut snapshots = Vec::new();
+    for Record { line: line_no, date, cells } in records {
+        let date = date.as_str();
+        if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(NwError::InvalidDate(date.to_string()));
+        }
+        if !seen_dates.insert(date.to_string())
+            || portfolio.snapshots.iter().any(|s| s.date == date)
+        {
+            return Err(NwError::SnapshotAlreadyExists(date.to_string()));
+        }
+
+        let mut rates = HashMap::new();
+        let mut entries = Vec::new();
+        for (column, (cell_line, cell)) in columns.iter().zip(&cells) {
+            if cell.is_empty() {
+                continue;
+            }
+            let number = cell
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| invalid(*cell_line, format!("invalid number '{}'", cell)))?;
+            match column {
+                Column::Rate(currency) if number > 0.0 => {
+                    rates.insert(currency.clone(), number);
+                }
+                Column::Rate(currency) => {
+                    let message = format!("{} rate must be positive", currency);
+                    return Err(invalid(*cell_line, message));
+                }
+                Column::Asset(id) if number >= 0.0 => {
+                    entries.push(SnapshotEntry {
+                        asset_id: id.clone(),
+                        value: number,
+                        ..Default::default()
+                    });
+                }
+                Column::Asset(id) => {
+                    return Err(invalid(*cell_line, format!("value for '{}' is negative", id)));
+                }
+            }
+        }
+
+        for entry in &entries {
+            let currency = portfolio
+                .assets
+                .iter()
+                .find(|a| a.id == entry.asset_id)
+                .map(|a| a.currency.as_str())
+                .unwrap_or("USD");
+            if currency != "USD" && !rates.contains_key(currency) {
+                let message = format!(
+                    "'{}' is in {} but the row has no {}{} value",
+                    entry.asset_id, currency, RATE_PREFIX, currency
+                );
+                return Err(invalid(line_no, message));
+            }
+        }
+
+        let rate_source = (!rates.is_empty()).then(|| IMPORT_RATE_SOURCE.to_string());
+        snapshots.push(Snapshot { rates, entries, rate_source, ..Snapshot::new(date) });
+    }
+    Ok(snapshots)
+}
+
+/// Classify asset and `rate:XXX` names; `line_of` gives the line of the name at an index.
+fn parse_columns(
+    names: &[String],
+    line_of: impl Fn(usize) -> usize,
+    portfolio: &Portfolio,
+) -> Result<Vec<Column>, NwError> {
+    let ids: Vec<&str> = portfolio.assets.iter().map(|a| a.id.as_str()).collect();
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if !seen.insert(name.clone()) {
+                return Err(invalid(line_of(i), format!("duplicate column '{}'", name)));
+            }
+            if let Some(currency) = name.strip_prefix(RATE_PREFIX) {
+                let currency = currency.trim().to_uppercase();
+                if currency == "USD" {
+                    return Err(NwError::UsdRateRejected);
+                }
+                return Ok(Column::Rate(currency));
+            }
+            if !ids.contains(&name.as_str()) {
+                return Err(NwError::AssetNotFound {
+                    id: name.clone(),
+                    suggestions: compute::suggest_similar(name, &ids),
+                });
+            }
+            Ok(Column::Asset(name.clone()))
+        })
+        .collect()
+}
+
+/// Split one CSV line into trimmed fields, honoring double-quoted fields with `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+fn invalid(line: usize, message: String) -> NwError {
+    NwError::InvalidImport { line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Asset;
+
+    fn portfolio() -> Portfolio {
+        Portfolio {
+            assets: vec![
+                Asset::new("vti", "vti", "etf", "USD"),
+                Asset::new("eur-cash", "eur-cash", "etf", "EUR"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshots_from_wide_csv() {
+        let csv = "date,vti,eur-cash,rate:eur\n\
+                   2024-01-31,10000,500,0.92\n\
+                   \n\
+                   2024-02-29,10500,,\n";
+        let snapshots = snapshots_from_csv(csv, &portfolio()).unwrap();
+        assert_eq!(snapshots.len(), 2);
+
+        assert_eq!(snapshots[0].date, "2024-01-31");
+        assert_eq!(snapshots[0].rates["EUR"], 0.92);
+        assert_eq!(snapshots[0].entries.len(), 2);
+        assert_eq!(snapshots[0].entries[1].asset_id, "eur-cash");
+        assert_eq!(snapshots[0].entries[1].value, 500.0);
+        assert_eq!(snapshots[0].rate_source.as_deref(), Some(IMPORT_RATE_SOURCE));
+
+        // Blank cells omit the asset and the rate
+        assert_eq!(snapshots[1].entries.len(), 1);
+        assert!(snapshots[1].rates.is_empty());
+        assert!(snapshots[1].rate_source.is_none());
+    }
+
+    #[test]
+    fn test_import_rejects_unknown_asset_column() {
+        let result = snapshots_from_csv("date,vtii\n2024-01-31,1\n", &portfolio());
+        match result {
+            Err(NwError::AssetNotFound { id, suggestions }) => {
+                assert_eq!(id, "vtii");
+                assert_eq!(suggestions, vec!["vti"]);
+            }
+            _ => panic!("expected AssetNotFound"),
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_bad_rows() {
+        let p = portfolio();
+        let bad_date = snapshots_from_csv("date,vti\n2024-02-30,1\n", &p);
+        assert!(matches!(bad_date, Err(NwError::InvalidDate(_))));
+
+        let duplicate = snapshots_from_csv("date,vti\n2024-01-31,1\n2024-01-31,2\n", &p);
+        assert!(matches!(duplicate, Err(NwError::SnapshotAlreadyExists(_))));
+
+        let bad_value = snapshots_from_csv("date,vti\n2024-01-31,1\n2024-02-29,abc\n", &p);
+        assert!(matches!(bad_value, Err(NwError::InvalidImport { line: 3, .. })));
+
+        let short_row = snapshots_from_csv("date,vti,eur-cash\n2024-01-31,1\n", &p);
+        assert!(matches!(short_row, Err(NwError::InvalidImport { line: 2, .. })));
+
+        let no_rate = snapshots_from_csv("date,eur-cash\n2024-01-31,100\n", &p);
+        assert!(matches!(no_rate, Err(NwError::InvalidImport { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_import_rejects_existing_snapshot_and_usd_rate() {
+        let mut p = portfolio();
+        p.snapshots.push(Snapshot { date: "2024-01-31".to_string(), ..Default::default() });
+        let existing = snapshots_from_csv("date,vti\n2024-01-31,1\n", &p);
+        assert!(matches!(existing, Err(NwError::SnapshotAlreadyExists(_))));
+
+        let usd = snapshots_from_csv("date,rate:USD\n2024-02-29,1\n", &p);
+        assert!(matches!(usd, Err(NwError::UsdRateRejected)));
+    }
+
+    #[test]
+    fn test_snapshots_from_transposed_csv() {
+        let csv = "asset,2024-01-31,2024-02-29,2024-03-31\n\
+                   vti,10000,10500,11000\n\
+                   eur-cash,500,,600\n\
+                   rate:eur,0.92,,0.9\n";
+        let snapshots = snapshots_from_transposed_csv(csv, &portfolio()).unwrap();
+        assert_eq!(snapshots.len(), 3);
+
+        assert_eq!(snapshots[0].date, "2024-01-31");
+        assert_eq!(snapshots[0].entries.len(), 2);
+        assert_eq!(snapshots[0].entries[1].asset_id, "eur-cash");
+        assert_eq!(snapshots[0].entries[1].value, 500.0);
+        assert_eq!(snapshots[0].rates["EUR"], 0.92);
+
+        // A blank asset cell omits the asset; a blank rate cell repeats the merged rate
+        assert_eq!(snapshots[1].entries.len(), 1);
+        assert_eq!(snapshots[1].entries[0].value, 10500.0);
+        assert_eq!(snapshots[1].rates["EUR"], 0.92);
+        assert_eq!(snapshots[2].rates["EUR"], 0.9);
+        assert_eq!(snapshots[2].rate_source.as_deref(), Some(IMPORT_RATE_SOURCE));
+    }
+
+    #[test]
+    fn test_transposed_import_rejects_ragged_grid_and_bad_cells() {
+        let p = portfolio();
+        let ragged = snapshots_from_transposed_csv("asset,2024-01-31\nvti,1,2\n", &p);
+        assert!(matches!(ragged, Err(NwError::InvalidImport { line: 2, .. })));
+
+        let bad_value = "asset,2024-01-31,2024-02-29\nvti,1,2\n\neur-cash,abc,3\n";
+        let result = snapshots_from_transposed_csv(bad_value, &p);
+        assert!(matches!(result, Err(NwError::InvalidImport { line: 4, .. })));
+
+        let bad_date = snapshots_from_transposed_csv("asset,2024-02-30\nvti,1\n", &p);
+        assert!(matches!(bad_date, Err(NwError::InvalidDate(_))));
+
+        let unknown = snapshots_from_transposed_csv("asset,2024-01-31\nvtii,1\n", &p);
+        assert!(matches!(unknown, Err(NwError::AssetNotFound { .. })));
+    }
+
+    #[test]
+    fn test_split_csv_line_quotes() {
+        assert_eq!(split_csv_line(r#"a, "b,c" ,"d""e""#), vec!["a", "b,c", r#"d"e"#]);
+    }
+}
@@ -1,59 +1,160 @@
+mod audit;
 mod cli;
+mod clock;
 mod compute;
+#[cfg(feature = "encryption")]
+mod crypto;
 mod display;
 mod error;
+mod export;
+mod import;
 mod model;
 mod prompt;
+mod returns;
 mod store;
 
 use anyhow::Result;
 use clap::Parser;
-use cli::{Cli, Command, AssetSubcommand, SnapshotSubcommand};
-use model::HistoryRange;
+use cli::{Cli, Command, AssetSubcommand, ConfigSubcommand, CurrencySubcommand, SnapshotSubcommand};
+use model::{ExportFormat, HistoryRange, OutputFormat, SortOrder};
+use std::io::{self, IsTerminal};
+use std::process::ExitCode;
 use std::str::FromStr;
+use std::time::Duration;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    let format = cli.output_format();
+    let json = matches!(format, Ok(OutputFormat::Json));
+
+    match format.map_err(anyhow::Error::from).and_then(|_| run(cli)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if json {
+                eprintln!("{}", error::json_error(&e));
+            } else {
+                eprintln!("Error: {e:?}");
+            }
+            let code = e.downcast_ref::<error::NwError>().map_or(1, error::NwError::exit_code);
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    cli.check_writable(std::env::var(cli::READ_ONLY_ENV).ok().as_deref())?;
+
+    // Resolving the path must work even when the file itself fails to load
+    if let Command::Path = cli.command {
+        return handle_path();
+    }
+    if let Command::Log(args) = &cli.command {
+        return handle_log(args);
+    }
+    #[cfg(feature = "schema")]
+    if let Command::Schema = cli.command {
+        let schema = schemars::schema_for!(model::Portfolio);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+
     let mut portfolio = store::load_portfolio()?;
+    // Check the file as stored, without the load warnings or included data
+    if let Command::Check = cli.command {
+        return handle_check(&mut io::stdout(), &portfolio);
+    }
+
+    for (date, cached, computed) in compute::cached_total_mismatches(&portfolio) {
+        eprintln!(
+            "warning: snapshot {date} cached total {cached:.2} does not match \
+             computed {computed:.2}"
+        );
+    }
+    for date in compute::empty_snapshot_dates(&portfolio.snapshots) {
+        eprintln!("warning: snapshot {date} has no asset values (total $0)");
+    }
+    // Included data is view-only: commands that save work on this file's own contents
+    if !cli.command.is_mutating() {
+        store::resolve_includes(&mut portfolio)?;
+    }
 
     match cli.command {
-        Command::Asset(args) => handle_asset(args, &mut portfolio)?,
-        Command::Snapshot(args) => handle_snapshot(args, &mut portfolio)?,
-        Command::Show(args) => handle_show(args, &portfolio)?,
+        Command::Asset(args) => handle_asset(args, &mut portfolio, cli.decimal_comma)?,
+        Command::Snapshot(args) => handle_snapshot(args, &mut portfolio, cli.decimal_comma)?,
+        Command::Show(args) => handle_show(args, &portfolio, cli.no_color)?,
+        Command::Total(args) => handle_total(args, &portfolio, cli.no_color)?,
         Command::History(args) => handle_history(args, &portfolio)?,
+        Command::Exposure(args) => handle_exposure(args, &portfolio)?,
+        Command::Xirr(args) => handle_xirr(args, &portfolio)?,
+        Command::Export(args) => handle_export(args, &portfolio)?,
+        Command::Currency(args) => handle_currency(args, &mut portfolio)?,
+        Command::Config(args) => handle_config(args, &mut portfolio)?,
+        Command::Normalize => handle_normalize(&mut portfolio)?,
+        #[cfg(feature = "encryption")]
+        Command::Encrypt => handle_encrypt(&mut portfolio)?,
+        Command::Version => display::print_version(&store::portfolio_path()?, &portfolio),
+        Command::Path | Command::Log(_) => unreachable!("handled before loading"),
+        Command::Check => unreachable!("handled right after loading"),
+        #[cfg(feature = "schema")]
+        Command::Schema => unreachable!("handled before loading"),
     }
 
     Ok(())
 }
 
-fn handle_asset(args: cli::AssetArgs, portfolio: &mut model::Portfolio) -> Result<()> {
+fn handle_asset(
+    args: cli::AssetArgs,
+    portfolio: &mut model::Portfolio,
+    decimal_comma: bool,
+) -> Result<()> {
     match args.subcommand {
         AssetSubcommand::Add(a) => {
-            let currency = a.currency.to_uppercase();
-            if portfolio.assets.iter().any(|x| x.id == a.id) {
-                return Err(error::NwError::DuplicateAssetId(a.id).into());
+            // Reject bad flags before asking for the rest
+            if let Some(currency) = &a.currency {
+                model::validate_currency(currency)?;
             }
-            portfolio.assets.push(model::Asset {
+            let cost_basis = a.cost_basis.as_deref().map(model::parse_cost_basis).transpose()?;
+            if let Some(id) = a.id.as_deref().map(str::trim) {
+                if portfolio.assets.iter().any(|x| x.id == id) {
+                    return Err(error::NwError::DuplicateAssetId(id.to_string()).into());
+                }
+            }
+            let fields = prompt::NewAssetFields {
                 id: a.id,
                 name: a.name,
-                category: a.category.to_lowercase(),
-                currency,
+                category: a.category,
+                currency: a.currency,
+            };
+            let (mut input, mut output) = terminal();
+            let asset =
+                prompt::prompt_new_asset(&mut input, &mut output, &portfolio.assets, fields)?;
+            let summary = format!("asset add {}", asset.id);
+            portfolio.assets.push(model::Asset {
+                note: a.note.as_deref().and_then(model::parse_note),
+                cost_basis: cost_basis.flatten(),
+                ..asset
             });
             store::save_portfolio(portfolio)?;
+            audit::append(&summary);
             println!("Asset added.");
         }
         AssetSubcommand::Edit(a) => {
-            let asset = portfolio
+            let idx = portfolio
                 .assets
-                .iter_mut()
-                .find(|x| x.id == a.id)
-                .ok_or_else(|| error::NwError::AssetNotFound(a.id.clone()))?;
-            let mut changed = false;
-            if let Some(name) = a.name { asset.name = name; changed = true; }
-            if let Some(cat) = a.category { asset.category = cat.to_lowercase(); changed = true; }
-            if let Some(cur) = a.currency { asset.currency = cur.to_uppercase(); changed = true; }
+                .iter()
+                .position(|x| x.id == a.id)
+                .ok_or_else(|| asset_not_found(portfolio, &a.id))?;
+            let changed = apply_asset_edit(&mut portfolio.assets[idx], &a)?;
+            let currency_changed = a.currency.is_some();
             if changed {
+                // Every chain, not just this asset's: a parent's currency change breaks
+                // the assets derived from it
+                portfolio.validate_derived()?;
+                if currency_changed {
+                    backfill_missing_rates(portfolio, &a.id, decimal_comma)?;
+                }
                 store::save_portfolio(portfolio)?;
+                audit::append(&format!("asset edit {}", a.id));
                 println!("Asset updated.");
             } else {
                 println!("Nothing to update.");
@@ -61,94 +162,737 @@ fn handle_asset(args: cli::AssetArgs, portfolio: &mut model::Portfolio) -> Resul
         }
         AssetSubcommand::Remove(a) => {
             if !portfolio.assets.iter().any(|x| x.id == a.id) {
-                return Err(error::NwError::AssetNotFound(a.id).into());
+                return Err(asset_not_found(portfolio, &a.id).into());
             }
-            let count = portfolio
-                .snapshots
-                .iter()
-                .filter(|s| s.entries.iter().any(|e| e.asset_id == a.id))
-                .count();
-            if !prompt::confirm(&format!(
-                "This asset appears in {count} snapshot(s). Are you sure? (y/N)"
-            )) {
+            // Removing a parent would leave its derived assets pointing at nothing
+            let derived = portfolio.derived_from(&a.id);
+            if !derived.is_empty() {
+                return Err(error::NwError::AssetHasDerived { id: a.id, derived }.into());
+            }
+            let (count, drafts) = compute::snapshot_reference_counts(portfolio, &a.id);
+            let needs_confirmation =
+                !a.yes && portfolio.settings.removal_needs_confirmation(count);
+            let drafts = match drafts {
+                0 => String::new(),
+                n => format!(", {n} of them draft(s)"),
+            };
+            let (mut input, mut output) = terminal();
+            if needs_confirmation
+                && !prompt::confirm_with_default(
+                    &mut input,
+                    &mut output,
+                    &format!("This asset appears in {count} snapshot(s){drafts}. Are you sure?"),
+                    false,
+                )
+            {
                 println!("Aborted.");
                 return Ok(());
             }
             portfolio.assets.retain(|x| x.id != a.id);
             store::save_portfolio(portfolio)?;
+            audit::append(&format!("asset remove {}", a.id));
             println!("Asset removed.");
         }
         AssetSubcommand::List => {
-            display::print_asset_list(&portfolio.assets);
+            display::print_asset_list(&mut io::stdout(), &portfolio.assets)?;
+        }
+        AssetSubcommand::Show(a) => {
+            let asset = portfolio
+                .assets
+                .iter()
+                .find(|x| x.id == a.id)
+                .ok_or_else(|| asset_not_found(portfolio, &a.id))?;
+            display::print_asset_detail(&mut io::stdout(), asset, portfolio)?;
         }
     }
     Ok(())
 }
 
-fn handle_snapshot(args: cli::SnapshotArgs, portfolio: &mut model::Portfolio) -> Result<()> {
+/// After an asset's currency changes, offer to enter rates for the snapshots
+/// that hold the asset but have no rate for its new currency.
+fn backfill_missing_rates(
+    portfolio: &mut model::Portfolio,
+    asset_id: &str,
+    decimal_comma: bool,
+) -> Result<()> {
+    let Some(currency) = portfolio
+        .assets
+        .iter()
+        .find(|a| a.id == asset_id)
+        .map(|a| a.currency.clone())
+    else {
+        return Ok(());
+    };
+    // Locked snapshots are closed out, so they are only reported, never filled in
+    let (locked, dates): (Vec<String>, Vec<String>) =
+        compute::snapshots_missing_rate(portfolio, asset_id).into_iter().partition(|date| {
+            portfolio.snapshots.iter().any(|s| &s.date == date && s.locked)
+        });
+    if !locked.is_empty() {
+        eprintln!(
+            "warning: locked snapshots have no {currency} rate; unlock them to add it: {}",
+            locked.join(", ")
+        );
+    }
+    if dates.is_empty() {
+        return Ok(());
+    }
+    let (mut input, mut output) = terminal();
+    let question = format!("{} snapshot(s) have no {currency} rate. Enter them now?", dates.len());
+    if !prompt::confirm_with_default(&mut input, &mut output, &question, false) {
+        eprintln!(
+            "warning: these snapshots cannot be shown until a {currency} rate is added: {}",
+            dates.join(", ")
+        );
+        return Ok(());
+    }
+    for date in dates {
+        println!("Snapshot {date}");
+        let currencies = std::slice::from_ref(&currency);
+        let rates = prompt::prompt_rates(
+            &mut input,
+            &mut output,
+            currencies,
+            None,
+            portfolio.settings.rate_sig_figs,
+            decimal_comma,
+        )?;
+        if let Some(snapshot) = portfolio.snapshots.iter_mut().find(|s| s.date == date) {
+            snapshot.rates.extend(rates);
+        }
+    }
+    Ok(())
+}
+
+/// Apply the `asset edit` flags to `asset`. Every optional field is cleared by passing
+/// `none`. Returns whether any flag was given.
+fn apply_asset_edit(
+    asset: &mut model::Asset,
+    a: &cli::AssetEditArgs,
+) -> Result<bool, error::NwError> {
+    let mut changed = false;
+    if let Some(name) = &a.name {
+        asset.name = name.trim().to_string();
+        changed = true;
+    }
+    if let Some(cat) = &a.category {
+        asset.category = model::normalize_category(cat);
+        changed = true;
+    }
+    if let Some(cur) = &a.currency {
+        asset.currency = model::validate_currency(cur)?;
+        changed = true;
+    }
+    if let Some(basis) = &a.cost_basis {
+        asset.cost_basis = model::parse_cost_basis(basis)?;
+        changed = true;
+    }
+    if let Some(min) = &a.min_value {
+        asset.min_value = model::parse_value_bound(min)?;
+        changed = true;
+    }
+    if let Some(max) = &a.max_value {
+        asset.max_value = model::parse_value_bound(max)?;
+        changed = true;
+    }
+    if let (Some(min), Some(max)) = (asset.min_value, asset.max_value) {
+        if min > max {
+            return Err(error::NwError::ValueBoundsReversed { min, max });
+        }
+    }
+    if let Some(percent_of) = &a.percent_of {
+        asset.percent_of = model::parse_percent_of(percent_of)?;
+        changed = true;
+    }
+    if let Some(order) = &a.order {
+        asset.sort_order = model::parse_sort_order(order)?;
+        changed = true;
+    }
+    if let Some(note) = &a.note {
+        asset.note = model::parse_note(note);
+        changed = true;
+    }
+    Ok(changed)
+}
+
+fn handle_snapshot(
+    args: cli::SnapshotArgs,
+    portfolio: &mut model::Portfolio,
+    decimal_comma: bool,
+) -> Result<()> {
+    let (mut input, mut output) = terminal();
     match args.subcommand {
         SnapshotSubcommand::Add(a) => {
             validate_date(&a.date)?;
+            let events = parse_events(&a.events)?;
             if portfolio.snapshots.iter().any(|s| s.date == a.date) {
                 return Err(error::NwError::SnapshotAlreadyExists(a.date).into());
             }
+            let too_close = portfolio.settings.min_snapshot_interval_days.and_then(|min_days| {
+                compute::nearest_snapshot_gap(&portfolio.snapshots, &a.date)
+                    .filter(|(_, days)| *days < i64::from(min_days))
+                    .map(|(nearest, days)| (nearest, days, min_days))
+            });
+            if let Some((nearest, days, min_days)) = too_close {
+                if a.strict {
+                    let date = a.date;
+                    return Err(
+                        error::NwError::SnapshotTooClose { date, nearest, days, min_days }.into()
+                    );
+                }
+                let question = format!(
+                    "{} is {} day(s) from the snapshot on {} (minimum {}). Add anyway?",
+                    a.date, days, nearest, min_days
+                );
+                if !prompt::confirm_with_default(&mut input, &mut output, &question, false) {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+            }
             let currencies = collect_non_usd_currencies(portfolio);
-            let rates = prompt::prompt_rates(&currencies, None)?;
-            let entries_raw = prompt::prompt_asset_values(&portfolio.assets, None)?;
-            let entries = entries_raw
-                .into_iter()
-                .map(|(asset_id, value)| model::SnapshotEntry { asset_id, value })
-                .collect();
+            // Enter reuses the preceding snapshot's rates, as in the edit flow
+            let default_rates = compute::rates_to_reuse(&portfolio.snapshots, &a.date);
+            let rates = prompt::prompt_rates(
+                &mut input,
+                &mut output,
+                &currencies,
+                default_rates,
+                portfolio.settings.rate_sig_figs,
+                decimal_comma,
+            )?;
+            let previous_map = previous_entry_map(portfolio, &a.date);
+            let entries = prompt::prompt_asset_values(
+                &mut input,
+                &mut output,
+                &portfolio.assets,
+                None,
+                previous_map.as_ref(),
+                &portfolio.settings,
+                a.lots,
+                decimal_comma,
+            )?;
+            if entries.is_empty()
+                && !prompt::confirm_with_default(
+                    &mut input,
+                    &mut output,
+                    "No asset values entered. Save an empty snapshot?",
+                    false,
+                )
+            {
+                println!("Aborted.");
+                return Ok(());
+            }
+            let rate_source = rate_source_for(&rates);
+            let date = a.date.clone();
             portfolio.snapshots.push(model::Snapshot {
                 date: a.date,
                 rates,
                 entries,
+                cached_total_usd: None,
+                rate_source,
+                draft: a.draft,
+                events,
+                note: a.note.filter(|n| !n.trim().is_empty()),
+                locked: false,
             });
             store::save_portfolio(portfolio)?;
-            println!("Snapshot saved.");
+            let draft = if a.draft { " (draft)" } else { "" };
+            audit::append(&format!("snapshot add {date}{draft}"));
+            if a.draft {
+                println!("Draft snapshot saved; `nw snapshot finalize --date {date}` to count it.");
+            } else {
+                println!("Snapshot saved.");
+            }
+            warn_rate_jumps(portfolio, &date);
         }
         SnapshotSubcommand::Edit(a) => {
             validate_date(&a.date)?;
+            let events = parse_events(&a.events)?;
             let idx = portfolio
                 .snapshots
                 .iter()
                 .position(|s| s.date == a.date)
                 .ok_or_else(|| error::NwError::SnapshotNotFound(a.date.clone()))?;
-            if !prompt::confirm(&format!("Overwrite snapshot for {}? (y/N)", a.date)) {
+            portfolio.snapshots[idx].ensure_unlocked(a.force)?;
+            let question = format!("Overwrite snapshot for {}?", a.date);
+            if !prompt::confirm_with_default(&mut input, &mut output, &question, false) {
                 println!("Aborted.");
                 return Ok(());
             }
             let existing = portfolio.snapshots[idx].clone();
             let currencies = collect_non_usd_currencies(portfolio);
-            let rates = prompt::prompt_rates(&currencies, Some(&existing.rates))?;
+            let existing_rates = Some(&existing.rates);
+            let rates = prompt::prompt_rates(
+                &mut input,
+                &mut output,
+                &currencies,
+                existing_rates,
+                portfolio.settings.rate_sig_figs,
+                decimal_comma,
+            )?;
             let existing_map: std::collections::HashMap<String, f64> = existing
                 .entries
                 .iter()
                 .map(|e| (e.asset_id.clone(), e.value))
                 .collect();
-            let entries_raw =
-                prompt::prompt_asset_values(&portfolio.assets, Some(&existing_map))?;
-            let entries = entries_raw
-                .into_iter()
-                .map(|(asset_id, value)| model::SnapshotEntry { asset_id, value })
-                .collect();
+            let previous_map = previous_entry_map(portfolio, &a.date);
+            let mut entries = prompt::prompt_asset_values(
+                &mut input,
+                &mut output,
+                &portfolio.assets,
+                Some(&existing_map),
+                previous_map.as_ref(),
+                &portfolio.settings,
+                a.lots,
+                decimal_comma,
+            )?;
+            // Keep recorded lots for values that were left unchanged
+            for entry in entries.iter_mut().filter(|e| e.lots.is_empty()) {
+                if let Some(old) = existing
+                    .entries
+                    .iter()
+                    .find(|e| e.asset_id == entry.asset_id && e.value == entry.value)
+                {
+                    entry.lots = old.lots.clone();
+                }
+            }
+            portfolio.snapshots[idx].rate_source = rate_source_for(&rates);
             portfolio.snapshots[idx].rates = rates;
             portfolio.snapshots[idx].entries = entries;
+            if !events.is_empty() {
+                portfolio.snapshots[idx].events = events;
+            }
+            if let Some(note) = a.note {
+                portfolio.snapshots[idx].note = (!note.trim().is_empty()).then_some(note);
+            }
+            let unused = compute::unused_rates(&portfolio.snapshots[idx], &portfolio.assets);
+            if !unused.is_empty() {
+                let question = format!(
+                    "No holdings left in {}. Drop the unused rate(s)?",
+                    unused.join(", ")
+                );
+                if prompt::confirm_with_default(&mut input, &mut output, &question, false) {
+                    portfolio.snapshots[idx].rates.retain(|c, _| !unused.contains(c));
+                }
+            }
             store::save_portfolio(portfolio)?;
+            audit::append(&format!("snapshot edit {}", a.date));
             println!("Snapshot updated.");
+            warn_rate_jumps(portfolio, &a.date);
         }
-        SnapshotSubcommand::List => {
-            display::print_snapshot_list(&portfolio.snapshots);
+        SnapshotSubcommand::Import(a) => {
+            let contents = std::fs::read_to_string(&a.input).map_err(|e| {
+                error::NwError::ReadFile { path: a.input.display().to_string(), source: e }
+            })?;
+            let snapshots = if a.transposed {
+                import::snapshots_from_transposed_csv(&contents, portfolio)?
+            } else {
+                import::snapshots_from_csv(&contents, portfolio)?
+            };
+            let count = snapshots.len();
+            portfolio.snapshots.extend(snapshots);
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!(
+                "snapshot import {} snapshot(s) from {}",
+                count,
+                a.input.display()
+            ));
+            println!("Imported {} snapshot(s).", count);
+        }
+        SnapshotSubcommand::Finalize(a) => {
+            validate_date(&a.date)?;
+            let snapshot = portfolio
+                .snapshots
+                .iter_mut()
+                .find(|s| s.date == a.date)
+                .ok_or_else(|| error::NwError::SnapshotNotFound(a.date.clone()))?;
+            if !snapshot.draft {
+                println!("Snapshot {} is not a draft.", a.date);
+                return Ok(());
+            }
+            snapshot.draft = false;
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!("snapshot finalize {}", a.date));
+            println!("Snapshot finalized.");
+        }
+        SnapshotSubcommand::Lock(a) => set_snapshot_lock(portfolio, &a.date, true)?,
+        SnapshotSubcommand::Unlock(a) => set_snapshot_lock(portfolio, &a.date, false)?,
+        SnapshotSubcommand::Rename(a) => {
+            validate_date(&a.from)?;
+            validate_date(&a.to)?;
+            if let Some(snapshot) = portfolio.snapshots.iter().find(|s| s.date == a.from) {
+                snapshot.ensure_unlocked(a.force)?;
+            }
+            portfolio.rename_snapshot(&a.from, &a.to)?;
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!("snapshot rename {} → {}", a.from, a.to));
+            println!("Snapshot {} moved to {}.", a.from, a.to);
+        }
+        SnapshotSubcommand::Remove(a) => {
+            validate_date(&a.date)?;
+            let snapshot = portfolio
+                .snapshots
+                .iter()
+                .find(|s| s.date == a.date)
+                .ok_or_else(|| error::NwError::SnapshotNotFound(a.date.clone()))?;
+            snapshot.ensure_unlocked(a.force)?;
+            let question = format!(
+                "Remove snapshot {} with {} entry(ies)?",
+                a.date,
+                snapshot.entries.len()
+            );
+            if !a.yes && !prompt::confirm_with_default(&mut input, &mut output, &question, false) {
+                println!("Aborted.");
+                return Ok(());
+            }
+            portfolio.snapshots.retain(|s| s.date != a.date);
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!("snapshot remove {}", a.date));
+            println!("Snapshot removed.");
+        }
+        SnapshotSubcommand::Export(a) => {
+            validate_date(&a.date)?;
+            let anonymized =
+                a.anonymize.then(|| export::anonymize(portfolio, export::random_scale()));
+            let source = anonymized.as_ref().unwrap_or(portfolio);
+            let bundle = export::snapshot_bundle(source, &a.date)?;
+            let path = a.out.as_ref().map_or("stdout".to_string(), |p| p.display().to_string());
+            let mut contents = serde_json::to_string_pretty(&bundle)
+                .map_err(|e| error::NwError::SerializeJson { path, source: e })?;
+            contents.push('\n');
+            write_export(a.out, &contents)?;
+        }
+        SnapshotSubcommand::ImportOne(a) => {
+            let path = a.input.display().to_string();
+            let contents = std::fs::read_to_string(&a.input)
+                .map_err(|e| error::NwError::ReadFile { path: path.clone(), source: e })?;
+            let bundle: export::SnapshotBundle = serde_json::from_str(&contents)
+                .map_err(|e| error::NwError::MalformedJson { path, source: e })?;
+            validate_date(&bundle.snapshot.date)?;
+            let date = bundle.snapshot.date.clone();
+            let added = export::import_snapshot_bundle(portfolio, bundle)?;
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!("snapshot import-one {} from {}", date, a.input.display()));
+            println!("Imported snapshot {} ({} new asset(s)).", date, added);
+        }
+        SnapshotSubcommand::List(a) => {
+            let order = SortOrder::from_str(&a.sort)?;
+            let selected = compute::select_snapshots(&portfolio.snapshots, order, a.limit);
+            display::print_snapshot_list(&mut io::stdout(), &selected, portfolio)?;
         }
     }
     Ok(())
 }
 
-fn handle_show(args: cli::ShowArgs, portfolio: &model::Portfolio) -> Result<()> {
-    if portfolio.snapshots.is_empty() {
-        println!("No snapshots yet.");
+/// `snapshot lock` / `snapshot unlock`.
+fn set_snapshot_lock(portfolio: &mut model::Portfolio, date: &str, lock: bool) -> Result<()> {
+    validate_date(date)?;
+    let snapshot = portfolio
+        .snapshots
+        .iter_mut()
+        .find(|s| s.date == date)
+        .ok_or_else(|| error::NwError::SnapshotNotFound(date.to_string()))?;
+    let verb = if lock { "lock" } else { "unlock" };
+    if snapshot.locked == lock {
+        println!("Snapshot {} is already {}ed.", date, verb);
         return Ok(());
     }
+    snapshot.locked = lock;
+    store::save_portfolio(portfolio)?;
+    audit::append(&format!("snapshot {} {}", verb, date));
+    println!("Snapshot {}ed.", verb);
+    Ok(())
+}
+
+/// Parse repeated `--event KIND:AMOUNT` flags.
+fn parse_events(events: &[String]) -> Result<Vec<model::SnapshotEvent>, error::NwError> {
+    events.iter().map(|e| model::SnapshotEvent::from_str(e)).collect()
+}
+
+/// `AssetNotFound` for `id`, suggesting existing ids that look like typos of it.
+fn asset_not_found(portfolio: &model::Portfolio, id: &str) -> error::NwError {
+    let ids: Vec<&str> = portfolio.assets.iter().map(|a| a.id.as_str()).collect();
+    error::NwError::AssetNotFound {
+        id: id.to_string(),
+        suggestions: compute::suggest_similar(id, &ids),
+    }
+}
+
+/// Standard input and output for the interactive prompts.
+fn terminal() -> (io::StdinLock<'static>, io::Stdout) {
+    (io::stdin().lock(), io::stdout())
+}
+
+/// Warn about order-of-magnitude rate changes into or out of the snapshot at `date`.
+fn warn_rate_jumps(portfolio: &model::Portfolio, date: &str) {
+    for jump in compute::rate_jump_warnings(&portfolio.snapshots) {
+        if jump.date == date || jump.previous_date == date {
+            eprintln!(
+                "warning: {} rate went from {} ({}) to {} ({}); check for a typo",
+                jump.currency, jump.previous_rate, jump.previous_date, jump.rate, jump.date
+            );
+        }
+    }
+}
+
+fn handle_export(args: cli::ExportArgs, portfolio: &model::Portfolio) -> Result<()> {
+    let format = ExportFormat::from_str(&args.kind)?;
+    let range = HistoryRange::from_str(&args.range)?;
+    let anonymized = args.anonymize.then(|| export::anonymize(portfolio, export::random_scale()));
+    let portfolio = anonymized.as_ref().unwrap_or(portfolio);
+    let today = clock::today()?.to_string();
+    let filtered = compute::filter_by_range(
+        &portfolio.snapshots,
+        range,
+        &today,
+        portfolio.settings.fiscal_start_month(),
+        false,
+    );
+
+    let contents = match format {
+        ExportFormat::AllocationCsv => {
+            export::allocation_csv(&export::allocation_matrix(&filtered, portfolio)?)
+        }
+        ExportFormat::Html => {
+            let visible = compute::visible_snapshots(&portfolio.snapshots, false);
+            let latest = visible.last().ok_or(error::NwError::NoSnapshots)?;
+            export::html_report(latest, &filtered, portfolio)?
+        }
+    };
+
+    write_export(args.out, &contents)
+}
+
+/// Write exported `contents` to `out`, or to stdout when no file is given.
+fn write_export(out: Option<std::path::PathBuf>, contents: &str) -> Result<()> {
+    match out {
+        Some(path) => {
+            std::fs::write(&path, contents).map_err(|e| error::NwError::WriteFile {
+                path: path.display().to_string(),
+                source: e,
+            })?;
+            println!("Exported to {}.", path.display());
+        }
+        None => print!("{contents}"),
+    }
+    Ok(())
+}
+
+/// List each problem found in the file; silent when it is clean.
+fn handle_check(out: &mut impl io::Write, portfolio: &model::Portfolio) -> Result<()> {
+    let problems = compute::check_problems(portfolio);
+    for problem in &problems {
+        writeln!(out, "{problem}")?;
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(error::NwError::CheckFailed(problems.len()).into())
+    }
+}
+
+fn handle_currency(args: cli::CurrencyArgs, portfolio: &mut model::Portfolio) -> Result<()> {
+    match args.subcommand {
+        CurrencySubcommand::Rename(a) => {
+            let count = portfolio.rename_currency(&a.from, &a.into)?;
+            store::save_portfolio(portfolio)?;
+            audit::append(&format!("currency rename {} → {}", a.from, a.into));
+            println!("Currency renamed on {count} asset(s).");
+        }
+    }
+    Ok(())
+}
+
+fn handle_config(args: cli::ConfigArgs, portfolio: &mut model::Portfolio) -> Result<()> {
+    let summary = match args.subcommand {
+        ConfigSubcommand::Decimals(a) => {
+            if a.places > model::MAX_DECIMALS {
+                return Err(error::NwError::InvalidDecimals(a.places).into());
+            }
+            let currency = model::validate_currency(&a.currency)?;
+            let summary = format!("config decimals {} {}", currency, a.places);
+            portfolio.settings.currency_decimals.insert(currency, a.places);
+            summary
+        }
+        ConfigSubcommand::ConfirmThreshold(a) => {
+            portfolio.settings.confirm_threshold = a.snapshots;
+            format!("config confirm-threshold {}", fmt_setting(a.snapshots))
+        }
+        ConfigSubcommand::FiscalYearStart(a) => {
+            if !(1..=12).contains(&a.month) {
+                return Err(error::NwError::InvalidMonth(a.month).into());
+            }
+            // January is the default, so don't store it
+            portfolio.settings.fiscal_year_start_month = (a.month != 1).then_some(a.month);
+            format!("config fiscal-year-start {}", a.month)
+        }
+        ConfigSubcommand::DefaultShow(a) => {
+            let mode = model::DefaultShow::from_str(&a.mode)?;
+            // Latest is the default, so don't store it
+            portfolio.settings.default_show = (mode != model::DefaultShow::Latest).then_some(mode);
+            format!("config default-show {}", a.mode.to_lowercase())
+        }
+        ConfigSubcommand::MinSnapshotInterval(a) => {
+            portfolio.settings.min_snapshot_interval_days = a.days.filter(|d| *d > 0);
+            let days = portfolio.settings.min_snapshot_interval_days;
+            format!("config min-snapshot-interval {}", fmt_setting(days))
+        }
+        ConfigSubcommand::RateSigFigs(a) => {
+            portfolio.settings.rate_sig_figs = a.figures;
+            format!("config rate-sig-figs {}", fmt_setting(a.figures))
+        }
+        ConfigSubcommand::RatePolicy(a) => {
+            let policy = model::RatePolicy::from_str(&a.policy)?;
+            // prefer-manual is the default, so don't store it
+            let is_default = policy == model::RatePolicy::default();
+            portfolio.settings.rate_policy = (!is_default).then_some(policy);
+            format!("config rate-policy {}", a.policy.to_lowercase())
+        }
+        ConfigSubcommand::CategoryColor(a) => {
+            let (category, color) = model::parse_category_color(&a.assignment)?;
+            let summary = format!("config category-color {}", a.assignment);
+            match color {
+                Some(color) => portfolio.settings.category_colors.insert(category, color),
+                None => portfolio.settings.category_colors.remove(&category),
+            };
+            summary
+        }
+        ConfigSubcommand::CategoryGoal(a) => {
+            let (category, goal) = model::parse_category_goal(&a.assignment)?;
+            let summary = format!("config category-goal {}", a.assignment);
+            match goal {
+                Some(goal) => portfolio.settings.category_goals.insert(category, goal),
+                None => portfolio.settings.category_goals.remove(&category),
+            };
+            summary
+        }
+    };
+    store::save_portfolio(portfolio)?;
+    audit::append(&summary);
+    println!("Setting saved.");
+    Ok(())
+}
+
+/// An optional setting for the audit log; unset shows as "none".
+fn fmt_setting(value: Option<impl std::fmt::Display>) -> String {
+    value.map_or("none".to_string(), |v| v.to_string())
+}
+
+#[cfg(feature = "encryption")]
+fn handle_encrypt(portfolio: &mut model::Portfolio) -> Result<()> {
+    if store::is_encrypted()? {
+        println!("Portfolio is already encrypted.");
+        return Ok(());
+    }
+    // stderr, so retry messages stay out of piped output
+    let passphrase = prompt::prompt_new_passphrase(&mut io::stderr())?;
+    let path = store::enable_encryption(portfolio, passphrase)?;
+    audit::append("encrypt");
+    println!("Portfolio encrypted to {}.", path.display());
+    Ok(())
+}
+
+fn handle_normalize(portfolio: &mut model::Portfolio) -> Result<()> {
+    let changes = portfolio.normalize_fields()?;
+    if changes.is_empty() {
+        println!("Nothing to normalize.");
+        return Ok(());
+    }
+    for change in &changes {
+        println!("  {}", change);
+    }
+    store::save_portfolio(portfolio)?;
+    audit::append(&format!("normalize ({} change(s))", changes.len()));
+    println!("Normalized {} field(s).", changes.len());
+    Ok(())
+}
+
+fn handle_log(args: &cli::LogArgs) -> Result<()> {
+    let entries = audit::tail(args.lines)?;
+    if entries.is_empty() {
+        println!("No changes logged yet.");
+    }
+    for entry in entries {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+fn handle_path() -> Result<()> {
+    let path = store::portfolio_path()?;
+    let status = if path.exists() { "exists" } else { "not created yet" };
+    println!("{} ({})", path.display(), status);
+    Ok(())
+}
+
+fn handle_show(args: cli::ShowArgs, portfolio: &model::Portfolio, no_color: bool) -> Result<()> {
+    let Some(interval) = args.watch else {
+        return render_show(&mut io::stdout(), &args, portfolio, no_color);
+    };
+    if !io::stdout().is_terminal() {
+        eprintln!("warning: --watch needs a terminal; showing once");
+        return render_show(&mut io::stdout(), &args, portfolio, no_color);
+    }
+    // Runs until interrupted. Ctrl-C's default action ends the process, and as nothing
+    // here changes terminal modes there is no state to restore on the way out.
+    watch(&mut io::stdout(), Duration::from_secs(interval), false, |out| {
+        let mut portfolio = store::load_portfolio()?;
+        store::resolve_includes(&mut portfolio)?;
+        render_show(out, &args, &portfolio, no_color)
+    })
+}
+
+/// Clear the screen and move the cursor to the top-left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Clear the screen and call `render` every `interval`, forever unless `once` is set.
+/// Stops at the first render error.
+fn watch<W: io::Write>(
+    out: &mut W,
+    interval: Duration,
+    once: bool,
+    mut render: impl FnMut(&mut W) -> Result<()>,
+) -> Result<()> {
+    loop {
+        write!(out, "{CLEAR_SCREEN}")?;
+        render(out)?;
+        out.flush()?;
+        if once {
+            return Ok(());
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn render_show(
+    out: &mut impl io::Write,
+    args: &cli::ShowArgs,
+    portfolio: &model::Portfolio,
+    no_color: bool,
+) -> Result<()> {
+    if let Some(pattern) = &args.date_format {
+        display::validate_date_format(pattern)?;
+    }
+    let allocation_by = args
+        .allocation_by
+        .as_deref()
+        .map(model::AllocationBy::from_str)
+        .transpose()?
+        .unwrap_or_default();
+    let highlight_above = args
+        .highlight_above
+        .as_deref()
+        .map(compute::parse_percent)
+        .transpose()?;
+    // An explicit --date may name a draft; index and default selection skip drafts
+    let visible = compute::visible_snapshots(&portfolio.snapshots, args.include_drafts);
+    let Some(&latest) = visible.last() else {
+        if args.fail_on_empty {
+            return Err(error::NwError::NoSnapshots.into());
+        }
+        writeln!(out, "No snapshots yet.")?;
+        return Ok(());
+    };
 
     let snapshot = if let Some(date) = &args.date {
         validate_date(date)?;
@@ -157,33 +901,294 @@ fn handle_show(args: cli::ShowArgs, portfolio: &model::Portfolio) -> Result<()>
             .iter()
             .find(|s| &s.date == date)
             .ok_or_else(|| error::NwError::SnapshotNotFound(date.clone()))?
+    } else if let Some(index) = args.index {
+        compute::snapshot_at_offset(&visible, index)?
     } else {
-        portfolio.snapshots.last().expect("non-empty checked above")
+        match portfolio.settings.default_show {
+            Some(model::DefaultShow::LatestComplete) => {
+                compute::latest_complete_snapshot(&visible, &portfolio.assets)
+                    .unwrap_or_else(|| {
+                        eprintln!("warning: no snapshot has every asset; showing the latest");
+                        latest
+                    })
+            }
+            Some(model::DefaultShow::Latest) | None => latest,
+        }
     };
 
     let category_filter = args.category.as_deref();
-    let (grand_total, rows) =
-        compute::compute_show_rows(snapshot, portfolio, category_filter)?;
+    let (mut grand_total, mut rows, missing_rates) = if args.lenient {
+        compute::compute_show_rows_lenient(snapshot, portfolio, category_filter)?
+    } else {
+        let (total, rows) = compute::compute_show_rows(snapshot, portfolio, category_filter)?;
+        (total, rows, Vec::new())
+    };
+    if args.show_missing {
+        rows.extend(compute::missing_asset_rows(snapshot, portfolio, category_filter));
+    }
+    // Kept out of the total and allocation (they have no USD value) and added for display
+    let unknown = if args.show_unknown {
+        compute::unknown_asset_rows(snapshot, portfolio, category_filter)
+    } else {
+        Vec::new()
+    };
+
+    if let Some(date) = &args.compare {
+        validate_date(date)?;
+        let compare = portfolio
+            .snapshots
+            .iter()
+            .find(|s| &s.date == date)
+            .ok_or_else(|| error::NwError::SnapshotNotFound(date.clone()))?;
+        let compare = compute::with_derived_entries(compare, portfolio)?;
+        compute::apply_comparison(&mut rows, &compare)?;
+    }
+    // Only what is displayed is rounded; comparisons above use the exact values
+    let round = |value: f64| match args.round_to {
+        Some(n) => compute::round_to_nearest(value, n as f64),
+        None => value,
+    };
+    if let Some(n) = args.round_to {
+        grand_total = compute::round_show_rows(&mut rows, n as f64);
+    }
 
-    let allocation = compute::compute_allocation(
+    // Within a category, holdings' shares of its subtotal replace category shares
+    let totals = if args.allocation_within {
+        compute::compute_asset_totals(&rows)
+    } else {
+        compute::compute_allocation_totals(&rows, allocation_by)
+    };
+    let allocation = compute::compute_allocation(&totals, grand_total);
+    let allocation = if args.allocation_within {
+        compute::asset_allocation_names(allocation, &rows)
+    } else {
+        allocation
+    };
+    let goals = compute::category_goal_progress(
         &compute::compute_category_totals(&rows),
-        grand_total,
+        &portfolio.settings.category_goals,
+        category_filter,
     );
 
-    display::print_show(rows, grand_total, allocation, &snapshot.date, category_filter);
+    let at_latest_rates = if args.at_latest_rates && latest.date != snapshot.date {
+        let total =
+            compute::total_at_rates(snapshot, portfolio, &latest.rates, category_filter)?;
+        Some((latest.date.as_str(), round(total)))
+    } else {
+        None
+    };
+
+    // Measured to the total as shown. A lifetime summary that can't be computed (e.g. a
+    // rate missing in the first snapshot) or that --lenient dropped holdings from is left
+    // out rather than failing the whole view
+    let since_inception = match category_filter {
+        None if missing_rates.is_empty() => {
+            compute::since_inception(&visible, &snapshot.date, grand_total, portfolio)
+                .ok()
+                .flatten()
+        }
+        _ => None,
+    };
+
+    let opts = display::ShowOptions {
+        category_filter,
+        min_value: args.min_value,
+        date_format: args.date_format.as_deref(),
+        highlight_above,
+        at_latest_rates,
+        missing_rates: &missing_rates,
+        color: display::color_enabled(no_color),
+        settings: Some(&portfolio.settings),
+        compare_date: args.compare.as_deref(),
+        allocation_within: args.allocation_within,
+        goals: &goals,
+        no_allocation: args.no_allocation,
+        allocation_only: args.allocation_only,
+        since_inception: since_inception
+            .as_ref()
+            .map(|(date, change, pct)| (date.as_str(), round(*change), *pct)),
+    };
+    rows.extend(unknown);
+    display::print_show(out, rows, grand_total, allocation, &snapshot.date, &opts)?;
     Ok(())
 }
 
 fn handle_history(args: cli::HistoryArgs, portfolio: &model::Portfolio) -> Result<()> {
-    let range = HistoryRange::from_str(&args.range)?;
-    let today = chrono::Local::now().date_naive().to_string();
-    let filtered = compute::filter_by_range(&portfolio.snapshots, range, &today);
+    if let Some(pattern) = &args.date_format {
+        display::validate_date_format(pattern)?;
+    }
+    if args.since_last {
+        return handle_since_last(&args, portfolio);
+    }
+    let range = HistoryRange::from_str(args.range.as_deref().unwrap_or_default())?;
+    let today = clock::today()?.to_string();
+    let filtered = compute::filter_by_range(
+        &portfolio.snapshots,
+        range,
+        &today,
+        portfolio.settings.fiscal_start_month(),
+        args.include_drafts,
+    );
+    // Filtering before computing rows makes each change relative to the previous match
+    let filtered = match &args.note_contains {
+        Some(needle) => compute::filter_by_note(filtered, needle),
+        None => filtered,
+    };
+    if args.explain {
+        let cutoff =
+            compute::range_cutoff(range, &today, portfolio.settings.fiscal_start_month());
+        display::print_range_explain(
+            &mut io::stdout(),
+            cutoff.as_deref(),
+            &portfolio.snapshots,
+            &filtered,
+        )?;
+    }
     if filtered.is_empty() {
+        if args.fail_on_empty {
+            return Err(error::NwError::NoSnapshotsInRange(range.to_string()).into());
+        }
         println!("No snapshots in range.");
         return Ok(());
     }
-    let history_rows = compute::compute_history_rows(&filtered, portfolio)?;
-    display::print_history(history_rows, &range.to_string());
+    let category_filter = args.category.map(|c| c.to_lowercase());
+    let latest = compute::visible_snapshots(&portfolio.snapshots, args.include_drafts)
+        .last()
+        .copied()
+        .filter(|_| args.constant_latest_rates);
+    let mut history_rows = compute::compute_history_rows(
+        &filtered,
+        portfolio,
+        category_filter.as_deref(),
+        latest.map(|s| &s.rates),
+    )?;
+    if args.attribution {
+        for (row, pair) in history_rows.iter_mut().skip(1).zip(filtered.windows(2)) {
+            row.attribution = Some(compute::change_attribution(pair[0], pair[1], portfolio)?);
+        }
+    }
+    if args.events {
+        // Each change spans everything since the previous listed snapshot, including
+        // snapshots a note filter left out
+        let visible = compute::visible_snapshots(&portfolio.snapshots, args.include_drafts);
+        for (row, pair) in history_rows.iter_mut().skip(1).zip(filtered.windows(2)) {
+            row.event_usd = Some(compute::events_between(&visible, &pair[0].date, &pair[1].date));
+        }
+    }
+    let mut label = match &category_filter {
+        Some(cat) => format!("{} — {}", range, cat.to_uppercase()),
+        None => range.to_string(),
+    };
+    if let Some(latest) = latest {
+        label.push_str(&format!(" — at {} rates", latest.date));
+    }
+    if let Some(needle) = &args.note_contains {
+        label.push_str(&format!(" — notes containing \"{}\"", needle));
+    }
+    let opts = display::HistoryOptions {
+        date_format: args.date_format.as_deref(),
+        annualized: args.annualized,
+        attribution: args.attribution,
+        drawdown: args.drawdown,
+        events: args.events,
+        note_filter: args.note_contains.is_some(),
+        plot: args.plot,
+    };
+    display::print_history(&mut io::stdout(), history_rows, &label, &opts)?;
+    Ok(())
+}
+
+fn handle_since_last(args: &cli::HistoryArgs, portfolio: &model::Portfolio) -> Result<()> {
+    let visible = compute::visible_snapshots(&portfolio.snapshots, args.include_drafts);
+    let Some(delta) = compute::since_last_delta(&visible, portfolio)? else {
+        if args.fail_on_empty {
+            return Err(error::NwError::NoSnapshotsInRange("since-last".to_string()).into());
+        }
+        println!("Need at least two snapshots to compare.");
+        return Ok(());
+    };
+    display::print_since_last(&mut io::stdout(), &delta, args.date_format.as_deref())?;
+    Ok(())
+}
+
+fn handle_total(args: cli::TotalArgs, portfolio: &model::Portfolio, no_color: bool) -> Result<()> {
+    let visible = compute::visible_snapshots(&portfolio.snapshots, args.include_drafts);
+    let Some((latest, earlier)) = visible.split_last() else {
+        println!("No snapshots yet.");
+        return Ok(());
+    };
+    let total = compute::snapshot_total_usd(latest, portfolio)?;
+    if !args.oneline {
+        println!("{}", display::fmt_currency(total));
+        return Ok(());
+    }
+    // No trend without a previous snapshot, or from a zero total
+    let change_pct = match earlier.last() {
+        Some(previous) => {
+            let previous = compute::snapshot_total_usd(previous, portfolio)?;
+            (previous != 0.0).then(|| compute::compute_change(previous, total).1)
+        }
+        None => None,
+    };
+    println!("{}", display::fmt_oneline(total, change_pct, display::color_enabled(no_color)));
+    Ok(())
+}
+
+fn handle_exposure(args: cli::ExposureArgs, portfolio: &model::Portfolio) -> Result<()> {
+    if let Some(pattern) = &args.date_format {
+        display::validate_date_format(pattern)?;
+    }
+    let snapshot = match &args.date {
+        Some(date) => {
+            validate_date(date)?;
+            portfolio
+                .snapshots
+                .iter()
+                .find(|s| &s.date == date)
+                .ok_or_else(|| error::NwError::SnapshotNotFound(date.clone()))?
+        }
+        None => match compute::visible_snapshots(&portfolio.snapshots, false).last() {
+            Some(latest) => latest,
+            None => {
+                println!("No snapshots yet.");
+                return Ok(());
+            }
+        },
+    };
+
+    let (grand_total, rows) = compute::compute_show_rows(snapshot, portfolio, None)?;
+    let totals = compute::compute_currency_totals(&rows);
+    let exposure = compute::compute_allocation(&totals, grand_total);
+    display::print_exposure(
+        &mut io::stdout(),
+        &exposure,
+        &totals,
+        &snapshot.date,
+        args.date_format.as_deref(),
+    )?;
+    Ok(())
+}
+
+fn handle_xirr(args: cli::XirrArgs, portfolio: &model::Portfolio) -> Result<()> {
+    let Some(latest) = compute::visible_snapshots(&portfolio.snapshots, false).pop() else {
+        println!("No snapshots yet.");
+        return Ok(());
+    };
+    validate_date(&latest.date)?;
+
+    // Contributions are money in, so they enter the flow series as negatives
+    let mut flows = args
+        .contributions
+        .iter()
+        .map(|c| returns::parse_flow(c).map(|(date, amount)| (date, -amount)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let latest_date = chrono::NaiveDate::parse_from_str(&latest.date, "%Y-%m-%d")?;
+    flows.push((latest_date, compute::snapshot_total_usd(latest, portfolio)?));
+
+    match returns::xirr(&flows) {
+        Some(rate) => println!("XIRR (as of {}): {:.2}%", latest.date, rate * 100.0),
+        None => println!("XIRR could not be computed for these cash flows."),
+    }
     Ok(())
 }
 
@@ -193,6 +1198,24 @@ fn validate_date(date: &str) -> Result<()> {
         .map_err(|_| error::NwError::InvalidDate(date.to_string()).into())
 }
 
+/// Prompted rates are manual; a USD-only snapshot has no rates to attribute.
+fn rate_source_for(rates: &std::collections::HashMap<String, f64>) -> Option<String> {
+    (!rates.is_empty()).then(|| prompt::MANUAL_RATE_SOURCE.to_string())
+}
+
+/// Asset values from the snapshot preceding `date`, keyed by asset id.
+fn previous_entry_map(
+    portfolio: &model::Portfolio,
+    date: &str,
+) -> Option<std::collections::HashMap<String, f64>> {
+    compute::previous_snapshot(&portfolio.snapshots, date).map(|s| {
+        s.entries
+            .iter()
+            .map(|e| (e.asset_id.clone(), e.value))
+            .collect()
+    })
+}
+
 fn collect_non_usd_currencies(portfolio: &model::Portfolio) -> Vec<String> {
     portfolio.assets
         .iter()
@@ -202,3 +1225,104 @@ fn collect_non_usd_currencies(portfolio: &model::Portfolio) -> Vec<String> {
         .into_iter()
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_watch_once_renders_a_single_frame() {
+        let mut out = Vec::new();
+        let mut renders = 0;
+        watch(&mut out, Duration::from_secs(3600), true, |out| {
+            renders += 1;
+            writeln!(out, "frame")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(renders, 1);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{CLEAR_SCREEN}frame\n"));
+    }
+
+    fn check_exit_code(portfolio: &model::Portfolio) -> (u8, String) {
+        let mut out = Vec::new();
+        let code = match handle_check(&mut out, portfolio) {
+            Ok(()) => 0,
+            Err(e) => e.downcast_ref::<error::NwError>().map_or(1, error::NwError::exit_code),
+        };
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_check_exit_codes() {
+        let good: model::Portfolio = serde_json::from_str(
+            r#"{"assets": [{"id": "sav", "name": "Savings", "category": "bank", "currency": "CHF"}],
+                "snapshots": [
+                  {"date": "2025-01-01", "rates": {"CHF": 0.9},
+                   "entries": [{"asset_id": "sav", "value": 9000.0}]}
+                ]}"#,
+        )
+        .unwrap();
+        assert_eq!(check_exit_code(&good), (0, String::new()));
+
+        let bad: model::Portfolio = serde_json::from_str(
+            r#"{"assets": [{"id": "sav", "name": "Savings", "category": "bank", "currency": "CHF"}],
+                "snapshots": [
+                  {"date": "2025-02-01", "rates": {},
+                   "entries": [{"asset_id": "sav", "value": 1.0}]},
+                  {"date": "2025-01-01", "rates": {"CHF": 0.9},
+                   "entries": [{"asset_id": "gone", "value": 1.0}]}
+                ]}"#,
+        )
+        .unwrap();
+        let (code, output) = check_exit_code(&bad);
+        assert_eq!(code, error::CHECK_FAILED_EXIT_CODE);
+        assert_eq!(output.lines().count(), 3);
+    }
+
+    fn edit_args(flags: &[&str]) -> cli::AssetEditArgs {
+        let args = ["nw", "asset", "edit", "--id", "flat"].iter().chain(flags);
+        match Cli::try_parse_from(args).unwrap().command {
+            Command::Asset(cli::AssetArgs { subcommand: AssetSubcommand::Edit(a) }) => a,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_asset_edit_clears_previously_set_fields() {
+        let mut asset = model::Asset {
+            note: Some("Rented out".to_string()),
+            cost_basis: Some(150_000.0),
+            min_value: Some(100_000.0),
+            max_value: Some(400_000.0),
+            percent_of: model::parse_percent_of("house=50%").unwrap(),
+            sort_order: Some(2),
+            ..model::Asset::new("flat", "Flat", "property", "EUR")
+        };
+        let clear_all = edit_args(&[
+            "--note", "none", "--cost-basis", "none", "--min-value", "none",
+            "--max-value", "none", "--percent-of", "none", "--order", "none",
+        ]);
+        assert!(apply_asset_edit(&mut asset, &clear_all).unwrap());
+        assert!(asset.note.is_none() && asset.cost_basis.is_none());
+        assert!(asset.min_value.is_none() && asset.max_value.is_none());
+        assert!(asset.percent_of.is_none() && asset.sort_order.is_none());
+
+        // Fields without a flag are left alone
+        asset.note = Some("Rented out".to_string());
+        assert!(!apply_asset_edit(&mut asset, &edit_args(&[])).unwrap());
+        assert!(apply_asset_edit(&mut asset, &edit_args(&["--order", "1"])).unwrap());
+        assert_eq!(asset.note.as_deref(), Some("Rented out"));
+        assert_eq!(asset.sort_order, Some(1));
+    }
+
+    #[test]
+    fn test_watch_stops_on_render_error() {
+        let mut out = Vec::new();
+        let result = watch(&mut out, Duration::from_secs(3600), false, |_| {
+            Err(error::NwError::NoSnapshots.into())
+        });
+        assert!(result.is_err());
+    }
+}
@@ -1,43 +1,683 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use crate::error::NwError;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Portfolio {
     pub assets: Vec<Asset>,
     pub snapshots: Vec<Snapshot>,
+    #[serde(default, skip_serializing_if = "Settings::is_default")]
+    pub settings: Settings,
+    /// Other portfolio files (relative to this one) whose assets and snapshots are merged in
+    /// read-only when viewing. Commands that write never see included data.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+}
+
+impl Portfolio {
+    /// Move every asset from currency `from` to `into` and clean up snapshot rates.
+    /// Merging into USD drops the old rate keys; otherwise an old rate is carried
+    /// over to `into` unless the snapshot already has a rate for it.
+    /// Returns the number of assets updated.
+    pub fn rename_currency(&mut self, from: &str, into: &str) -> Result<usize, NwError> {
+        let from = normalize_currency(from);
+        let into = validate_currency(into)?;
+        if from == into {
+            return Err(NwError::InvalidCurrency(into));
+        }
+        if !self.assets.iter().any(|a| a.currency == from) {
+            return Err(NwError::CurrencyNotFound(from));
+        }
+
+        let mut count = 0;
+        for asset in self.assets.iter_mut().filter(|a| a.currency == from) {
+            asset.currency = into.clone();
+            count += 1;
+        }
+        for snapshot in &mut self.snapshots {
+            if let Some(rate) = snapshot.rates.remove(&from) {
+                if into != "USD" {
+                    snapshot.rates.entry(into.clone()).or_insert(rate);
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// The entered asset a `percent_of` asset is ultimately derived from, and the combined
+    /// fraction of it along the chain. An entered asset is its own source at fraction 1.
+    /// Fails on a missing parent, a cycle, or a parent in another currency.
+    pub fn derived_source(&self, id: &str) -> Result<(&Asset, f64), NwError> {
+        let find = |id: &str| {
+            self.assets.iter().find(|a| a.id == id).ok_or_else(|| NwError::AssetNotFound {
+                id: id.to_string(),
+                suggestions: Vec::new(),
+            })
+        };
+        let mut asset = find(id)?;
+        let mut fraction = 1.0;
+        let mut seen = std::collections::HashSet::new();
+        while let Some(percent_of) = &asset.percent_of {
+            if !seen.insert(asset.id.as_str()) {
+                return Err(NwError::DerivedAssetCycle(id.to_string()));
+            }
+            let parent = find(&percent_of.asset_id)?;
+            if parent.currency != asset.currency {
+                return Err(NwError::DerivedCurrencyMismatch {
+                    id: asset.id.clone(),
+                    currency: asset.currency.clone(),
+                    parent: parent.id.clone(),
+                    parent_currency: parent.currency.clone(),
+                });
+            }
+            fraction *= percent_of.fraction;
+            asset = parent;
+        }
+        Ok((asset, fraction))
+    }
+
+    /// Check the chain of every `percent_of` asset (see `derived_source`).
+    pub fn validate_derived(&self) -> Result<(), NwError> {
+        for asset in self.assets.iter().filter(|a| a.percent_of.is_some()) {
+            self.derived_source(&asset.id)?;
+        }
+        Ok(())
+    }
+
+    /// Ids of the assets whose `percent_of` names `id` directly.
+    pub fn derived_from(&self, id: &str) -> Vec<String> {
+        self.assets
+            .iter()
+            .filter(|a| a.percent_of.as_ref().is_some_and(|p| p.asset_id == id))
+            .map(|a| a.id.clone())
+            .collect()
+    }
+
+    /// Move the snapshot dated `from` to `to`, keeping snapshots sorted by date.
+    pub fn rename_snapshot(&mut self, from: &str, to: &str) -> Result<(), NwError> {
+        if self.snapshots.iter().any(|s| s.date == to) {
+            return Err(NwError::SnapshotAlreadyExists(to.to_string()));
+        }
+        let snapshot = self
+            .snapshots
+            .iter_mut()
+            .find(|s| s.date == from)
+            .ok_or_else(|| NwError::SnapshotNotFound(from.to_string()))?;
+        snapshot.date = to.to_string();
+        self.snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(())
+    }
+
+    /// Apply the add/edit casing rules to all stored data: trimmed ids and names, lowercase
+    /// categories, uppercase currency codes and rate keys, snapshots sorted by date.
+    /// Returns a description of each change; fails without changing anything if trimming
+    /// would make two asset ids collide.
+    pub fn normalize_fields(&mut self) -> Result<Vec<String>, NwError> {
+        let mut seen = std::collections::HashSet::new();
+        for asset in &self.assets {
+            if !seen.insert(asset.id.trim()) {
+                return Err(NwError::DuplicateAssetId(asset.id.trim().to_string()));
+            }
+        }
+
+        let mut changes = Vec::new();
+        for asset in &mut self.assets {
+            let id = asset.id.trim().to_string();
+            if id != asset.id {
+                changes.push(format!("asset id '{}' → '{}'", asset.id, id));
+                for snapshot in &mut self.snapshots {
+                    for entry in snapshot.entries.iter_mut().filter(|e| e.asset_id == asset.id) {
+                        entry.asset_id = id.clone();
+                    }
+                }
+                asset.id = id;
+            }
+            let name = asset.name.trim().to_string();
+            if name != asset.name {
+                changes.push(format!("{}: name '{}' → '{}'", asset.id, asset.name, name));
+                asset.name = name;
+            }
+            let category = normalize_category(&asset.category);
+            if category != asset.category {
+                changes.push(format!(
+                    "{}: category '{}' → '{}'",
+                    asset.id, asset.category, category
+                ));
+                asset.category = category;
+            }
+            let currency = normalize_currency(&asset.currency);
+            if currency != asset.currency {
+                changes.push(format!(
+                    "{}: currency '{}' → '{}'",
+                    asset.id, asset.currency, currency
+                ));
+                asset.currency = currency;
+            }
+        }
+
+        for snapshot in &mut self.snapshots {
+            let stray: Vec<String> = snapshot
+                .rates
+                .keys()
+                .filter(|k| normalize_currency(k) != **k)
+                .cloned()
+                .collect();
+            for key in stray {
+                let rate = snapshot.rates.remove(&key).expect("key collected above");
+                let currency = normalize_currency(&key);
+                changes.push(format!("{}: rate '{}' → '{}'", snapshot.date, key, currency));
+                // An already-normalized rate for the same currency wins
+                snapshot.rates.entry(currency).or_insert(rate);
+            }
+        }
+
+        if !self.snapshots.is_sorted_by(|a, b| a.date <= b.date) {
+            self.snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+            changes.push("snapshots sorted by date".to_string());
+        }
+        Ok(changes)
+    }
+
+    /// Merge an included portfolio's assets and snapshots into this one. Snapshots on the
+    /// same date are combined; for rates both define, this portfolio's rate wins.
+    /// Fails if the include defines an asset id that already exists.
+    pub fn merge_include(&mut self, included: Portfolio, source: &str) -> Result<(), NwError> {
+        let existing = |id: &str| self.assets.iter().any(|a| a.id == id);
+        if let Some(dup) = included.assets.iter().find(|a| existing(&a.id)) {
+            return Err(NwError::IncludeConflict { id: dup.id.clone(), path: source.to_string() });
+        }
+        self.assets.extend(included.assets);
+
+        for snapshot in included.snapshots {
+            match self.snapshots.iter_mut().find(|s| s.date == snapshot.date) {
+                Some(own) => {
+                    own.entries.extend(snapshot.entries);
+                    for (currency, rate) in snapshot.rates {
+                        own.rates.entry(currency).or_insert(rate);
+                    }
+                    // The cached total only covers this file's own entries
+                    own.cached_total_usd = None;
+                }
+                None => self.snapshots.push(snapshot),
+            }
+        }
+        self.snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(())
+    }
+}
+
+/// Categories are stored lowercase.
+pub fn normalize_category(category: &str) -> String {
+    category.trim().to_lowercase()
+}
+
+/// Currency codes are stored uppercase.
+pub fn normalize_currency(currency: &str) -> String {
+    currency.trim().to_uppercase()
+}
+
+/// Crypto tickers accepted despite not being three letters long.
+const LONG_CRYPTO_TICKERS: &[&str] =
+    &["ALGO", "ATOM", "AVAX", "DOGE", "LINK", "MATIC", "SHIB", "USDC", "USDT"];
+
+/// Normalize a currency code and reject anything that isn't three ASCII letters
+/// (ISO 4217 style) or a known longer crypto ticker.
+pub fn validate_currency(currency: &str) -> Result<String, NwError> {
+    let code = normalize_currency(currency);
+    let letters = code.chars().all(|c| c.is_ascii_alphabetic());
+    if letters && (code.len() == 3 || LONG_CRYPTO_TICKERS.contains(&code.as_str())) {
+        Ok(code)
+    } else {
+        Err(NwError::InvalidCurrency(currency.to_string()))
+    }
+}
+
+/// User preferences, stored alongside the data so they travel with it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Settings {
+    /// Decimal places per currency code, overriding the built-in defaults.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub currency_decimals: BTreeMap<String, u32>,
+    /// Removals touching fewer snapshots than this skip the confirmation prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_threshold: Option<usize>,
+    /// Header color per category in `show`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub category_colors: BTreeMap<String, CategoryColor>,
+    /// USD target per category; `show` reports progress toward each.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub category_goals: BTreeMap<String, f64>,
+    /// Month (1–12) the fiscal year starts in, for YTD ranges. Defaults to January.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiscal_year_start_month: Option<u32>,
+    /// Snapshot `show` picks when no date or index is given. Defaults to the latest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_show: Option<DefaultShow>,
+    /// New snapshots closer than this many days to an existing one need confirmation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_snapshot_interval_days: Option<u32>,
+    /// How to combine a manually entered rate with a fetched one. Defaults to prefer-manual.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_policy: Option<RatePolicy>,
+    /// Significant figures kept when storing exchange rates. Unset keeps rates as entered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_sig_figs: Option<u32>,
+}
+
+/// Currencies with no meaningful fractional unit.
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &["AMD", "CLP", "ISK", "JPY", "KRW", "VND"];
+
+/// Upper bound for configured decimal places.
+pub const MAX_DECIMALS: u32 = 8;
+
+impl Settings {
+    pub fn is_default(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    /// Decimal places used to enter and render native values in `currency`.
+    /// A hand-edited value above `MAX_DECIMALS` is clamped to it.
+    pub fn decimals_for(&self, currency: &str) -> u32 {
+        match self.currency_decimals.get(currency) {
+            Some(d) => (*d).min(MAX_DECIMALS),
+            None if ZERO_DECIMAL_CURRENCIES.contains(&currency) => 0,
+            None => 2,
+        }
+    }
+
+    /// Whether removing something referenced by `affected` snapshots should ask first.
+    /// Without a threshold every removal asks.
+    pub fn removal_needs_confirmation(&self, affected: usize) -> bool {
+        self.confirm_threshold.is_none_or(|t| affected >= t)
+    }
+
+    /// First month of the fiscal year (1 = January).
+    pub fn fiscal_start_month(&self) -> u32 {
+        self.fiscal_year_start_month.unwrap_or(1)
+    }
+
+    /// Configured header color for a category, if any.
+    pub fn color_for_category(&self, category: &str) -> Option<CategoryColor> {
+        self.category_colors.get(&category.to_lowercase()).copied()
+    }
+}
+
+/// Whether a flag value is `none`, the keyword that clears an optional asset field.
+fn is_none_keyword(s: &str) -> bool {
+    s.trim().eq_ignore_ascii_case("none")
+}
+
+/// Parse a `--note` argument: the trimmed text, or no note for `none` or a blank value.
+pub fn parse_note(s: &str) -> Option<String> {
+    let note = s.trim();
+    (!note.is_empty() && !is_none_keyword(note)).then(|| note.to_string())
+}
+
+/// Parse a `--cost-basis` argument: a non-negative USD amount, or `none` to clear it.
+pub fn parse_cost_basis(s: &str) -> Result<Option<f64>, NwError> {
+    if is_none_keyword(s) {
+        return Ok(None);
+    }
+    match s.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(Some(v)),
+        _ => Err(NwError::InvalidCostBasis(s.to_string())),
+    }
+}
+
+/// Parse a `--min-value`/`--max-value` argument: a non-negative native amount, or `none`
+/// to clear the bound.
+pub fn parse_value_bound(s: &str) -> Result<Option<f64>, NwError> {
+    if is_none_keyword(s) {
+        return Ok(None);
+    }
+    match s.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(Some(v)),
+        _ => Err(NwError::InvalidValueBound(s.to_string())),
+    }
+}
+
+/// Which rate wins when a manual entry and a fetched rate disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum RatePolicy {
+    #[default]
+    PreferManual,
+    PreferApi,
+    /// Mean of both rates.
+    Average,
+}
+
+impl FromStr for RatePolicy {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "prefer-manual" => Ok(RatePolicy::PreferManual),
+            "prefer-api" => Ok(RatePolicy::PreferApi),
+            "average" => Ok(RatePolicy::Average),
+            _ => Err(NwError::InvalidRatePolicy(s.to_string())),
+        }
+    }
+}
+
+/// Which snapshot `nw show` displays by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum DefaultShow {
+    /// The most recent snapshot.
+    Latest,
+    /// The most recent snapshot with a value for every asset.
+    LatestComplete,
+}
+
+impl FromStr for DefaultShow {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "latest" => Ok(DefaultShow::Latest),
+            "latest-complete" => Ok(DefaultShow::LatestComplete),
+            _ => Err(NwError::InvalidDefaultShow(s.to_string())),
+        }
+    }
+}
+
+/// Terminal colors that can be assigned to categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum CategoryColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+impl FromStr for CategoryColor {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "red" => Ok(CategoryColor::Red),
+            "green" => Ok(CategoryColor::Green),
+            "yellow" => Ok(CategoryColor::Yellow),
+            "blue" => Ok(CategoryColor::Blue),
+            "magenta" => Ok(CategoryColor::Magenta),
+            "cyan" => Ok(CategoryColor::Cyan),
+            _ => Err(NwError::InvalidColor(s.to_string())),
+        }
+    }
+}
+
+/// Parse a `CATEGORY=COLOR` assignment. The color `none` clears the assignment.
+pub fn parse_category_color(s: &str) -> Result<(String, Option<CategoryColor>), NwError> {
+    let (category, color) = s
+        .split_once('=')
+        .ok_or_else(|| NwError::InvalidColor(s.to_string()))?;
+    let category = category.trim().to_lowercase();
+    if category.is_empty() {
+        return Err(NwError::InvalidColor(s.to_string()));
+    }
+    let color = match color.trim() {
+        c if is_none_keyword(c) => None,
+        c => Some(CategoryColor::from_str(c)?),
+    };
+    Ok((category, color))
+}
+
+/// Parse a `CATEGORY=AMOUNT` goal in USD. The amount `none` clears the goal.
+pub fn parse_category_goal(s: &str) -> Result<(String, Option<f64>), NwError> {
+    let invalid = || NwError::InvalidGoal(s.to_string());
+    let (category, amount) = s.split_once('=').ok_or_else(invalid)?;
+    let category = normalize_category(category);
+    if category.is_empty() {
+        return Err(invalid());
+    }
+    let amount = match amount.trim() {
+        a if is_none_keyword(a) => None,
+        a => match a.parse::<f64>() {
+            Ok(v) if v > 0.0 && v.is_finite() => Some(v),
+            _ => return Err(invalid()),
+        },
+    };
+    Ok((category, amount))
+}
+
+/// Parse `PARENT=PERCENT` (e.g. `house=40%`) for `asset edit --percent-of`, or `none`.
+pub fn parse_percent_of(s: &str) -> Result<Option<PercentOf>, NwError> {
+    if is_none_keyword(s) {
+        return Ok(None);
+    }
+    let invalid = || NwError::InvalidPercentOf(s.to_string());
+    let (parent, percent) = s.split_once('=').ok_or_else(invalid)?;
+    let parent = parent.trim();
+    let percent = percent.trim().trim_end_matches('%').trim().parse::<f64>();
+    match percent {
+        Ok(p) if !parent.is_empty() && p > 0.0 && p <= 100.0 => {
+            Ok(Some(PercentOf { asset_id: parent.to_string(), fraction: p / 100.0 }))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse an asset's display position for `asset edit --order`, or `none` to unset it.
+pub fn parse_sort_order(s: &str) -> Result<Option<i64>, NwError> {
+    match s.trim() {
+        o if is_none_keyword(o) => Ok(None),
+        o => o.parse().map(Some).map_err(|_| NwError::InvalidAssetOrder(s.to_string())),
+    }
+}
+
+/// An asset whose value is a fixed fraction of another asset's entry instead of entered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PercentOf {
+    pub asset_id: String,
+    /// Share of the parent's native value, from 0 (exclusive) to 1.
+    pub fraction: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Asset {
     pub id: String,
     pub name: String,
     pub category: String,
     pub currency: String,
+    /// Free-form description shown by `asset show`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Purchase cost in USD; `show` reports unrealized gain against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_basis: Option<f64>,
+    /// Lowest plausible native value; entering less asks for confirmation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<f64>,
+    /// Highest plausible native value; entering more asks for confirmation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    /// Derive the value from another asset's entry; such assets are never entered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent_of: Option<PercentOf>,
+    /// Position within its category in `show`, lowest first; unordered assets follow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Asset {
+    /// An asset with no note, cost basis, value bounds, parent, or display order.
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        category: impl Into<String>,
+        currency: impl Into<String>,
+    ) -> Self {
+        Asset {
+            id: id.into(),
+            name: name.into(),
+            category: category.into(),
+            currency: currency.into(),
+            note: None,
+            cost_basis: None,
+            min_value: None,
+            max_value: None,
+            percent_of: None,
+            sort_order: None,
+        }
+    }
+
+    /// Whether `value` falls outside the asset's plausible range, if it has one.
+    pub fn outside_bounds(&self, value: f64) -> bool {
+        self.min_value.is_some_and(|min| value < min)
+            || self.max_value.is_some_and(|max| value > max)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Snapshot {
     pub date: String,
     pub rates: HashMap<String, f64>,
     pub entries: Vec<SnapshotEntry>,
+    /// Total USD value computed at save time; compared on load to detect drift.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_total_usd: Option<f64>,
+    /// Where the rates came from, e.g. "manual".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_source: Option<String>,
+    /// Preliminary numbers, left out of `show` and `history` until finalized.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    /// Events since the previous snapshot that explain part of the change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<SnapshotEvent>,
+    /// Free-form remark, e.g. "after year-end bonus"; `history --note-contains` filters on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Closed-out data: edit, rename, and remove refuse to change it without `--force`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Snapshot {
+    /// An empty snapshot for `date`; chain `with_rate`/`with_entry` to fill it in.
+    pub fn new(date: impl Into<String>) -> Self {
+        Snapshot { date: date.into(), ..Default::default() }
+    }
+
+    /// Fails for a locked snapshot unless `force` is set.
+    pub fn ensure_unlocked(&self, force: bool) -> Result<(), NwError> {
+        if self.locked && !force {
+            return Err(NwError::SnapshotLocked(self.date.clone()));
+        }
+        Ok(())
+    }
+
+    /// Add a "1 USD = `rate` `currency`" exchange rate.
+    // Only fixtures chain these so far
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn with_rate(mut self, currency: impl Into<String>, rate: f64) -> Self {
+        self.rates.insert(currency.into(), rate);
+        self
+    }
+
+    /// Add a single-value entry for `asset_id`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn with_entry(mut self, asset_id: impl Into<String>, value: f64) -> Self {
+        self.entries.push(SnapshotEntry { asset_id: asset_id.into(), value, lots: Vec::new() });
+        self
+    }
+}
+
+/// Something that moved net worth other than market or balance changes, e.g. a deposit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SnapshotEvent {
+    /// Free-form lowercase label such as "deposit", "withdrawal", or "gift".
+    pub kind: String,
+    /// USD effect on net worth: positive for money in, negative for money out.
+    pub amount: f64,
+}
+
+impl FromStr for SnapshotEvent {
+    type Err = NwError;
+
+    /// Parse `KIND:AMOUNT`, e.g. "deposit:5000" or "withdrawal:-2000".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NwError::InvalidEvent(s.to_string());
+        let (kind, amount) = s.split_once(':').ok_or_else(invalid)?;
+        let kind = kind.trim().to_lowercase();
+        let amount: f64 = amount.trim().parse().map_err(|_| invalid())?;
+        if kind.is_empty() || !amount.is_finite() {
+            return Err(invalid());
+        }
+        Ok(SnapshotEvent { kind, amount })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SnapshotEntry {
     pub asset_id: String,
     pub value: f64,
+    /// Optional tax lots; when present, `value` must equal their sum.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lots: Vec<Lot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Lot {
+    /// Acquisition date (YYYY-MM-DD).
+    pub date: String,
+    pub value: f64,
+}
+
+impl SnapshotEntry {
+    /// Native value of the entry: the lot sum if lots are recorded, else `value`.
+    /// Errors if recorded lots do not add up to `value`.
+    pub fn native_value(&self) -> Result<f64, NwError> {
+        if self.lots.is_empty() {
+            return Ok(self.value);
+        }
+        let sum: f64 = self.lots.iter().map(|l| l.value).sum();
+        if (sum - self.value).abs() > 1e-6 {
+            return Err(NwError::LotSumMismatch {
+                asset_id: self.asset_id.clone(),
+                sum,
+                value: self.value,
+            });
+        }
+        Ok(sum)
+    }
 }
 
 // View models — never serialized
 
 pub struct ShowRow {
+    pub asset_id: String,
     pub asset_name: String,
     pub currency: String,
     pub native_value: f64,
+    pub native_decimals: u32,
     pub usd_value: f64,
     pub category: String,
+    /// USD change versus a comparison snapshot (`show --compare`).
+    pub change_usd: Option<f64>,
+    /// `change_usd` as a percentage of the comparison value; `None` when that value is
+    /// zero (the holding is new since then).
+    pub change_pct: Option<f64>,
+    /// USD value minus the asset's cost basis, when one is recorded.
+    pub gain_usd: Option<f64>,
+    /// The asset's `sort_order`, for ordering rows within a category.
+    pub sort_order: Option<i64>,
 }
 
 pub struct HistoryRow {
@@ -45,6 +685,86 @@ pub struct HistoryRow {
     pub total_usd: f64,
     pub change_usd: Option<f64>,
     pub change_pct: Option<f64>,
+    /// Period change annualized over the days since the previous row.
+    pub annualized_pct: Option<f64>,
+    pub attribution: Option<Attribution>,
+    /// USD change explained by the snapshot's events (`history --events`).
+    pub event_usd: Option<f64>,
+}
+
+/// Progress of one category toward its `category_goals` target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub category: String,
+    pub current_usd: f64,
+    pub goal_usd: f64,
+    /// Share of the goal reached; can exceed 100.
+    pub pct: f64,
+}
+
+/// Summary of the totals in a history range. Ties go to the earliest date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryStats {
+    /// (date, total) of the lowest total.
+    pub min: (String, f64),
+    /// (date, total) of the highest total.
+    pub max: (String, f64),
+    pub average: f64,
+}
+
+/// Change between the two most recent snapshots (`history --since-last`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDelta {
+    /// (date, total) of the earlier snapshot.
+    pub from: (String, f64),
+    /// (date, total) of the latest snapshot.
+    pub to: (String, f64),
+    pub change_usd: f64,
+    pub change_pct: f64,
+    /// Per-category breakdown in alphabetical order, including categories held in only
+    /// one of the two snapshots.
+    pub categories: Vec<CategoryDelta>,
+    /// USD change from each currency's rate move (see `compute::fx_impact`).
+    pub fx_impact: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryDelta {
+    pub category: String,
+    pub from_usd: f64,
+    pub to_usd: f64,
+    pub change_usd: f64,
+    pub change_pct: f64,
+}
+
+/// Largest peak-to-trough decline in a history range (`history --drawdown`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drawdown {
+    /// (date, total) of the peak the decline started from.
+    pub peak: (String, f64),
+    /// (date, total) of the lowest total before a new peak.
+    pub trough: (String, f64),
+    /// Decline in USD, zero or negative.
+    pub change_usd: f64,
+    /// Decline as a percentage of the peak, zero or negative.
+    pub change_pct: f64,
+}
+
+/// A total change split into holdings-driven and exchange-rate-driven parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attribution {
+    pub value_usd: f64,
+    pub fx_usd: f64,
+}
+
+/// A currency whose rate moved by an implausible factor between consecutive snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateJump {
+    pub currency: String,
+    pub previous_date: String,
+    pub previous_rate: f64,
+    pub date: String,
+    pub rate: f64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,6 +773,8 @@ pub enum HistoryRange {
     SixMonths,
     OneYear,
     FiveYears,
+    /// Since the start of the current fiscal year (see `Settings::fiscal_year_start_month`).
+    YearToDate,
     All,
 }
 
@@ -65,6 +787,7 @@ impl FromStr for HistoryRange {
             "6M" => Ok(HistoryRange::SixMonths),
             "1Y" => Ok(HistoryRange::OneYear),
             "5Y" => Ok(HistoryRange::FiveYears),
+            "YTD" => Ok(HistoryRange::YearToDate),
             "ALL" => Ok(HistoryRange::All),
             _ => Err(NwError::InvalidHistoryRange(s.to_string())),
         }
@@ -78,7 +801,523 @@ impl std::fmt::Display for HistoryRange {
             HistoryRange::SixMonths => write!(f, "6M"),
             HistoryRange::OneYear => write!(f, "1Y"),
             HistoryRange::FiveYears => write!(f, "5Y"),
+            HistoryRange::YearToDate => write!(f, "YTD"),
             HistoryRange::All => write!(f, "ALL"),
         }
     }
 }
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    AllocationCsv,
+    /// Self-contained page with the latest snapshot, allocation, and history.
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allocation-csv" => Ok(ExportFormat::AllocationCsv),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(NwError::InvalidExportFormat(s.to_string())),
+        }
+    }
+}
+
+/// Dimension the `show` allocation block groups holdings by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllocationBy {
+    #[default]
+    Category,
+    Currency,
+}
+
+impl FromStr for AllocationBy {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "category" => Ok(AllocationBy::Category),
+            "currency" => Ok(AllocationBy::Currency),
+            _ => Err(NwError::InvalidAllocationBy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(NwError::InvalidOutputFormat(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortOrder {
+    type Err = NwError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(NwError::InvalidSortOrder(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimals_for_defaults() {
+        let settings = Settings::default();
+        assert_eq!(settings.decimals_for("USD"), 2);
+        assert_eq!(settings.decimals_for("JPY"), 0);
+        assert_eq!(settings.decimals_for("AMD"), 0);
+    }
+
+    #[test]
+    fn test_decimals_for_override() {
+        let mut settings = Settings::default();
+        settings.currency_decimals.insert("BTC".to_string(), 8);
+        settings.currency_decimals.insert("JPY".to_string(), 2);
+        assert_eq!(settings.decimals_for("BTC"), 8);
+        assert_eq!(settings.decimals_for("JPY"), 2);
+    }
+
+    #[test]
+    fn test_decimals_for_clamps_hand_edited_values() {
+        let mut settings = Settings::default();
+        settings.currency_decimals.insert("BTC".to_string(), 40);
+        assert_eq!(settings.decimals_for("BTC"), MAX_DECIMALS);
+        assert_eq!(crate::display::fmt_amount(1.5, settings.decimals_for("BTC")), "1.50000000");
+    }
+
+    #[test]
+    fn test_removal_needs_confirmation() {
+        let mut settings = Settings::default();
+        assert!(settings.removal_needs_confirmation(0));
+
+        settings.confirm_threshold = Some(3);
+        assert!(!settings.removal_needs_confirmation(0));
+        assert!(!settings.removal_needs_confirmation(2));
+        assert!(settings.removal_needs_confirmation(3));
+        assert!(settings.removal_needs_confirmation(10));
+    }
+
+    #[test]
+    fn test_category_color_round_trip() {
+        let (category, color) = parse_category_color("Crypto=magenta").unwrap();
+        assert_eq!(category, "crypto");
+        let mut settings = Settings::default();
+        settings.category_colors.insert(category, color.unwrap());
+
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains(r#""crypto":"magenta""#));
+        let back: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.color_for_category("CRYPTO"), Some(CategoryColor::Magenta));
+        assert_eq!(back.color_for_category("etf"), None);
+    }
+
+    #[test]
+    fn test_builders() {
+        let asset = Asset::new("vti", "VTI", "etf", "USD");
+        assert_eq!((asset.id.as_str(), asset.currency.as_str()), ("vti", "USD"));
+        assert!(asset.note.is_none() && asset.cost_basis.is_none());
+
+        let snapshot = Snapshot::new("2025-01-01")
+            .with_rate("EUR", 0.9)
+            .with_entry("vti", 100.0)
+            .with_entry("cash", 50.0);
+        assert_eq!(snapshot.date, "2025-01-01");
+        assert_eq!(snapshot.rates["EUR"], 0.9);
+        assert_eq!(snapshot.entries.len(), 2);
+        let cash = &snapshot.entries[1];
+        assert_eq!((cash.asset_id.as_str(), cash.value), ("cash", 50.0));
+        assert!(!snapshot.draft && snapshot.events.is_empty());
+    }
+
+    #[test]
+    fn test_locked_snapshot_needs_force() {
+        let mut snapshot = Snapshot::new("2024-12-31");
+        assert!(snapshot.ensure_unlocked(false).is_ok());
+        snapshot.locked = true;
+        assert!(matches!(
+            snapshot.ensure_unlocked(false),
+            Err(NwError::SnapshotLocked(date)) if date == "2024-12-31"
+        ));
+        assert!(snapshot.ensure_unlocked(true).is_ok());
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains(r#""locked":true"#));
+        snapshot.locked = false;
+        assert!(!serde_json::to_string(&snapshot).unwrap().contains("locked"));
+    }
+
+    #[test]
+    fn test_parse_sort_order() {
+        assert_eq!(parse_sort_order(" 3 ").unwrap(), Some(3));
+        assert_eq!(parse_sort_order("-1").unwrap(), Some(-1));
+        assert_eq!(parse_sort_order("None").unwrap(), None);
+        assert!(matches!(parse_sort_order("first"), Err(NwError::InvalidAssetOrder(_))));
+    }
+
+    #[test]
+    fn test_parse_percent_of() {
+        let percent_of = parse_percent_of("house=40%").unwrap().unwrap();
+        assert_eq!(percent_of, PercentOf { asset_id: "house".to_string(), fraction: 0.4 });
+        assert_eq!(parse_percent_of(" house = 25 ").unwrap().unwrap().fraction, 0.25);
+        assert!(parse_percent_of("none").unwrap().is_none());
+        for bad in ["house", "=40%", "house=0", "house=150%", "house=half"] {
+            assert!(matches!(parse_percent_of(bad), Err(NwError::InvalidPercentOf(_))));
+        }
+    }
+
+    #[test]
+    fn test_derived_source_rejects_cycles_and_missing_parents() {
+        let derived = |id: &str, parent: &str, fraction: f64| Asset {
+            percent_of: Some(PercentOf { asset_id: parent.to_string(), fraction }),
+            ..Asset::new(id, id, "property", "USD")
+        };
+        let mut portfolio = Portfolio {
+            assets: vec![
+                Asset::new("house", "House", "property", "USD"),
+                derived("equity", "house", 0.5),
+                derived("share", "equity", 0.4),
+            ],
+            ..Default::default()
+        };
+        let (source, fraction) = portfolio.derived_source("share").unwrap();
+        assert_eq!(source.id, "house");
+        assert!((fraction - 0.2).abs() < 1e-9);
+
+        portfolio.assets[0] = derived("house", "share", 0.5);
+        assert!(matches!(
+            portfolio.derived_source("equity"),
+            Err(NwError::DerivedAssetCycle(id)) if id == "equity"
+        ));
+
+        portfolio.assets[0] = derived("house", "land", 0.5);
+        assert!(matches!(
+            portfolio.derived_source("share"),
+            Err(NwError::AssetNotFound { id, .. }) if id == "land"
+        ));
+
+        portfolio.assets[0] = Asset::new("house", "House", "property", "EUR");
+        assert!(matches!(
+            portfolio.derived_source("equity"),
+            Err(NwError::DerivedCurrencyMismatch { .. })
+        ));
+        // The parent's own chain is fine; only its children's are broken
+        assert!(portfolio.derived_source("house").is_ok());
+        assert!(matches!(
+            portfolio.validate_derived(),
+            Err(NwError::DerivedCurrencyMismatch { id, .. }) if id == "equity"
+        ));
+
+        assert_eq!(portfolio.derived_from("house"), vec!["equity"]);
+        assert_eq!(portfolio.derived_from("equity"), vec!["share"]);
+        assert!(portfolio.derived_from("share").is_empty());
+    }
+
+    #[test]
+    fn test_parse_category_goal() {
+        let (category, goal) = parse_category_goal(" Crypto =20000").unwrap();
+        assert_eq!((category.as_str(), goal), ("crypto", Some(20000.0)));
+        assert_eq!(parse_category_goal("etf=None").unwrap(), ("etf".to_string(), None));
+        for bad in ["crypto", "=100", "crypto=0", "crypto=-5", "crypto=lots"] {
+            assert!(matches!(parse_category_goal(bad), Err(NwError::InvalidGoal(_))));
+        }
+    }
+
+    #[test]
+    fn test_parse_category_color_rejects_bad_input() {
+        assert_eq!(parse_category_color("etf=none").unwrap(), ("etf".to_string(), None));
+        assert!(matches!(parse_category_color("etf"), Err(NwError::InvalidColor(_))));
+        assert!(matches!(parse_category_color("etf=pink"), Err(NwError::InvalidColor(_))));
+        assert!(parse_category_color("=red").is_err());
+    }
+
+    #[test]
+    fn test_rename_currency_into_usd_drops_rates() {
+        let mut portfolio = Portfolio {
+            assets: vec![
+                Asset::new("a", "a", "crypto", "USDT"),
+                Asset::new("b", "b", "crypto", "USD"),
+                Asset::new("c", "c", "crypto", "EUR"),
+            ],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_rate("USDT", 1.001).with_rate("EUR", 0.92),
+            ],
+            ..Default::default()
+        };
+        let count = portfolio.rename_currency("usdt", "USD").unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(portfolio.assets[0].currency, "USD");
+        assert_eq!(portfolio.assets[2].currency, "EUR");
+        assert!(!portfolio.snapshots[0].rates.contains_key("USDT"));
+        assert!(!portfolio.snapshots[0].rates.contains_key("USD"));
+        assert_eq!(portfolio.snapshots[0].rates["EUR"], 0.92);
+    }
+
+    #[test]
+    fn test_rename_currency_carries_rate_over() {
+        let mut portfolio = Portfolio {
+            assets: vec![Asset::new("a", "a", "crypto", "EURO")],
+            snapshots: vec![
+                Snapshot::new("2025-01-01").with_rate("EURO", 0.9),
+                Snapshot::new("2025-02-01").with_rate("EURO", 0.9).with_rate("EUR", 0.92),
+            ],
+            ..Default::default()
+        };
+        portfolio.rename_currency("EURO", "EUR").unwrap();
+        assert_eq!(portfolio.snapshots[0].rates["EUR"], 0.9);
+        // An existing rate for the target wins
+        assert_eq!(portfolio.snapshots[1].rates["EUR"], 0.92);
+        assert!(!portfolio.snapshots[1].rates.contains_key("EURO"));
+    }
+
+    #[test]
+    fn test_rename_snapshot_resorts() {
+        let mut portfolio = Portfolio {
+            snapshots: vec![
+                Snapshot::new("2025-01-31").with_rate("EUR", 0.9),
+                Snapshot::new("2025-02-28").with_rate("EUR", 0.92),
+                Snapshot::new("2025-03-31").with_rate("EUR", 0.94),
+            ],
+            ..Default::default()
+        };
+        portfolio.rename_snapshot("2025-02-28", "2025-01-15").unwrap();
+        let dates: Vec<&str> = portfolio.snapshots.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-15", "2025-01-31", "2025-03-31"]);
+        assert_eq!(portfolio.snapshots[0].rates["EUR"], 0.92);
+    }
+
+    #[test]
+    fn test_rename_snapshot_rejects_missing_or_taken_dates() {
+        let mut portfolio = Portfolio {
+            snapshots: vec![
+                Snapshot::new("2025-01-31"),
+                Snapshot::new("2025-02-28"),
+            ],
+            ..Default::default()
+        };
+        let taken = portfolio.rename_snapshot("2025-02-28", "2025-01-31");
+        assert!(matches!(taken, Err(NwError::SnapshotAlreadyExists(_))));
+        let missing = portfolio.rename_snapshot("2025-02-27", "2025-02-26");
+        assert!(matches!(missing, Err(NwError::SnapshotNotFound(_))));
+        assert_eq!(portfolio.snapshots[1].date, "2025-02-28");
+    }
+
+    #[test]
+    fn test_rename_currency_validates() {
+        let mut portfolio = Portfolio {
+            assets: vec![Asset::new("a", "a", "crypto", "USDT")],
+            ..Default::default()
+        };
+        let not_found = portfolio.rename_currency("BTC", "USD");
+        assert!(matches!(not_found, Err(NwError::CurrencyNotFound(_))));
+        let junk = portfolio.rename_currency("USDT", "U$D");
+        assert!(matches!(junk, Err(NwError::InvalidCurrency(_))));
+        let same = portfolio.rename_currency("USDT", "usdt");
+        assert!(matches!(same, Err(NwError::InvalidCurrency(_))));
+    }
+
+    #[test]
+    fn test_normalize_fields_fixes_case_and_whitespace() {
+        let mut portfolio = Portfolio {
+            assets: vec![
+                Asset::new(" vti ", "VTI ", "ETF", "usd"),
+                Asset::new("cash", "Cash", " Bank", "eur"),
+            ],
+            snapshots: vec![
+                Snapshot {
+                    entries: vec![SnapshotEntry {
+                        asset_id: " vti ".to_string(),
+                        value: 1.0,
+                        ..Default::default()
+                    }],
+                    ..Snapshot::new("2025-02-01").with_rate("eur", 0.9)
+                },
+                Snapshot::new("2025-01-01").with_rate("EUR", 0.92),
+            ],
+            ..Default::default()
+        };
+        let changes = portfolio.normalize_fields().unwrap();
+
+        assert_eq!(portfolio.assets[0].id, "vti");
+        assert_eq!(portfolio.assets[0].name, "VTI");
+        assert_eq!(portfolio.assets[0].category, "etf");
+        assert_eq!(portfolio.assets[0].currency, "USD");
+        assert_eq!(portfolio.assets[1].category, "bank");
+        assert_eq!(portfolio.assets[1].currency, "EUR");
+        assert_eq!(portfolio.snapshots[0].date, "2025-01-01");
+        assert_eq!(portfolio.snapshots[1].entries[0].asset_id, "vti");
+        assert_eq!(portfolio.snapshots[1].rates["EUR"], 0.9);
+        assert!(changes.iter().any(|c| c == "cash: currency 'eur' → 'EUR'"));
+        assert!(changes.iter().any(|c| c == "snapshots sorted by date"));
+
+        // Already normalized: nothing to report
+        assert!(portfolio.normalize_fields().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_fields_rejects_colliding_ids() {
+        let mut portfolio = Portfolio {
+            assets: vec![
+                Asset::new("vti", "vti", "crypto", "USD"),
+                Asset::new("vti ", "vti ", "crypto", "USD"),
+            ],
+            ..Default::default()
+        };
+        let result = portfolio.normalize_fields();
+        assert!(matches!(result, Err(NwError::DuplicateAssetId(_))));
+        assert_eq!(portfolio.assets[1].id, "vti ");
+    }
+
+    #[test]
+    fn test_native_value_sums_lots() {
+        let entry = SnapshotEntry {
+            asset_id: "vti".to_string(),
+            value: 1500.0,
+            lots: vec![
+                Lot { date: "2023-01-10".to_string(), value: 1000.0 },
+                Lot { date: "2024-06-01".to_string(), value: 500.0 },
+            ],
+        };
+        assert_eq!(entry.native_value().unwrap(), 1500.0);
+
+        let json = serde_json::to_string(&entry).unwrap();
+        let back: SnapshotEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.lots.len(), 2);
+    }
+
+    #[test]
+    fn test_native_value_rejects_lot_mismatch() {
+        let entry = SnapshotEntry {
+            asset_id: "vti".to_string(),
+            value: 2000.0,
+            lots: vec![Lot { date: "2023-01-10".to_string(), value: 1000.0 }],
+        };
+        assert!(matches!(entry.native_value(), Err(NwError::LotSumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_rate_source_round_trip() {
+        let snapshot = Snapshot {
+            date: "2025-02-28".to_string(),
+            rate_source: Some("manual".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let back: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.rate_source.as_deref(), Some("manual"));
+
+        // Older files without the field still load
+        let old: Snapshot =
+            serde_json::from_str(r#"{"date":"2025-01-01","rates":{},"entries":[]}"#).unwrap();
+        assert!(old.rate_source.is_none());
+    }
+
+    #[test]
+    fn test_parse_snapshot_event() {
+        let event = SnapshotEvent::from_str("Deposit:5000").unwrap();
+        assert_eq!(event, SnapshotEvent { kind: "deposit".to_string(), amount: 5000.0 });
+        assert_eq!(SnapshotEvent::from_str("withdrawal: -2000.5").unwrap().amount, -2000.5);
+        for bad in ["deposit", ":5000", "deposit:lots", "deposit:inf"] {
+            assert!(matches!(SnapshotEvent::from_str(bad), Err(NwError::InvalidEvent(_))));
+        }
+    }
+
+    #[test]
+    fn test_snapshot_events_round_trip() {
+        let snapshot = Snapshot {
+            date: "2025-02-28".to_string(),
+            events: vec![SnapshotEvent { kind: "deposit".to_string(), amount: 5000.0 }],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains(r#""events":[{"kind":"deposit","amount":5000.0}]"#));
+        let back: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.events, snapshot.events);
+
+        // No events, no key
+        let plain = Snapshot { events: vec![], ..snapshot };
+        assert!(!serde_json::to_string(&plain).unwrap().contains("events"));
+    }
+
+    #[test]
+    fn test_validate_currency() {
+        assert_eq!(validate_currency(" chf ").unwrap(), "CHF");
+        assert_eq!(validate_currency("usdt").unwrap(), "USDT");
+        for junk in ["u$d", "US1", "US", "EURO", "", "€"] {
+            assert!(
+                matches!(validate_currency(junk), Err(NwError::InvalidCurrency(_))),
+                "{junk:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cost_basis() {
+        assert_eq!(parse_cost_basis("1250.50").unwrap(), Some(1250.5));
+        assert_eq!(parse_cost_basis("None").unwrap(), None);
+        for junk in ["-5", "abc", "NaN", ""] {
+            assert!(matches!(parse_cost_basis(junk), Err(NwError::InvalidCostBasis(_))));
+        }
+    }
+
+    #[test]
+    fn test_parse_note() {
+        assert_eq!(parse_note("  Joint account \n").as_deref(), Some("Joint account"));
+        assert_eq!(parse_note("none"), None);
+        assert_eq!(parse_note(" NONE "), None);
+        assert_eq!(parse_note("   "), None);
+        assert_eq!(parse_note("none of it").as_deref(), Some("none of it"));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_schema_describes_portfolio() {
+        let schema = serde_json::to_value(schemars::schema_for!(Portfolio)).unwrap();
+        let properties = &schema["properties"];
+        assert!(properties.get("assets").is_some());
+        assert!(properties.get("snapshots").is_some());
+    }
+
+    #[test]
+    fn test_asset_note_round_trip() {
+        let asset = Asset {
+            note: Some("Joint account, statements by mail".to_string()),
+            ..Asset::new("sav", "sav", "crypto", "CHF")
+        };
+        let json = serde_json::to_string(&asset).unwrap();
+        let back: Asset = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.note, asset.note);
+
+        // No note: the field is omitted, and older files without it still load
+        let plain = serde_json::to_string(&Asset::new("vti", "vti", "crypto", "USD")).unwrap();
+        assert!(!plain.contains("note"));
+        let old: Asset = serde_json::from_str(&plain).unwrap();
+        assert!(old.note.is_none());
+    }
+}
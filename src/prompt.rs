@@ -1,21 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, BufRead, Write};
+use crate::compute;
 use crate::error::NwError;
-use crate::model::Asset;
+use crate::model::{self, Asset, Lot, Settings, SnapshotEntry};
+
+/// Recorded as a snapshot's `rate_source` when rates were typed in.
+pub const MANUAL_RATE_SOURCE: &str = "manual";
 
 /// Prompt for exchange rates for each non-USD currency.
 /// `existing_rates` pre-fills values when editing (shown in brackets).
+/// With `sig_figs`, each rate is rounded to that many significant figures before it's kept.
+/// With `decimal_comma`, typed rates use a comma as the decimal mark (see `normalize_number`).
 pub fn prompt_rates(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
     currencies: &[String],
     existing_rates: Option<&HashMap<String, f64>>,
+    sig_figs: Option<u32>,
+    decimal_comma: bool,
 ) -> Result<HashMap<String, f64>, NwError> {
+    let round = |v: f64| sig_figs.map_or(v, |figures| compute::round_sig_figs(v, figures));
     let mut rates = HashMap::new();
 
     if currencies.is_empty() {
         return Ok(rates);
     }
 
-    println!("--- Exchange Rates ---");
+    say(output, "--- Exchange Rates ---")?;
     for currency in currencies {
         if currency == "USD" {
             return Err(NwError::UsdRateRejected);
@@ -28,26 +39,25 @@ pub fn prompt_rates(
         };
 
         loop {
-            let input = read_line(&prompt)?;
-            let trimmed = input.trim();
+            let line = read_line(input, output, &prompt)?;
+            let trimmed = line.trim();
 
             if trimmed.is_empty() {
                 if let Some(v) = existing {
-                    rates.insert(currency.clone(), *v);
+                    rates.insert(currency.clone(), round(*v));
                     break;
                 } else {
-                    println!("  Rate is required.");
+                    say(output, "  Rate is required.")?;
                     continue;
                 }
             }
 
-            match trimmed.parse::<f64>() {
-                Ok(v) if v > 0.0 => {
-                    rates.insert(currency.clone(), v);
+            match parse_rate(trimmed, &rates, decimal_comma) {
+                Ok(v) => {
+                    rates.insert(currency.clone(), round(v));
                     break;
                 }
-                Ok(_) => println!("  Rate must be a positive number."),
-                Err(_) => println!("  Invalid number. Please try again."),
+                Err(msg) => say(output, &format!("  {}", msg))?,
             }
         }
     }
@@ -55,56 +65,105 @@ pub fn prompt_rates(
     Ok(rates)
 }
 
+/// Parse a typed rate, returning the message to show on rejection.
+/// An `i` prefix such as "i1.08" gives the inverse quote ("1 foreign = 1.08 USD"), which
+/// is flipped to the stored "1 USD = N foreign" form.
+/// A pivot suffix such as "0.85 EUR" means "1 EUR = 0.85 of this currency" and is
+/// converted to a USD rate through the EUR rate already entered in `rates`.
+fn parse_rate(
+    input: &str,
+    rates: &HashMap<String, f64>,
+    decimal_comma: bool,
+) -> Result<f64, &'static str> {
+    let (number, pivot) = match input.split_once(char::is_whitespace) {
+        Some((number, pivot)) => (number, Some(pivot.trim().to_uppercase())),
+        None => (input, None),
+    };
+    let (number, inverse) = match number.strip_prefix(['i', 'I']) {
+        Some(rest) => (rest, true),
+        None => (number, false),
+    };
+    let value = match normalize_number(number, decimal_comma)?.parse::<f64>() {
+        Ok(v) if v > 0.0 => v,
+        Ok(_) => return Err("Rate must be a positive number."),
+        Err(_) => return Err("Invalid number. Please try again."),
+    };
+    let value = if inverse { 1.0 / value } else { value };
+    if !value.is_finite() {
+        return Err("Rate is out of range.");
+    }
+    match pivot.as_deref() {
+        None | Some("USD") => Ok(value),
+        Some(code) => rates
+            .get(code)
+            .map(|pivot_rate| value * pivot_rate)
+            .ok_or("Pivot currency has no rate yet; enter its rate first."),
+    }
+}
+
 /// Prompt for asset values. Press Enter to omit an asset.
 /// `existing_entries` pre-fills values when editing.
+/// `previous_entries` (values from the preceding snapshot) are shown as a hint.
+/// With `with_lots`, each asset first asks for dated lots whose sum becomes its value.
+/// With `decimal_comma`, typed values use a comma as the decimal mark.
+#[allow(clippy::too_many_arguments)]
 pub fn prompt_asset_values(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
     assets: &[Asset],
     existing_entries: Option<&HashMap<String, f64>>,
-) -> Result<Vec<(String, f64)>, NwError> {
+    previous_entries: Option<&HashMap<String, f64>>,
+    settings: &Settings,
+    with_lots: bool,
+    decimal_comma: bool,
+) -> Result<Vec<SnapshotEntry>, NwError> {
     let mut entries = Vec::new();
 
     if assets.is_empty() {
         return Ok(entries);
     }
 
-    println!("--- Asset Values (press Enter to omit) ---");
-    for asset in assets {
+    say(output, "--- Asset Values (press Enter to omit) ---")?;
+    // Assets with a percent-of parent are derived from its value, not entered
+    for asset in assets.iter().filter(|a| a.percent_of.is_none()) {
         let existing = existing_entries.and_then(|m| m.get(&asset.id));
-        let prompt = match existing {
-            Some(v) => format!(
-                "{} ({}, {}) [{}]: ",
-                asset.name,
-                asset.category.to_uppercase(),
-                asset.currency,
-                v
-            ),
-            None => format!(
-                "{} ({}, {}): ",
-                asset.name,
-                asset.category.to_uppercase(),
-                asset.currency
-            ),
-        };
+        let previous = previous_entries.and_then(|m| m.get(&asset.id));
+        let prompt = asset_prompt(asset, existing, previous);
+        let decimals = settings.decimals_for(&asset.currency);
+        let baseline = existing.or(previous).copied();
+
+        if with_lots {
+            let lots = prompt_lots(input, output, asset, decimals, decimal_comma)?;
+            if !lots.is_empty() {
+                let value = lots.iter().map(|l| l.value).sum();
+                entries.push(SnapshotEntry { asset_id: asset.id.clone(), value, lots });
+                continue;
+            }
+            // no lots → fall back to a single value
+        }
 
         loop {
-            let input = read_line(&prompt)?;
-            let trimmed = input.trim();
+            let line = read_line(input, output, &prompt)?;
+            let trimmed = line.trim();
 
             if trimmed.is_empty() {
                 if let Some(v) = existing {
-                    entries.push((asset.id.clone(), *v));
+                    entries.push(single_entry(asset, *v));
                 }
                 // no existing → omit asset
                 break;
             }
 
-            match trimmed.parse::<f64>() {
-                Ok(v) if v >= 0.0 => {
-                    entries.push((asset.id.clone(), v));
+            match parse_asset_value(trimmed, decimals, baseline, decimal_comma) {
+                Ok(v) => {
+                    // Advisory only: declining just asks for the value again
+                    if asset.outside_bounds(v) && !confirm_out_of_bounds(input, output, asset, v)? {
+                        continue;
+                    }
+                    entries.push(single_entry(asset, v));
                     break;
                 }
-                Ok(_) => println!("  Value must be non-negative."),
-                Err(_) => println!("  Invalid number. Please try again."),
+                Err(msg) => say(output, &format!("  {}", msg))?,
             }
         }
     }
@@ -112,23 +171,696 @@ pub fn prompt_asset_values(
     Ok(entries)
 }
 
-/// Ask a yes/no confirmation question. Defaults to No.
-pub fn confirm(message: &str) -> bool {
-    let input = read_line(message).unwrap_or_default();
-    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+/// Fields of a new asset given as flags; `prompt_new_asset` asks for the missing ones.
+#[derive(Debug, Default)]
+pub struct NewAssetFields {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub currency: Option<String>,
+}
+
+/// Ask for each field of a new asset not already in `fields`.
+/// The ID must not be taken by one of `assets`; category and currency suggest the most
+/// common existing value, which Enter accepts. Given fields are used as-is.
+pub fn prompt_new_asset(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    assets: &[Asset],
+    fields: NewAssetFields,
+) -> Result<Asset, NwError> {
+    // Flag values get the same trimming as typed answers; a blank flag asks instead
+    let id = match fields.id.map(|id| id.trim().to_string()).filter(|id| !id.is_empty()) {
+        Some(id) => id,
+        None => loop {
+            let line = read_line(input, output, "ID: ")?;
+            let id = line.trim();
+            if id.is_empty() {
+                say(output, "  ID is required.")?;
+            } else if assets.iter().any(|a| a.id == id) {
+                say(output, &format!("  An asset with ID '{}' already exists.", id))?;
+            } else {
+                break id.to_string();
+            }
+        },
+    };
+    let name = match fields.name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()) {
+        Some(name) => name,
+        None => loop {
+            let line = read_line(input, output, "Name: ")?;
+            match line.trim() {
+                "" => say(output, "  Name is required.")?,
+                name => break name.to_string(),
+            }
+        },
+    };
+    let category = match fields.category {
+        Some(category) => model::normalize_category(&category),
+        None => {
+            let known = known_values(assets, |a| &a.category);
+            let answer = prompt_with_suggestion(input, output, "Category", &known)?;
+            model::normalize_category(&answer)
+        }
+    };
+    let currency = match fields.currency {
+        Some(currency) => model::validate_currency(&currency)?,
+        None => {
+            let known = known_values(assets, |a| &a.currency);
+            loop {
+                let answer = prompt_with_suggestion(input, output, "Currency", &known)?;
+                match model::validate_currency(&answer) {
+                    Ok(code) => break code,
+                    Err(e) => say(output, &format!("  {}", e))?,
+                }
+            }
+        }
+    };
+    Ok(Asset::new(id, name, category, currency))
+}
+
+/// Distinct values of `field` across `assets`, most common first (ties alphabetical).
+fn known_values(assets: &[Asset], field: impl Fn(&Asset) -> &String) -> Vec<String> {
+    let mut counts: BTreeMap<&String, usize> = BTreeMap::new();
+    for asset in assets {
+        *counts.entry(field(asset)).or_default() += 1;
+    }
+    let mut values: Vec<(&String, usize)> = counts.into_iter().collect();
+    values.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    values.into_iter().map(|(v, _)| v.clone()).collect()
+}
+
+/// Ask for a required value, listing `known` values and accepting the first on Enter.
+fn prompt_with_suggestion(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    label: &str,
+    known: &[String],
+) -> Result<String, NwError> {
+    let prompt = match known.first() {
+        Some(suggested) => format!("{} ({}) [{}]: ", label, known.join(", "), suggested),
+        None => format!("{}: ", label),
+    };
+    loop {
+        let line = read_line(input, output, &prompt)?;
+        match (line.trim(), known.first()) {
+            ("", Some(suggested)) => return Ok(suggested.clone()),
+            ("", None) => say(output, &format!("  {} is required.", label))?,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Warn that `value` is outside the asset's plausible range and ask whether to keep it.
+fn confirm_out_of_bounds(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    asset: &Asset,
+    value: f64,
+) -> Result<bool, NwError> {
+    let limit = match (asset.min_value, asset.max_value) {
+        (Some(min), _) if value < min => format!("below the usual minimum of {}", min),
+        (_, Some(max)) => format!("above the usual maximum of {}", max),
+        _ => "outside the usual range".to_string(),
+    };
+    say(output, &format!("  {} {} is {}.", value, asset.currency, limit))?;
+    Ok(confirm_with_default(input, output, "  Keep it?", false))
+}
+
+fn single_entry(asset: &Asset, value: f64) -> SnapshotEntry {
+    SnapshotEntry { asset_id: asset.id.clone(), value, lots: vec![] }
+}
+
+/// Prompt for dated lots of one asset until a blank date is entered.
+fn prompt_lots(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    asset: &Asset,
+    decimals: u32,
+    decimal_comma: bool,
+) -> Result<Vec<Lot>, NwError> {
+    say(output, &format!("{} lots (press Enter at the date to finish):", asset.name))?;
+    let mut lots = Vec::new();
+    loop {
+        let n = lots.len() + 1;
+        let date = read_line(input, output, &format!("  Lot {} date (YYYY-MM-DD): ", n))?;
+        let date = date.trim();
+        if date.is_empty() {
+            return Ok(lots);
+        }
+        if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            say(output, "  Invalid date. Please use YYYY-MM-DD.")?;
+            continue;
+        }
+        loop {
+            let prompt = format!("  Lot {} value ({}): ", n, asset.currency);
+            let line = read_line(input, output, &prompt)?;
+            match parse_asset_value(line.trim(), decimals, None, decimal_comma) {
+                Ok(value) => {
+                    lots.push(Lot { date: date.to_string(), value });
+                    break;
+                }
+                Err(msg) => say(output, &format!("  {}", msg))?,
+            }
+        }
+    }
+}
+
+/// Parse a typed asset value, returning the message to show on rejection.
+/// A leading `+` or `-` applies a delta to `baseline` (the existing or previous value).
+/// Currencies configured with zero decimals only accept whole numbers.
+fn parse_asset_value(
+    input: &str,
+    decimals: u32,
+    baseline: Option<f64>,
+    decimal_comma: bool,
+) -> Result<f64, &'static str> {
+    let number = normalize_number(input, decimal_comma).and_then(|n| {
+        n.parse::<f64>().map_err(|_| "Invalid number. Please try again.")
+    });
+    let value = if input.starts_with(['+', '-']) {
+        let base = baseline.ok_or("No previous value to adjust; enter the full value.")?;
+        base + number?
+    } else {
+        number?
+    };
+
+    if value < 0.0 {
+        Err("Value must be non-negative.")
+    } else if decimals == 0 && value.fract() != 0.0 {
+        Err("This currency has no fractional unit; enter a whole number.")
+    } else {
+        Ok(value)
+    }
 }
 
-fn read_line(prompt: &str) -> Result<String, NwError> {
-    print!("{}", prompt);
-    io::stdout().flush().map_err(|e| NwError::WriteFile {
-        path: "stdout".to_string(),
+/// Rewrite a typed number into the form `f64::from_str` accepts, returning the message to
+/// show when its separators don't make sense. The decimal mark is `.` by default and `,`
+/// with `decimal_comma`; the other character is a thousands separator, allowed only between
+/// groups of three digits before the decimal mark. So "1,234.56" (or "1.234,56" with the
+/// flag) is 1234.56, while "1,5" is rejected rather than guessed at. Grouped numbers that
+/// could just as well be a decimal in the other style ("0,125", or "1,234" with a single
+/// group and no decimal mark) are rejected as ambiguous so a wrong guess can't scale a value
+/// by a thousand.
+fn normalize_number(input: &str, decimal_comma: bool) -> Result<String, &'static str> {
+    const INVALID: &str = "Invalid number. Please try again.";
+    let (separator, mark) = if decimal_comma { ('.', ',') } else { (',', '.') };
+    let (whole, fraction) = match input.split_once(mark) {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (input, None),
+    };
+    if fraction.is_some_and(|f| f.contains([separator, mark])) {
+        return Err(INVALID);
+    }
+    let digits = whole.trim_start_matches(['+', '-']);
+    if digits.contains(separator) {
+        let groups: Vec<&str> = digits.split(separator).collect();
+        let first = groups[0];
+        if first.is_empty() || first.len() > 3 || groups[1..].iter().any(|g| g.len() != 3) {
+            return Err(INVALID);
+        }
+        if first.starts_with('0') || (groups.len() == 2 && fraction.is_none()) {
+            return Err(if decimal_comma {
+                "Ambiguous number: use a comma for decimals, or leave out the dots."
+            } else {
+                "Ambiguous number: use a dot for decimals, or leave out the commas."
+            });
+        }
+    }
+    let whole = whole.replace(separator, "");
+    Ok(match fraction {
+        Some(fraction) => format!("{whole}.{fraction}"),
+        None => whole,
+    })
+}
+
+/// Build the value prompt for one asset, e.g. "Bitcoin (CRYPTO, BTC) — last 0.5 BTC [0.6]: ".
+fn asset_prompt(asset: &Asset, existing: Option<&f64>, previous: Option<&f64>) -> String {
+    let mut prompt = format!(
+        "{} ({}, {})",
+        asset.name,
+        asset.category.to_uppercase(),
+        asset.currency
+    );
+    if let Some(v) = previous {
+        prompt.push_str(&format!(" — last {} {}", v, asset.currency));
+    }
+    if let Some(v) = existing {
+        prompt.push_str(&format!(" [{}]", v));
+    }
+    prompt.push_str(": ");
+    prompt
+}
+
+/// Read the passphrase for an encrypted portfolio without echoing it.
+#[cfg(feature = "encryption")]
+pub fn prompt_passphrase() -> Result<String, NwError> {
+    rpassword::prompt_password("Passphrase: ").map_err(|e| NwError::ReadFile {
+        path: "stdin".to_string(),
         source: e,
-    })?;
-    let stdin = io::stdin();
+    })
+}
+
+/// Ask for a new passphrase twice until both entries match and are non-empty. Retry
+/// messages go to `output`; the passphrases themselves are read from the terminal.
+#[cfg(feature = "encryption")]
+pub fn prompt_new_passphrase(output: &mut impl Write) -> Result<String, NwError> {
+    loop {
+        let first = prompt_passphrase()?;
+        if first.is_empty() {
+            say(output, "  Passphrase must not be empty.")?;
+            continue;
+        }
+        let second = rpassword::prompt_password("Repeat passphrase: ").map_err(|e| {
+            NwError::ReadFile { path: "stdin".to_string(), source: e }
+        })?;
+        if first == second {
+            return Ok(first);
+        }
+        say(output, "  Passphrases do not match.")?;
+    }
+}
+
+/// Ask a yes/no question, appending the "(y/N)" or "(Y/n)" hint for `default`.
+/// An empty answer returns `default`; closed input counts as No.
+pub fn confirm_with_default(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    message: &str,
+    default: bool,
+) -> bool {
+    let hint = if default { "(Y/n)" } else { "(y/N)" };
+    let Ok(line) = read_line(input, output, &format!("{} {} ", message, hint)) else {
+        return false;
+    };
+    match line.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => matches!(answer, "y" | "yes"),
+    }
+}
+
+/// Print `prompt` and read one line.
+/// End of input is an error so retry loops cannot spin on a closed stdin.
+fn read_line(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+) -> Result<String, NwError> {
+    write!(output, "{}", prompt)
+        .and_then(|_| output.flush())
+        .map_err(stdout_error)?;
     let mut line = String::new();
-    stdin.lock().read_line(&mut line).map_err(|e| NwError::ReadFile {
+    let read = input.read_line(&mut line).and_then(|n| match n {
+        0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of input")),
+        n => Ok(n),
+    });
+    read.map_err(|e| NwError::ReadFile {
         path: "stdin".to_string(),
         source: e,
     })?;
     Ok(line)
-}
\ No newline at end of file
+}
+
+/// Print one line of prompt output (headers and retry messages).
+fn say(output: &mut impl Write, message: &str) -> Result<(), NwError> {
+    writeln!(output, "{}", message).map_err(stdout_error)
+}
+
+fn stdout_error(e: io::Error) -> NwError {
+    NwError::WriteFile { path: "stdout".to_string(), source: e }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---- parse_asset_value ----
+
+    #[test]
+    fn test_parse_asset_value_rejects_fractional_jpy() {
+        let decimals = Settings::default().decimals_for("JPY");
+        assert!(parse_asset_value("1500.5", decimals, None, false).is_err());
+        assert_eq!(parse_asset_value("1500", decimals, None, false), Ok(1500.0));
+    }
+
+    #[test]
+    fn test_parse_asset_value_allows_fractional_usd() {
+        let decimals = Settings::default().decimals_for("USD");
+        assert_eq!(parse_asset_value("12.34", decimals, None, false), Ok(12.34));
+        assert!(parse_asset_value("abc", decimals, None, false).is_err());
+    }
+
+    #[test]
+    fn test_parse_asset_value_thousands_separators() {
+        assert_eq!(parse_asset_value("1,234.56", 2, None, false), Ok(1234.56));
+        assert_eq!(parse_asset_value("1,234.0", 2, None, false), Ok(1234.0));
+        assert_eq!(parse_asset_value("12,345,678", 2, None, false), Ok(12_345_678.0));
+        assert!(parse_asset_value("1,5", 2, None, false).is_err());
+        assert!(parse_asset_value("1234,56", 2, None, false).is_err());
+        assert!(parse_asset_value("1.234,56", 2, None, false).is_err());
+    }
+
+    #[test]
+    fn test_parse_asset_value_decimal_comma() {
+        assert_eq!(parse_asset_value("1234,56", 2, None, true), Ok(1234.56));
+        assert_eq!(parse_asset_value("1.234,56", 2, None, true), Ok(1234.56));
+        assert_eq!(parse_asset_value("1.234.567", 2, None, true), Ok(1_234_567.0));
+        assert_eq!(parse_asset_value("0,125", 8, None, true), Ok(0.125));
+        assert_eq!(parse_asset_value("+200,5", 2, Some(1000.0), true), Ok(1200.5));
+        // With the flag a dot is only ever a thousands separator
+        assert!(parse_asset_value("12.34", 2, None, true).is_err());
+        assert!(parse_asset_value("1,234.56", 2, None, true).is_err());
+        assert!(parse_asset_value("1,2,3", 2, None, true).is_err());
+        assert!(parse_asset_value("1500,5", 0, None, true).is_err());
+    }
+
+    #[test]
+    fn test_parse_asset_value_rejects_ambiguous_grouping() {
+        // Each could be a small decimal in the other style; never read them as thousands
+        assert_eq!(parse_asset_value("0.125", 8, None, false), Ok(0.125));
+        assert_eq!(parse_asset_value("0,125", 8, None, true), Ok(0.125));
+        assert!(parse_asset_value("+1,234", 8, Some(1.0), false).is_err());
+        assert!(parse_asset_value("012,345.5", 8, None, false).is_err());
+        for input in ["0,125", "1,234"] {
+            let err = parse_asset_value(input, 8, None, false).unwrap_err();
+            assert!(err.starts_with("Ambiguous number"), "{input}");
+        }
+        for input in ["0.125", "1.234"] {
+            let err = parse_asset_value(input, 8, None, true).unwrap_err();
+            assert!(err.starts_with("Ambiguous number"), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_asset_value_positive_delta() {
+        assert_eq!(parse_asset_value("+200", 2, Some(1000.0), false), Ok(1200.0));
+    }
+
+    #[test]
+    fn test_parse_asset_value_negative_delta() {
+        assert_eq!(parse_asset_value("-250.5", 2, Some(1000.0), false), Ok(749.5));
+        // A delta below zero is still rejected
+        assert!(parse_asset_value("-1500", 2, Some(1000.0), false).is_err());
+    }
+
+    #[test]
+    fn test_parse_asset_value_delta_without_baseline() {
+        assert!(parse_asset_value("+200", 2, None, false).is_err());
+        assert!(parse_asset_value("-1", 2, None, false).is_err());
+    }
+
+    // ---- scripted input ----
+
+    fn run_asset_values(
+        script: &str,
+        assets: &[Asset],
+        existing: Option<&HashMap<String, f64>>,
+    ) -> (Vec<SnapshotEntry>, String) {
+        let mut input = script.as_bytes();
+        let mut output = Vec::new();
+        let entries = prompt_asset_values(
+            &mut input,
+            &mut output,
+            assets,
+            existing,
+            None,
+            &Settings::default(),
+            false,
+            false,
+        )
+        .unwrap();
+        (entries, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_prompt_asset_values_retries_invalid_input() {
+        let btc = Asset::new("btc", "Bitcoin", "crypto", "BTC");
+        let (entries, output) = run_asset_values("abc\n-5\n0.75\n", &[btc], None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].value, 0.75);
+        assert!(output.contains("Invalid number. Please try again."));
+        assert!(output.contains("No previous value to adjust"));
+    }
+
+    #[test]
+    fn test_prompt_asset_values_enter_omits_or_keeps_existing() {
+        let assets = [
+            Asset::new("btc", "Bitcoin", "crypto", "BTC"),
+            Asset::new("eth", "Ether", "crypto", "BTC"),
+        ];
+        let (entries, _) = run_asset_values("\n\n", &assets, None);
+        assert!(entries.is_empty());
+
+        let existing = HashMap::from([("eth".to_string(), 2.0)]);
+        let (entries, _) = run_asset_values("\n\n", &assets, Some(&existing));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].asset_id, "eth");
+        assert_eq!(entries[0].value, 2.0);
+    }
+
+    #[test]
+    fn test_prompt_asset_values_accepts_value_in_bounds() {
+        let asset = Asset {
+            min_value: Some(0.1),
+            max_value: Some(2.0),
+            ..Asset::new("btc", "Bitcoin", "crypto", "BTC")
+        };
+        let (entries, output) = run_asset_values("1.5\n", &[asset], None);
+        assert_eq!(entries[0].value, 1.5);
+        assert!(!output.contains("Keep it?"));
+    }
+
+    #[test]
+    fn test_prompt_asset_values_confirms_value_out_of_bounds() {
+        let asset = Asset {
+            min_value: Some(0.1),
+            max_value: Some(2.0),
+            ..Asset::new("btc", "Bitcoin", "crypto", "BTC")
+        };
+        // Declining re-asks; confirming keeps the out-of-range value
+        let assets = std::slice::from_ref(&asset);
+        let (entries, output) = run_asset_values("15\n\n15\ny\n", assets, None);
+        assert_eq!(entries[0].value, 15.0);
+        assert_eq!(output.matches("15 BTC is above the usual maximum of 2.").count(), 2);
+        assert!(output.contains("Keep it? (y/N) "));
+
+        let (entries, output) = run_asset_values("0.01\nn\n0.5\n", &[asset], None);
+        assert_eq!(entries[0].value, 0.5);
+        assert!(output.contains("0.01 BTC is below the usual minimum of 0.1."));
+    }
+
+    #[test]
+    fn test_prompt_rates_requires_positive_rate() {
+        let mut input = "\n-1\n0.92\n".as_bytes();
+        let mut output = Vec::new();
+        let currencies = ["EUR".to_string()];
+        let rates =
+            prompt_rates(&mut input, &mut output, &currencies, None, None, false).unwrap();
+        assert_eq!(rates["EUR"], 0.92);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Rate is required."));
+        assert!(output.contains("Rate must be a positive number."));
+    }
+
+    #[test]
+    fn test_parse_rate_cross_rate_through_pivot() {
+        let rates = HashMap::from([("EUR".to_string(), 0.9)]);
+        // 1 USD = 0.9 EUR and 1 EUR = 0.85 GBP, so 1 USD = 0.765 GBP
+        let gbp = parse_rate("0.85 EUR", &rates, false).unwrap();
+        assert!((gbp - 0.765).abs() < 1e-12);
+        assert_eq!(parse_rate("0.85 eur", &rates, false), Ok(gbp));
+        assert_eq!(parse_rate("0.79 USD", &rates, false), Ok(0.79));
+        assert_eq!(parse_rate("0.79", &rates, false), Ok(0.79));
+    }
+
+    #[test]
+    fn test_parse_rate_rejects_unknown_pivot_and_bad_numbers() {
+        let rates = HashMap::from([("EUR".to_string(), 0.9)]);
+        assert!(parse_rate("0.85 CHF", &rates, false).is_err());
+        assert!(parse_rate("-0.85 EUR", &rates, false).is_err());
+        assert!(parse_rate("abc EUR", &rates, false).is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_inverse_quote() {
+        let rates = HashMap::new();
+        // 1 EUR = 1.25 USD is stored as 1 USD = 0.8 EUR
+        assert_eq!(parse_rate("i1.25", &rates, false), Ok(0.8));
+        assert_eq!(parse_rate("I0.5", &rates, false), Ok(2.0));
+        assert_eq!(parse_rate("i-1.25", &rates, false), Err("Rate must be a positive number."));
+        assert_eq!(parse_rate("i0", &rates, false), Err("Rate must be a positive number."));
+        assert_eq!(parse_rate("i1e-320", &rates, false), Err("Rate is out of range."));
+        assert!(parse_rate("i", &rates, false).is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_decimal_comma() {
+        let rates = HashMap::from([("EUR".to_string(), 0.9)]);
+        assert_eq!(parse_rate("0,92", &rates, true), Ok(0.92));
+        assert_eq!(parse_rate("i1,25", &rates, true), Ok(0.8));
+        assert_eq!(parse_rate("1.234,5", &rates, true), Ok(1234.5));
+        assert!(parse_rate("0.92", &rates, true).is_err());
+        assert!(parse_rate("0,92", &rates, false).is_err());
+    }
+
+    #[test]
+    fn test_prompt_rates_stores_inverted_rate() {
+        let mut input = "i-2\ni1.25\n".as_bytes();
+        let mut output = Vec::new();
+        let currencies = ["EUR".to_string()];
+        let rates =
+            prompt_rates(&mut input, &mut output, &currencies, None, None, false).unwrap();
+        assert_eq!(rates["EUR"], 0.8);
+        assert!(String::from_utf8(output).unwrap().contains("Rate must be a positive number."));
+    }
+
+    #[test]
+    fn test_prompt_rates_accepts_cross_rate() {
+        let mut input = "0.9\n0.85 CHF\n0.85 EUR\n".as_bytes();
+        let mut output = Vec::new();
+        let currencies = ["EUR".to_string(), "GBP".to_string()];
+        let rates =
+            prompt_rates(&mut input, &mut output, &currencies, None, None, false).unwrap();
+        assert!((rates["GBP"] - 0.765).abs() < 1e-12);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Pivot currency has no rate yet"));
+    }
+
+    #[test]
+    fn test_prompt_rates_rounds_to_sig_figs() {
+        let mut input = "0.9\n0.85 EUR\n".as_bytes();
+        let mut output = Vec::new();
+        let currencies = ["EUR".to_string(), "GBP".to_string()];
+        let existing = HashMap::from([("EUR".to_string(), 0.923_456_7)]);
+        let existing = Some(&existing);
+        let rates =
+            prompt_rates(&mut input, &mut output, &currencies, existing, Some(3), false).unwrap();
+        assert_eq!(rates["EUR"], 0.9);
+        assert_eq!(rates["GBP"], 0.765);
+
+        let mut output = Vec::new();
+        let mut input = "\n".as_bytes();
+        let rates =
+            prompt_rates(&mut input, &mut output, &currencies[..1], existing, Some(4), false)
+                .unwrap();
+        assert_eq!(rates["EUR"], 0.9235);
+    }
+
+    fn run_new_asset(script: &str, fields: NewAssetFields) -> (Asset, String) {
+        let assets = vec![
+            Asset::new("btc", "Bitcoin", "crypto", "BTC"),
+            Asset::new("vti", "VTI", "etf", "USD"),
+            Asset::new("voo", "VOO", "etf", "USD"),
+        ];
+        let mut input = script.as_bytes();
+        let mut output = Vec::new();
+        let asset = prompt_new_asset(&mut input, &mut output, &assets, fields).unwrap();
+        (asset, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_prompt_new_asset_wizard() {
+        let script = "\nvti\neth\nEther\nCrypto\nETH\n";
+        let (asset, output) = run_new_asset(script, NewAssetFields::default());
+        assert_eq!(asset.id, "eth");
+        assert_eq!(asset.name, "Ether");
+        assert_eq!(asset.category, "crypto");
+        assert_eq!(asset.currency, "ETH");
+        assert!(output.contains("ID is required."));
+        assert!(output.contains("An asset with ID 'vti' already exists."));
+        // Most common existing values are listed first and suggested
+        assert!(output.contains("Category (etf, crypto) [etf]: "));
+        assert!(output.contains("Currency (USD, BTC) [USD]: "));
+    }
+
+    #[test]
+    fn test_prompt_new_asset_enter_accepts_suggestion_and_retries_currency() {
+        let (asset, output) = run_new_asset("spy\nSPY\n\nusdollar\n\n", NewAssetFields::default());
+        assert_eq!(asset.category, "etf");
+        assert_eq!(asset.currency, "USD");
+        assert!(output.contains("invalid currency"));
+    }
+
+    #[test]
+    fn test_prompt_new_asset_flags_skip_prompts() {
+        let fields = NewAssetFields {
+            id: Some("gold".to_string()),
+            category: Some("Metals".to_string()),
+            ..Default::default()
+        };
+        let (asset, output) = run_new_asset("Gold bar\nxau\n", fields);
+        assert_eq!((asset.id.as_str(), asset.name.as_str()), ("gold", "Gold bar"));
+        assert_eq!((asset.category.as_str(), asset.currency.as_str()), ("metals", "XAU"));
+        assert!(!output.contains("ID: "));
+        assert!(!output.contains("Category"));
+    }
+
+    #[test]
+    fn test_prompt_new_asset_trims_flag_values() {
+        let fields = NewAssetFields {
+            id: Some("  gold ".to_string()),
+            name: Some(" Gold bar\t".to_string()),
+            ..Default::default()
+        };
+        let (asset, _) = run_new_asset("metals\nxau\n", fields);
+        assert_eq!((asset.id.as_str(), asset.name.as_str()), ("gold", "Gold bar"));
+
+        let blank = NewAssetFields { id: Some("  ".to_string()), ..Default::default() };
+        let (asset, output) = run_new_asset("gold\nGold\nmetals\nxau\n", blank);
+        assert_eq!(asset.id, "gold");
+        assert!(output.contains("ID: "));
+    }
+
+    #[test]
+    fn test_prompt_new_asset_without_existing_assets_requires_values() {
+        let mut input = "a\nA\n\nbank\nchf\n".as_bytes();
+        let mut output = Vec::new();
+        let fields = NewAssetFields::default();
+        let asset = prompt_new_asset(&mut input, &mut output, &[], fields).unwrap();
+        assert_eq!((asset.category.as_str(), asset.currency.as_str()), ("bank", "CHF"));
+        assert!(String::from_utf8(output).unwrap().contains("Category is required."));
+    }
+
+    #[test]
+    fn test_confirm_defaults_to_no() {
+        let mut output = Vec::new();
+        assert!(confirm_with_default(&mut "y\n".as_bytes(), &mut output, "Sure?", false));
+        assert!(!confirm_with_default(&mut "\n".as_bytes(), &mut output, "Sure?", false));
+        assert!(!confirm_with_default(&mut "".as_bytes(), &mut output, "Sure?", false));
+        assert_eq!(String::from_utf8(output).unwrap(), "Sure? (y/N) ".repeat(3));
+    }
+
+    #[test]
+    fn test_confirm_with_default_yes() {
+        let mut output = Vec::new();
+        assert!(confirm_with_default(&mut "\n".as_bytes(), &mut output, "Sure?", true));
+        assert!(!confirm_with_default(&mut "n\n".as_bytes(), &mut output, "Sure?", true));
+        assert!(confirm_with_default(&mut " YES \n".as_bytes(), &mut output, "Sure?", true));
+        // Closed input never confirms
+        assert!(!confirm_with_default(&mut "".as_bytes(), &mut output, "Sure?", true));
+        assert!(String::from_utf8(output).unwrap().starts_with("Sure? (Y/n) "));
+    }
+
+    #[test]
+    fn test_prompt_rates_fails_at_end_of_input() {
+        let mut output = Vec::new();
+        let currencies = ["EUR".to_string()];
+        let mut input = "\n".as_bytes();
+        let result = prompt_rates(&mut input, &mut output, &currencies, None, None, false);
+        assert!(matches!(result, Err(NwError::ReadFile { .. })));
+    }
+
+    // ---- asset_prompt ----
+
+    #[test]
+    fn test_asset_prompt_plain() {
+        let btc = Asset::new("btc", "Bitcoin", "crypto", "BTC");
+        assert_eq!(asset_prompt(&btc, None, None), "Bitcoin (CRYPTO, BTC): ");
+    }
+
+    #[test]
+    fn test_asset_prompt_includes_previous_hint() {
+        let btc = Asset::new("btc", "Bitcoin", "crypto", "BTC");
+        let prompt = asset_prompt(&btc, Some(&0.6), Some(&0.5));
+        assert_eq!(prompt, "Bitcoin (CRYPTO, BTC) — last 0.5 BTC [0.6]: ");
+    }
+}
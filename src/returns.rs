@@ -0,0 +1,97 @@
+use chrono::NaiveDate;
+use crate::error::NwError;
+
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-9;
+
+/// Solve for the annualized internal rate of return of irregular dated cash flows
+/// (money-weighted return) using Newton's method.
+/// Money going in is negative, money coming out (or the final value) is positive.
+/// Returns `None` when the flows lack both signs or the iteration fails to converge.
+pub fn xirr(flows: &[(NaiveDate, f64)]) -> Option<f64> {
+    let has_negative = flows.iter().any(|(_, v)| *v < 0.0);
+    let has_positive = flows.iter().any(|(_, v)| *v > 0.0);
+    if !has_negative || !has_positive {
+        return None;
+    }
+
+    let start = flows.iter().map(|(d, _)| *d).min()?;
+    let years: Vec<(f64, f64)> = flows
+        .iter()
+        .map(|(d, v)| ((*d - start).num_days() as f64 / 365.0, *v))
+        .collect();
+
+    let mut rate: f64 = 0.1;
+    for _ in 0..MAX_ITERATIONS {
+        let (npv, d_npv) = years.iter().fold((0.0, 0.0), |(npv, d_npv), (t, v)| {
+            let discount = (1.0 + rate).powf(*t);
+            (npv + v / discount, d_npv - t * v / (discount * (1.0 + rate)))
+        });
+        if d_npv == 0.0 || !d_npv.is_finite() {
+            return None;
+        }
+        let next = rate - npv / d_npv;
+        if !next.is_finite() || next <= -1.0 {
+            return None;
+        }
+        if (next - rate).abs() < TOLERANCE {
+            return Some(next);
+        }
+        rate = next;
+    }
+    None
+}
+
+/// Parse a `YYYY-MM-DD=AMOUNT` cash flow argument.
+pub fn parse_flow(s: &str) -> Result<(NaiveDate, f64), NwError> {
+    let invalid = || NwError::InvalidCashFlow(s.to_string());
+    let (date, amount) = s.split_once('=').ok_or_else(invalid)?;
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| invalid())?;
+    let amount = amount.trim().parse::<f64>().map_err(|_| invalid())?;
+    Ok((date, amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_xirr_single_deposit_one_year() {
+        let flows = vec![(d("2021-01-01"), -1000.0), (d("2022-01-01"), 1100.0)];
+        let rate = xirr(&flows).unwrap();
+        assert!((rate - 0.10).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_xirr_known_example() {
+        // Reference example with a known XIRR of ~37.336%
+        let flows = vec![
+            (d("2008-01-01"), -10000.0),
+            (d("2008-03-01"), 2750.0),
+            (d("2008-10-30"), 4250.0),
+            (d("2009-02-15"), 3250.0),
+            (d("2009-04-01"), 2750.0),
+        ];
+        let rate = xirr(&flows).unwrap();
+        assert!((rate - 0.373362535).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_xirr_requires_both_signs() {
+        let flows = vec![(d("2021-01-01"), 1000.0), (d("2022-01-01"), 1100.0)];
+        assert!(xirr(&flows).is_none());
+    }
+
+    #[test]
+    fn test_parse_flow() {
+        let (date, amount) = parse_flow("2024-03-01=5000").unwrap();
+        assert_eq!(date, d("2024-03-01"));
+        assert_eq!(amount, 5000.0);
+        assert!(parse_flow("2024-03-01").is_err());
+        assert!(parse_flow("2024-13-01=5").is_err());
+    }
+}
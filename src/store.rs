@@ -1,7 +1,19 @@
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "encryption")]
+use std::sync::OnceLock;
+use crate::compute;
+#[cfg(feature = "encryption")]
+use crate::crypto;
 use crate::error::NwError;
 use crate::model::Portfolio;
+#[cfg(feature = "encryption")]
+use crate::prompt;
+
+/// Passphrase entered for this run, so load and save only ask once.
+#[cfg(feature = "encryption")]
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
 
 pub fn portfolio_path() -> Result<PathBuf, NwError> {
     let config_dir = dirs_next().ok_or(NwError::NoConfigDir)?;
@@ -9,41 +21,238 @@ pub fn portfolio_path() -> Result<PathBuf, NwError> {
 }
 
 fn dirs_next() -> Option<PathBuf> {
-    // Use $HOME/.config on Unix (XDG convention)
-    if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME") {
+    resolve_config_dir(
+        std::env::var("XDG_CONFIG_HOME").ok(),
+        std::env::var("HOME").ok(),
+    )
+}
+
+/// Use $XDG_CONFIG_HOME if set, else $HOME/.config (XDG convention).
+fn resolve_config_dir(xdg: Option<String>, home: Option<String>) -> Option<PathBuf> {
+    if let Some(xdg) = xdg {
         return Some(PathBuf::from(xdg));
     }
-    dirs_home().map(|h| h.join(".config"))
+    home.map(|h| PathBuf::from(h).join(".config"))
 }
 
-fn dirs_home() -> Option<PathBuf> {
-    std::env::var("HOME").ok().map(PathBuf::from)
+/// Encrypted counterpart of `portfolio.json`; when it exists it is used instead.
+#[cfg(feature = "encryption")]
+fn encrypted_path(path: &Path) -> PathBuf {
+    path.with_extension("json.enc")
 }
 
-pub fn load_portfolio() -> Result<Portfolio, NwError> {
+#[cfg(feature = "encryption")]
+pub fn is_encrypted() -> Result<bool, NwError> {
+    Ok(encrypted_path(&portfolio_path()?).exists())
+}
+
+#[cfg(feature = "encryption")]
+fn passphrase() -> Result<String, NwError> {
+    if let Some(p) = PASSPHRASE.get() {
+        return Ok(p.clone());
+    }
+    let p = prompt::prompt_passphrase()?;
+    Ok(PASSPHRASE.get_or_init(|| p).clone())
+}
+
+/// Switch to encrypted storage: write `portfolio.json.enc`, then delete the plaintext file.
+#[cfg(feature = "encryption")]
+pub fn enable_encryption(
+    portfolio: &mut Portfolio,
+    passphrase: String,
+) -> Result<PathBuf, NwError> {
     let path = portfolio_path()?;
+    let contents = serialize_for_save(portfolio, &path)?;
+    let enc_path = write_encrypted(&path, &contents, &passphrase)?;
+    let _ = PASSPHRASE.set(passphrase);
+    Ok(enc_path)
+}
 
-    if !path.exists() {
+/// Encrypt `contents` into the `.enc` file next to `path` in one atomic write, and only
+/// then delete the plaintext `path`. A failed write leaves the plaintext file as the
+/// portfolio, with no `.enc` that loading would prefer.
+#[cfg(feature = "encryption")]
+fn write_encrypted(path: &Path, contents: &str, passphrase: &str) -> Result<PathBuf, NwError> {
+    let enc_path = encrypted_path(path);
+    write_atomic(&enc_path, &crypto::encrypt(contents.as_bytes(), passphrase))?;
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| NwError::WriteFile {
+            path: path.display().to_string(),
+            source: e,
+        })?;
+    }
+    Ok(enc_path)
+}
+
+pub fn load_portfolio() -> Result<Portfolio, NwError> {
+    let path = portfolio_path()?;
+    if !path.exists() && !has_encrypted_copy(&path) {
         return Ok(Portfolio::default());
     }
+    read_portfolio_file(&path)
+}
 
-    let contents = fs::read_to_string(&path).map_err(|e| NwError::ReadFile {
+/// Whether an encrypted `.enc` counterpart of `path` exists.
+#[cfg(feature = "encryption")]
+fn has_encrypted_copy(path: &Path) -> bool {
+    encrypted_path(path).exists()
+}
+
+#[cfg(not(feature = "encryption"))]
+fn has_encrypted_copy(_path: &Path) -> bool {
+    false
+}
+
+/// Read and parse the portfolio file at `path`, decrypting its `.enc` counterpart instead
+/// when that exists. Used for the main file and for every include.
+fn read_portfolio_file(path: &Path) -> Result<Portfolio, NwError> {
+    #[cfg(feature = "encryption")]
+    {
+        let enc_path = encrypted_path(path);
+        if enc_path.exists() {
+            let data = fs::read(&enc_path).map_err(|e| NwError::ReadFile {
+                path: enc_path.display().to_string(),
+                source: e,
+            })?;
+            let decryption = || NwError::Decryption(enc_path.display().to_string());
+            let plaintext = crypto::decrypt(&data, &passphrase()?).ok_or_else(decryption)?;
+            let contents = String::from_utf8(plaintext).map_err(|_| decryption())?;
+            return parse_portfolio(&contents, &enc_path);
+        }
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| NwError::ReadFile {
         path: path.display().to_string(),
         source: e,
     })?;
 
-    serde_json::from_str(&contents).map_err(|e| NwError::MalformedJson {
+    parse_portfolio(&contents, path)
+}
+
+/// Merge the portfolio's `includes` into it, recursively. Paths are relative to the
+/// portfolio file's directory.
+pub fn resolve_includes(portfolio: &mut Portfolio) -> Result<(), NwError> {
+    merge_includes(portfolio, &portfolio_path()?, &mut Vec::new(), &mut HashSet::new())
+}
+
+/// `chain` holds the files currently being resolved, to detect cycles. `merged` holds every
+/// file already merged, so a file reached through two includes is only merged once.
+fn merge_includes(
+    portfolio: &mut Portfolio,
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    merged: &mut HashSet<PathBuf>,
+) -> Result<(), NwError> {
+    if portfolio.includes.is_empty() {
+        return Ok(());
+    }
+    chain.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+    let base = path.parent().unwrap_or(Path::new("."));
+    for include in portfolio.includes.clone() {
+        let include_path = base.join(&include);
+        let canonical = fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+        if chain.contains(&canonical) {
+            return Err(NwError::IncludeCycle(include_path.display().to_string()));
+        }
+        if !merged.insert(canonical) {
+            continue;
+        }
+        let mut included = read_portfolio_file(&include_path)?;
+        merge_includes(&mut included, &include_path, chain, merged)?;
+        portfolio.merge_include(included, &include_path.display().to_string())?;
+    }
+    chain.pop();
+    Ok(())
+}
+
+fn parse_portfolio(contents: &str, path: &Path) -> Result<Portfolio, NwError> {
+    #[cfg(feature = "json-comments")]
+    let stripped = strip_comments(contents);
+    #[cfg(feature = "json-comments")]
+    let contents = stripped.as_str();
+
+    serde_json::from_str(contents).map_err(|e| NwError::MalformedJson {
         path: path.display().to_string(),
         source: e,
     })
 }
 
+/// Blank out `//` line comments and `/* */` block comments outside of strings.
+/// Newlines are kept so parse errors still point at the right line.
+#[cfg(feature = "json-comments")]
+fn strip_comments(contents: &str) -> String {
+    let mut out = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.next_if(|&n| n != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for n in chars.by_ref() {
+                    if n == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && n == '/' {
+                        break;
+                    }
+                    prev = n;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
 pub fn save_portfolio(portfolio: &mut Portfolio) -> Result<(), NwError> {
     let path = portfolio_path()?;
+    let contents = serialize_for_save(portfolio, &path)?;
+
+    #[cfg(feature = "encryption")]
+    {
+        let enc_path = encrypted_path(&path);
+        if enc_path.exists() {
+            let data = crypto::encrypt(contents.as_bytes(), &passphrase()?);
+            return write_atomic(&enc_path, &data);
+        }
+    }
 
+    write_atomic(&path, contents.as_bytes())
+}
+
+/// Sort snapshots, refresh cached totals, make sure `path`'s directory exists, and
+/// serialize the portfolio as it will be written.
+fn serialize_for_save(portfolio: &mut Portfolio, path: &Path) -> Result<String, NwError> {
     // Enforce ascending date sort — single enforcement point
     portfolio.snapshots.sort_by(|a, b| a.date.cmp(&b.date));
 
+    // Refresh cached totals so the next load can detect hand edits or drift
+    let totals: Vec<Option<f64>> = portfolio
+        .snapshots
+        .iter()
+        .map(|s| compute::snapshot_total_usd(s, portfolio).ok())
+        .collect();
+    for (snapshot, total) in portfolio.snapshots.iter_mut().zip(totals) {
+        snapshot.cached_total_usd = total;
+    }
+
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| NwError::WriteFile {
             path: parent.display().to_string(),
@@ -51,18 +260,346 @@ pub fn save_portfolio(portfolio: &mut Portfolio) -> Result<(), NwError> {
         })?;
     }
 
-    let contents = serde_json::to_string_pretty(portfolio).map_err(|e| NwError::SerializeJson {
-        path: path.display().to_string(),
-        source: e,
-    })?;
-
-    let tmp_path = path.with_extension("json.tmp");
-    fs::write(&tmp_path, &contents).map_err(|e| NwError::WriteFile {
-        path: tmp_path.display().to_string(),
-        source: e,
-    })?;
-    fs::rename(&tmp_path, &path).map_err(|e| NwError::WriteFile {
+    serde_json::to_string_pretty(portfolio).map_err(|e| NwError::SerializeJson {
         path: path.display().to_string(),
         source: e,
     })
 }
+
+/// Write to a uniquely named temp file next to `path`, then rename it over `path`, so a
+/// crash never leaves a partial file. The temp file is removed if either step fails.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), NwError> {
+    let tmp_path = tmp_path_for(path);
+    let result = fs::write(&tmp_path, contents)
+        .map_err(|e| NwError::WriteFile {
+            path: tmp_path.display().to_string(),
+            source: e,
+        })
+        .and_then(|_| {
+            fs::rename(&tmp_path, path).map_err(|e| NwError::WriteFile {
+                path: path.display().to_string(),
+                source: e,
+            })
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// `.<name>.<pid>.<nanos>.tmp` in the same directory as `path`, so the rename never
+/// crosses filesystems and concurrent writers never share a temp file.
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---- resolve_config_dir ----
+
+    #[test]
+    fn test_resolve_config_dir_prefers_xdg() {
+        let dir = resolve_config_dir(Some("/tmp/xdg".to_string()), Some("/home/me".to_string()));
+        assert_eq!(
+            dir.unwrap().join("nw-tracker").join("portfolio.json"),
+            PathBuf::from("/tmp/xdg/nw-tracker/portfolio.json")
+        );
+    }
+
+    // ---- parse_portfolio ----
+
+    #[cfg(feature = "json-comments")]
+    #[test]
+    fn test_parse_portfolio_allows_comments() {
+        let contents = r#"{
+            // Brokerage first
+            "assets": [
+                { "id": "vti", "name": "VTI // total market", "category": "etf", "currency": "USD" }
+            ],
+            /* Monthly close-outs,
+               oldest first */
+            "snapshots": [
+                { "date": "2025-01-01", "rates": {}, "entries": [] }
+            ]
+        }"#;
+        let portfolio = parse_portfolio(contents, Path::new("portfolio.json")).unwrap();
+        assert_eq!(portfolio.assets[0].name, "VTI // total market");
+        assert_eq!(portfolio.snapshots.len(), 1);
+    }
+
+    #[cfg(feature = "json-comments")]
+    #[test]
+    fn test_strip_comments_keeps_escaped_quotes_and_lines() {
+        let stripped = strip_comments("{\"a\": \"x\\\" // y\"} // note\n/* a\nb */1");
+        assert_eq!(stripped, "{\"a\": \"x\\\" // y\"} \n\n1");
+    }
+
+    #[cfg(not(feature = "json-comments"))]
+    #[test]
+    fn test_parse_portfolio_rejects_comments() {
+        let result = parse_portfolio("{ // no\n }", Path::new("portfolio.json"));
+        assert!(matches!(result, Err(NwError::MalformedJson { .. })));
+    }
+
+    // ---- includes ----
+
+    fn write_json(path: &Path, value: serde_json::Value) {
+        fs::write(path, value.to_string()).unwrap();
+    }
+
+    fn asset_json(id: &str, currency: &str) -> serde_json::Value {
+        serde_json::json!({ "id": id, "name": id, "category": "etf", "currency": currency })
+    }
+
+    fn snapshot_json(
+        date: &str,
+        rates: serde_json::Value,
+        id: &str,
+        value: f64,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "date": date,
+            "rates": rates,
+            "entries": [{ "asset_id": id, "value": value }],
+        })
+    }
+
+    fn load_with_includes(path: &Path) -> Result<Portfolio, NwError> {
+        let mut portfolio = parse_portfolio(&fs::read_to_string(path).unwrap(), path)?;
+        merge_includes(&mut portfolio, path, &mut Vec::new(), &mut HashSet::new())?;
+        Ok(portfolio)
+    }
+
+    #[test]
+    fn test_includes_merge_two_files() {
+        let dir = scratch_dir("include-merge");
+        let no_rates = serde_json::json!({});
+        write_json(
+            &dir.join("personal.json"),
+            serde_json::json!({
+                "assets": [asset_json("vti", "USD")],
+                "snapshots": [
+                    snapshot_json("2025-01-01", no_rates.clone(), "vti", 100.0),
+                    snapshot_json("2025-02-01", no_rates, "vti", 110.0),
+                ],
+            }),
+        );
+        write_json(
+            &dir.join("household.json"),
+            serde_json::json!({
+                "includes": ["personal.json"],
+                "assets": [asset_json("house", "EUR")],
+                "snapshots": [
+                    snapshot_json("2025-02-01", serde_json::json!({ "EUR": 0.5 }), "house", 50.0),
+                ],
+            }),
+        );
+
+        let portfolio = load_with_includes(&dir.join("household.json")).unwrap();
+        let ids: Vec<&str> = portfolio.assets.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, vec!["house", "vti"]);
+        let dates: Vec<&str> = portfolio.snapshots.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-01", "2025-02-01"]);
+        // Same-date snapshots are combined: 110 USD + 50 EUR at 0.5
+        let total = compute::snapshot_total_usd(&portfolio.snapshots[1], &portfolio).unwrap();
+        assert_eq!(total, 210.0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_includes_detect_cycles() {
+        let dir = scratch_dir("include-cycle");
+        for (name, include) in [("a.json", "b.json"), ("b.json", "a.json")] {
+            write_json(
+                &dir.join(name),
+                serde_json::json!({ "assets": [], "snapshots": [], "includes": [include] }),
+            );
+        }
+        let result = load_with_includes(&dir.join("a.json"));
+        assert!(matches!(result, Err(NwError::IncludeCycle(_))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_includes_merge_shared_file_once() {
+        let dir = scratch_dir("include-diamond");
+        let no_rates = serde_json::json!({});
+        write_json(
+            &dir.join("shared.json"),
+            serde_json::json!({
+                "assets": [asset_json("vti", "USD")],
+                "snapshots": [snapshot_json("2025-01-01", no_rates, "vti", 100.0)],
+            }),
+        );
+        for name in ["a.json", "b.json"] {
+            write_json(
+                &dir.join(name),
+                serde_json::json!({ "assets": [], "snapshots": [], "includes": ["shared.json"] }),
+            );
+        }
+        write_json(
+            &dir.join("main.json"),
+            serde_json::json!({ "assets": [], "snapshots": [], "includes": ["a.json", "b.json"] }),
+        );
+        let portfolio = load_with_includes(&dir.join("main.json")).unwrap();
+        assert_eq!(portfolio.assets.len(), 1);
+        assert_eq!(portfolio.snapshots[0].entries.len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_includes_read_encrypted_files() {
+        let dir = scratch_dir("include-encrypted");
+        let _ = PASSPHRASE.set("hunter2".to_string());
+        let passphrase = PASSPHRASE.get().unwrap();
+        let personal = serde_json::json!({ "assets": [asset_json("vti", "USD")], "snapshots": [] });
+        let data = crypto::encrypt(personal.to_string().as_bytes(), passphrase);
+        fs::write(encrypted_path(&dir.join("personal.json")), data).unwrap();
+        write_json(
+            &dir.join("main.json"),
+            serde_json::json!({ "assets": [], "snapshots": [], "includes": ["personal.json"] }),
+        );
+        let portfolio = load_with_includes(&dir.join("main.json")).unwrap();
+        assert_eq!(portfolio.assets[0].id, "vti");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_includes_reject_duplicate_asset_ids() {
+        let dir = scratch_dir("include-dup");
+        write_json(
+            &dir.join("a.json"),
+            serde_json::json!({ "assets": [asset_json("vti", "USD")], "snapshots": [] }),
+        );
+        write_json(
+            &dir.join("main.json"),
+            serde_json::json!({
+                "assets": [asset_json("vti", "USD")],
+                "snapshots": [],
+                "includes": ["a.json"],
+            }),
+        );
+        let result = load_with_includes(&dir.join("main.json"));
+        assert!(matches!(result, Err(NwError::IncludeConflict { id, .. }) if id == "vti"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // ---- write_atomic ----
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nw-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn leftover_tmp_files(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp"))
+            .count()
+    }
+
+    #[test]
+    fn test_serialize_for_save_refreshes_cached_totals() {
+        use crate::model::{Asset, Snapshot};
+        let dir = scratch_dir("cache");
+        let mut portfolio = Portfolio {
+            assets: vec![Asset::new("cash", "Cash", "bank", "EUR")],
+            snapshots: vec![
+                Snapshot::new("2025-02-01").with_rate("EUR", 0.5).with_entry("cash", 100.0),
+                // No EUR rate, so there is no total to cache
+                Snapshot { cached_total_usd: Some(1.0), ..Snapshot::new("2025-01-01") }
+                    .with_entry("cash", 50.0),
+            ],
+            ..Default::default()
+        };
+        serialize_for_save(&mut portfolio, &dir.join("portfolio.json")).unwrap();
+        let cached: Vec<(&str, Option<f64>)> = portfolio
+            .snapshots
+            .iter()
+            .map(|s| (s.date.as_str(), s.cached_total_usd))
+            .collect();
+        assert_eq!(cached, vec![("2025-01-01", None), ("2025-02-01", Some(200.0))]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = scratch_dir("replace");
+        let path = dir.join("portfolio.json");
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        assert_eq!(leftover_tmp_files(&dir), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_write_encrypted_replaces_plaintext() {
+        let dir = scratch_dir("encrypt");
+        let path = dir.join("portfolio.json");
+        fs::write(&path, "{}").unwrap();
+
+        let enc_path = write_encrypted(&path, "{}", "hunter2").unwrap();
+        assert!(!path.exists());
+        let plaintext = crypto::decrypt(&fs::read(&enc_path).unwrap(), "hunter2").unwrap();
+        assert_eq!(plaintext, b"{}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_write_encrypted_failure_keeps_plaintext() {
+        let dir = scratch_dir("encrypt-failure");
+        let path = dir.join("portfolio.json");
+        fs::write(&path, "{}").unwrap();
+        // A non-empty directory where the .enc file should go makes the write fail
+        fs::create_dir_all(encrypted_path(&path).join("occupied")).unwrap();
+
+        let result = write_encrypted(&path, "{}", "hunter2");
+        assert!(matches!(result, Err(NwError::WriteFile { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        assert!(encrypted_path(&path).is_dir());
+        assert_eq!(leftover_tmp_files(&dir), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_failure_leaves_no_tmp_file() {
+        let dir = scratch_dir("failure");
+        // A non-empty directory where the file should go makes the rename fail
+        let path = dir.join("portfolio.json");
+        fs::create_dir_all(path.join("occupied")).unwrap();
+
+        let result = write_atomic(&path, b"data");
+        assert!(matches!(result, Err(NwError::WriteFile { .. })));
+        assert_eq!(leftover_tmp_files(&dir), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tmp_path_is_hidden_sibling() {
+        let tmp = tmp_path_for(Path::new("/data/nw-tracker/portfolio.json"));
+        assert_eq!(tmp.parent(), Some(Path::new("/data/nw-tracker")));
+        let name = tmp.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with(".portfolio.json."));
+        assert!(name.ends_with(".tmp"));
+    }
+
+    #[test]
+    fn test_resolve_config_dir_falls_back_to_home() {
+        let dir = resolve_config_dir(None, Some("/home/me".to_string()));
+        assert_eq!(dir.unwrap(), PathBuf::from("/home/me/.config"));
+        assert!(resolve_config_dir(None, None).is_none());
+    }
+}